@@ -0,0 +1,42 @@
+// 测试 hosts 延迟的开销：单次 tcp 连接失败的耗时，以及测试不同数量的 hosts 时 `to_stats_and_reset` 的耗时
+// 用于评估是否需要并行测试 hosts
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use stats::{run_test_tcp, HostPingInfo, PingMethod, PingProtocol, RequestStats, StatsBase};
+use std::net::TcpListener;
+use std::time::Duration;
+
+fn bench_run_test_tcp(c: &mut Criterion) {
+    // 端口没有监听时立即失败
+    c.bench_function("run_test_tcp closed port", |b| {
+        b.iter(|| run_test_tcp(black_box("127.0.0.1"), 80, Duration::from_secs(1)).is_err())
+    });
+}
+
+fn bench_to_stats_and_reset(c: &mut Criterion) {
+    // 监听随机端口，接受连接后立即关闭
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || for _stream in listener.incoming() {});
+
+    let stats = RequestStats::new();
+    let base = StatsBase::builder().server_name("server").build();
+    let mut group = c.benchmark_group("to_stats_and_reset");
+    for hosts in [1, 5, 10] {
+        let info = HostPingInfo {
+            hosts: vec!["127.0.0.1".to_string(); hosts],
+            port,
+            timeout: Duration::from_secs(1),
+            samples: 1,
+            method: PingMethod::Tcp,
+            protocol: PingProtocol::Tcp,
+            precision: 3,
+        };
+        group.bench_with_input(BenchmarkId::from_parameter(hosts), &info, |b, info| {
+            b.iter(|| stats.to_stats_and_reset(&base, Some(info.clone())))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_run_test_tcp, bench_to_stats_and_reset);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+// 比较直接构造 JSON 和通过 serde 序列化获取 `Stats` 字段的开销
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use stats::{RequestInfo, RequestResult, RequestStats, StatsBase};
+
+fn bench_stats_value(c: &mut Criterion) {
+    let stats = RequestStats::new();
+    for i in 0..100 {
+        let result = match i % 10 {
+            0 => RequestResult::TimeoutError(Some("read timeout".into())),
+            _ => RequestResult::Successful,
+        };
+        stats.update_stats_with(
+            0,
+            1000,
+            200,
+            result,
+            &RequestInfo {
+                request_bytes: 100,
+                response_bytes: 2000,
+                proxy_id: Some("proxy-a"),
+                ..Default::default()
+            },
+        );
+    }
+    let base = StatsBase::builder().server_name("server").build();
+    let snapshot = stats.snapshot(&base);
+
+    c.bench_function("serde_json::to_value", |b| {
+        b.iter(|| {
+            let value = serde_json::to_value(black_box(&snapshot)).unwrap();
+            value["errorRate"].as_f64()
+        })
+    });
+
+    c.bench_function("Value::from", |b| {
+        b.iter(|| {
+            let value = serde_json::Value::from(black_box(&snapshot));
+            value["errorRate"].as_f64()
+        })
+    });
+}
+
+criterion_group!(benches, bench_stats_value);
+criterion_main!(benches);
@@ -0,0 +1,14 @@
+// 开启 grpc feature 时使用 prost-build 生成 proto/stats.proto 的代码
+// 使用 protoc-bin-vendored 提供的 protoc，不需要另外安装
+fn main() {
+    // 不输出时 cargo 在包内任何文件变化后都会重新运行
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/stats.proto");
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("找不到 protoc");
+        std::env::set_var("PROTOC", protoc);
+        prost_build::compile_protos(&["proto/stats.proto"], &["proto"])
+            .expect("生成 proto/stats.proto 的代码失败");
+    }
+}
@@ -0,0 +1,142 @@
+// 会话汇总
+// 累计每个周期推送的统计快照，生成整个会话的汇总数据
+use crate::{round_to, OwnedStats, DEFAULT_STATS_PRECISION, MAX_STATS_PRECISION};
+use serde::Serialize;
+
+// 会话汇总数据
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSummary {
+    // 累计的统计周期数
+    pub cycles: u64,
+    // 所有周期的请求总数
+    pub total_requests_all_cycles: i64,
+    // 按请求数加权的平均错误率
+    pub weighted_avg_error_rate: f64,
+    // 运行时长（秒）
+    pub total_runtime_seconds: i64,
+    // 单个周期的最大每秒请求数
+    pub peak_rps: f64,
+    // 单个周期的最大错误率
+    pub peak_error_rate: f64,
+}
+
+pub struct StatsAccumulator {
+    cycles: u64,
+    total_requests: i64,
+    // 错误请求数；由每个周期的错误率和请求数计算
+    total_errors: f64,
+    runtime_seconds: i64,
+    peak_rps: f64,
+    peak_error_rate: f64,
+    // 平均错误率保留的小数位数
+    precision: u8,
+}
+
+impl Default for StatsAccumulator {
+    fn default() -> Self {
+        Self {
+            cycles: 0,
+            total_requests: 0,
+            total_errors: 0.0,
+            runtime_seconds: 0,
+            peak_rps: 0.0,
+            peak_error_rate: 0.0,
+            precision: DEFAULT_STATS_PRECISION,
+        }
+    }
+}
+
+impl StatsAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置平均错误率保留的小数位数；最多 15 位
+    pub fn set_precision(&mut self, precision: u8) {
+        self.precision = precision.min(MAX_STATS_PRECISION);
+    }
+
+    /// 累计一个周期的统计快照
+    pub fn push(&mut self, stats: &OwnedStats) {
+        self.cycles += 1;
+        self.total_requests += stats.total_requests;
+        self.total_errors += stats.error_rate * stats.total_requests as f64;
+        self.runtime_seconds = self.runtime_seconds.max(stats.runtime_duration);
+        self.peak_rps = self.peak_rps.max(stats.requests_per_second);
+        self.peak_error_rate = self.peak_error_rate.max(stats.error_rate);
+    }
+
+    /// 生成会话汇总数据
+    pub fn summarize(&self) -> SessionSummary {
+        let weighted_avg_error_rate = if self.total_requests > 0 {
+            self.total_errors / self.total_requests as f64
+        } else {
+            0.0
+        };
+
+        SessionSummary {
+            cycles: self.cycles,
+            total_requests_all_cycles: self.total_requests,
+            weighted_avg_error_rate: round_to(weighted_avg_error_rate, self.precision),
+            total_runtime_seconds: self.runtime_seconds,
+            peak_rps: self.peak_rps,
+            peak_error_rate: self.peak_error_rate,
+        }
+    }
+
+    /// 清空累计数据；保留小数位数的设置
+    pub fn reset(&mut self) {
+        *self = Self {
+            precision: self.precision,
+            ..Self::default()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Stats;
+
+    fn cycle(total_requests: i64, error_rate: f64, rps: f64, runtime: i64) -> OwnedStats {
+        Stats {
+            total_requests,
+            error_rate,
+            requests_per_second: rps,
+            runtime_duration: runtime,
+            ..Default::default()
+        }
+        .into_owned()
+    }
+
+    #[test]
+    fn test_accumulator() {
+        let mut acc = StatsAccumulator::new();
+        assert_eq!(acc.summarize(), SessionSummary::default());
+
+        acc.push(&cycle(100, 0.1, 10.0, 10));
+        acc.push(&cycle(300, 0.5, 30.0, 20));
+        acc.push(&cycle(0, 0.0, 0.0, 30));
+
+        let summary = acc.summarize();
+        assert_eq!(summary.cycles, 3);
+        assert_eq!(summary.total_requests_all_cycles, 400);
+        assert_eq!(summary.weighted_avg_error_rate, 0.4);
+        assert_eq!(summary.total_runtime_seconds, 30);
+        assert_eq!(summary.peak_rps, 30.0);
+        assert_eq!(summary.peak_error_rate, 0.5);
+
+        acc.reset();
+        assert_eq!(acc.summarize().cycles, 0);
+
+        // 平均错误率按设置的精度保留小数
+        acc.set_precision(1);
+        acc.push(&cycle(100, 0.1, 10.0, 10));
+        acc.push(&cycle(200, 0.2, 10.0, 10));
+        assert_eq!(acc.summarize().weighted_avg_error_rate, 0.2);
+        acc.reset();
+        acc.push(&cycle(300, 0.123, 10.0, 10));
+        assert_eq!(acc.summarize().weighted_avg_error_rate, 0.1);
+    }
+}
@@ -0,0 +1,154 @@
+// 统计数据告警
+// 每次推送统计数据之后检查告警规则，触发时回调
+use crate::Stats;
+use serde::{Deserialize, Serialize};
+
+/// 告警回调；参数为触发的规则和当前值
+pub type AlertCallback = Box<dyn Fn(&AlertRule, f64) + Send + Sync>;
+
+// 告警配置
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct AlertConfig {
+    pub rules: Vec<AlertRule>,
+}
+
+impl AlertConfig {
+    /// 检查所有规则，返回触发的规则和当前值
+    pub fn check(&self, stats: &Stats) -> Vec<(&AlertRule, f64)> {
+        self.rules
+            .iter()
+            .filter_map(|rule| rule.check(stats).map(|v| (rule, v)))
+            .collect()
+    }
+}
+
+// 告警规则；`field` 的值和 `threshold` 比较满足 `comparator` 时触发
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AlertRule {
+    pub field: AlertField,
+    pub threshold: f64,
+    pub comparator: Comparator,
+}
+
+impl AlertRule {
+    /// 规则触发时返回当前值
+    pub fn check(&self, stats: &Stats) -> Option<f64> {
+        let value = self.field.value(stats);
+        self.comparator
+            .compare(value, self.threshold)
+            .then_some(value)
+    }
+}
+
+// 可以告警的统计字段
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum AlertField {
+    // 错误率
+    ErrorRate,
+    // 平均请求延迟（毫秒）
+    AverageLatency,
+    // 缓存命中率
+    CacheHitRate,
+    // 请求总次数
+    TotalRequests,
+    // 每秒请求数
+    RequestsPerSecond,
+    // CPU 使用率（百分比）
+    CpuUsage,
+    // 统计周期内最长的连续失败次数
+    ConsecutiveFailures,
+}
+
+impl AlertField {
+    /// 获取字段的当前值
+    pub fn value(&self, stats: &Stats) -> f64 {
+        match self {
+            AlertField::ErrorRate => stats.error_rate(),
+            AlertField::AverageLatency => stats.average_latency_ms(),
+            AlertField::CacheHitRate => stats.cache_hit_rate(),
+            AlertField::TotalRequests => stats.total_requests() as f64,
+            AlertField::RequestsPerSecond => stats.requests_per_second(),
+            AlertField::CpuUsage => stats.cpu_usage(),
+            AlertField::ConsecutiveFailures => stats.max_consecutive_failures as f64,
+        }
+    }
+}
+
+// 比较方式
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Comparator {
+    // 大于
+    GreaterThan,
+    // 大于等于
+    GreaterOrEqual,
+    // 小于
+    LessThan,
+    // 小于等于
+    LessOrEqual,
+}
+
+impl Comparator {
+    pub fn compare(&self, value: f64, threshold: f64) -> bool {
+        match self {
+            Comparator::GreaterThan => value > threshold,
+            Comparator::GreaterOrEqual => value >= threshold,
+            Comparator::LessThan => value < threshold,
+            Comparator::LessOrEqual => value <= threshold,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alert_check() {
+        let stats = Stats {
+            error_rate: 0.15,
+            average_request_latency: 6000.0,
+            cache_hit_rate: 0.5,
+            total_requests: 100,
+            ..Default::default()
+        };
+
+        let config = AlertConfig {
+            rules: vec![
+                AlertRule {
+                    field: AlertField::ErrorRate,
+                    threshold: 0.1,
+                    comparator: Comparator::GreaterThan,
+                },
+                AlertRule {
+                    field: AlertField::AverageLatency,
+                    threshold: 5000.0,
+                    comparator: Comparator::GreaterOrEqual,
+                },
+                AlertRule {
+                    field: AlertField::TotalRequests,
+                    threshold: 10.0,
+                    comparator: Comparator::LessThan,
+                },
+            ],
+        };
+
+        let triggered = config.check(&stats);
+        assert_eq!(triggered.len(), 2);
+        assert_eq!(triggered[0].0.field, AlertField::ErrorRate);
+        assert_eq!(triggered[0].1, 0.15);
+        assert_eq!(triggered[1].0.field, AlertField::AverageLatency);
+        assert_eq!(triggered[1].1, 6000.0);
+    }
+
+    #[test]
+    fn test_alert_config_deserialize() {
+        let config: AlertConfig = serde_json::from_str(
+            r#"{"rules":[{"field":"errorRate","threshold":0.1,"comparator":"greaterThan"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.rules[0].field, AlertField::ErrorRate);
+        assert_eq!(config.rules[0].comparator, Comparator::GreaterThan);
+    }
+}
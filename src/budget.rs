@@ -0,0 +1,133 @@
+// 错误预算
+// 在多个上报周期内累计请求数和错误数，判断错误预算是否耗尽
+use crate::{get_now_millis, Stats};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// 错误预算配置
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ErrorBudget {
+    // 允许的错误率，如 0.001 表示 0.1%
+    pub allowed_error_rate: f64,
+    // 统计窗口，如一周
+    pub window: Duration,
+}
+
+// 单个周期的记录
+struct BudgetEntry {
+    // 记录时间（毫秒级时间戳）
+    timestamp: i64,
+    total_requests: i64,
+    error_count: i64,
+}
+
+pub struct ErrorBudgetTracker {
+    budget: ErrorBudget,
+    entries: Mutex<VecDeque<BudgetEntry>>,
+}
+
+impl ErrorBudgetTracker {
+    pub fn new(budget: ErrorBudget) -> Self {
+        Self {
+            budget,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    pub fn budget(&self) -> &ErrorBudget {
+        &self.budget
+    }
+
+    /// 记录一个周期的请求数和错误数
+    pub fn record(&self, total_requests: i64, error_count: i64) {
+        self.record_at(get_now_millis(), total_requests, error_count);
+    }
+
+    /// 记录一个周期的统计数据
+    pub fn record_stats(&self, stats: &Stats) {
+        self.record(stats.total_requests, stats.exception_types.total());
+    }
+
+    fn record_at(&self, timestamp: i64, total_requests: i64, error_count: i64) {
+        let mut entries = self.entries.lock();
+        entries.push_back(BudgetEntry {
+            timestamp,
+            total_requests,
+            error_count,
+        });
+        Self::evict(&mut entries, timestamp, self.budget.window);
+    }
+
+    // 清理窗口外的记录
+    fn evict(entries: &mut VecDeque<BudgetEntry>, now: i64, window: Duration) {
+        let window_start = now - window.as_millis() as i64;
+        while entries.front().is_some_and(|e| e.timestamp < window_start) {
+            entries.pop_front();
+        }
+    }
+
+    /// 窗口内的请求总数和错误总数
+    pub fn totals(&self) -> (i64, i64) {
+        let mut entries = self.entries.lock();
+        Self::evict(&mut entries, get_now_millis(), self.budget.window);
+        entries.iter().fold((0, 0), |(total, errors), e| {
+            (total + e.total_requests, errors + e.error_count)
+        })
+    }
+
+    /// 剩余的错误预算比例（0.0 - 1.0）；窗口内没有请求时为 1.0
+    pub fn remaining_budget(&self) -> f64 {
+        let (total, errors) = self.totals();
+        let allowed = self.budget.allowed_error_rate * total as f64;
+        if allowed <= 0.0 {
+            return if errors > 0 { 0.0 } else { 1.0 };
+        }
+
+        ((allowed - errors as f64) / allowed).clamp(0.0, 1.0)
+    }
+
+    /// 错误预算是否已经耗尽
+    pub fn is_budget_exhausted(&self) -> bool {
+        self.remaining_budget() <= 0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_budget() {
+        let tracker = ErrorBudgetTracker::new(ErrorBudget {
+            allowed_error_rate: 0.01,
+            window: Duration::from_secs(3600),
+        });
+        assert_eq!(tracker.remaining_budget(), 1.0);
+        assert!(!tracker.is_budget_exhausted());
+
+        tracker.record(1000, 5);
+        tracker.record(1000, 5);
+        assert_eq!(tracker.totals(), (2000, 10));
+        assert_eq!(tracker.remaining_budget(), 0.5);
+
+        tracker.record(0, 20);
+        assert_eq!(tracker.remaining_budget(), 0.0);
+        assert!(tracker.is_budget_exhausted());
+    }
+
+    #[test]
+    fn test_error_budget_evict() {
+        let tracker = ErrorBudgetTracker::new(ErrorBudget {
+            allowed_error_rate: 0.01,
+            window: Duration::from_secs(60),
+        });
+
+        let now = get_now_millis();
+        tracker.record_at(now - 120_000, 100, 100);
+        tracker.record_at(now, 1000, 0);
+        assert_eq!(tracker.totals(), (1000, 0));
+        assert_eq!(tracker.remaining_budget(), 1.0);
+    }
+}
@@ -1,51 +1,666 @@
-use std::fs::{self, DirEntry};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, DirEntry, Metadata};
 use std::io;
+use std::ops::Add;
 use std::path::Path;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
 
-pub fn clean_old_files(folder_path: &str, max_ts: Duration) -> anyhow::Result<()> {
+/// 判断文件是否过期时使用的时间
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanMode {
+    // 文件创建时间；部分 linux 文件系统不支持，不支持时使用文件修改时间
+    CreationTime,
+    // 文件修改时间
+    ModificationTime,
+    // 文件访问时间
+    AccessTime,
+}
+
+// 文件系统不支持创建时间时只警告一次
+static CREATION_TIME_FALLBACK_WARNED: AtomicBool = AtomicBool::new(false);
+
+impl CleanMode {
+    fn file_time(&self, metadata: &Metadata) -> io::Result<SystemTime> {
+        match self {
+            CleanMode::CreationTime => metadata.created().or_else(|err| {
+                if !CREATION_TIME_FALLBACK_WARNED.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "文件系统不支持创建时间，使用文件修改时间判断是否过期：{}",
+                        err
+                    );
+                }
+                metadata.modified()
+            }),
+            CleanMode::ModificationTime => metadata.modified(),
+            CleanMode::AccessTime => metadata.accessed(),
+        }
+    }
+}
+
+/// 文件清理配置；按时间和按大小清理可以同时配置
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CleanConfig {
+    // 需要清理的目录
+    pub paths: Vec<String>,
+    // 过期时间；根据文件修改时间来判断；None 表示不按时间清理
+    pub max_age: Option<Duration>,
+    // 目录下文件总大小上限（字节）；超过时从最旧的文件开始删除；None 表示不按大小清理
+    pub max_total_bytes: Option<u64>,
+    // 只打印不删除
+    pub dry_run: bool,
+    // 只清理这些后缀的文件（不区分大小写，不带 `.`）；None 表示不过滤
+    pub allowed_extensions: Option<Vec<String>>,
+}
+
+impl CleanConfig {
+    /// 按配置清理所有目录；先按时间清理，再按大小清理；单个目录失败不影响其他目录
+    /// 返回每个目录的清理结果
+    pub fn run(&self) -> Vec<(String, anyhow::Result<CleanStats>)> {
+        let exts: Option<Vec<&str>> = self
+            .allowed_extensions
+            .as_ref()
+            .map(|exts| exts.iter().map(String::as_str).collect());
+
+        self.paths
+            .iter()
+            .map(|p| (p.clone(), self.run_path(p, exts.as_deref())))
+            .collect()
+    }
+
+    fn run_path(&self, p: &str, exts: Option<&[&str]>) -> anyhow::Result<CleanStats> {
+        let mut stats = CleanStats::default();
+        if let Some(max_age) = self.max_age {
+            if self.dry_run {
+                clean_old_files_dry_run(p, max_age, exts)?;
+            } else {
+                stats = stats + clean_old_files(p, max_age, exts)?;
+            }
+        }
+
+        if let Some(max_total_bytes) = self.max_total_bytes {
+            stats = stats + clean_by_size_inner(p, max_total_bytes, exts, self.dry_run)?;
+        }
+        Ok(stats)
+    }
+}
+
+/// 清理结果统计
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CleanStats {
+    // 删除的文件数
+    pub files_deleted: u64,
+    // 跳过的文件数（未过期、后缀不匹配或无法读取）
+    pub files_skipped: u64,
+    // 删除的空目录数
+    pub dirs_removed: u64,
+    // 释放的空间（字节）
+    pub bytes_freed: u64,
+}
+
+impl Add for CleanStats {
+    type Output = CleanStats;
+
+    fn add(self, rhs: CleanStats) -> CleanStats {
+        CleanStats {
+            files_deleted: self.files_deleted + rhs.files_deleted,
+            files_skipped: self.files_skipped + rhs.files_skipped,
+            dirs_removed: self.dirs_removed + rhs.dirs_removed,
+            bytes_freed: self.bytes_freed + rhs.bytes_freed,
+        }
+    }
+}
+
+/// 删除目录下的过期文件；根据文件修改时间来判断
+/// `allowed_extensions` 不为空时只清理对应后缀的文件
+pub fn clean_old_files(
+    folder_path: &str,
+    max_ts: Duration,
+    allowed_extensions: Option<&[&str]>,
+) -> anyhow::Result<CleanStats> {
+    clean_old_files_with_mode(
+        folder_path,
+        max_ts,
+        CleanMode::ModificationTime,
+        allowed_extensions,
+    )
+}
+
+/// 删除目录下的过期文件；根据 `mode` 指定的文件时间来判断
+pub fn clean_old_files_with_mode(
+    folder_path: &str,
+    max_age: Duration,
+    mode: CleanMode,
+    allowed_extensions: Option<&[&str]>,
+) -> anyhow::Result<CleanStats> {
     let folder = Path::new(folder_path);
     if !folder.is_dir() {
         return Err(anyhow::anyhow!("Provided path is not a directory"));
     }
 
-    let now = std::time::SystemTime::now();
+    let now = SystemTime::now();
+    let mut stats = CleanStats::default();
 
     for entry in fs::read_dir(folder)? {
         let entry = entry?;
-        if let Ok(metadata) = entry.metadata() {
-            if let Ok(created_time) = metadata.created() {
-                if now.duration_since(created_time)?.gt(&max_ts) {
-                    delete_file(&entry)?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            debug!("跳过目录：{:?}", entry.path());
+            continue;
+        }
+        if !matches_extension(&entry.path(), allowed_extensions) {
+            stats.files_skipped += 1;
+            continue;
+        }
+        // 符号链接使用链接本身的时间，不跟随到目标文件；失效的链接也能被清理
+        let expired = fs::symlink_metadata(entry.path())
+            .ok()
+            .and_then(|metadata| {
+                let file_time = mode.file_time(&metadata).ok()?;
+                Some((metadata.len(), file_time))
+            });
+        match expired {
+            // 文件时间晚于当前时间时按未过期处理
+            Some((size, file_time))
+                if now.duration_since(file_time).unwrap_or_default() > max_age =>
+            {
+                if delete_file(&entry)? {
+                    stats.files_deleted += 1;
+                    stats.bytes_freed += size;
+                } else {
+                    stats.files_skipped += 1;
                 }
             }
+            _ => stats.files_skipped += 1,
+        }
+    }
+
+    Ok(stats)
+}
+
+/// 只打印会被 `clean_old_files` 删除的文件，不做任何删除操作
+/// 读取文件信息失败时会返回错误，用于提前检查路径和权限配置
+pub fn clean_old_files_dry_run(
+    folder_path: &str,
+    max_age: Duration,
+    allowed_extensions: Option<&[&str]>,
+) -> anyhow::Result<()> {
+    let folder = Path::new(folder_path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Provided path is not a directory"));
+    }
+
+    let now = SystemTime::now();
+
+    for entry in fs::read_dir(folder)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let modified = metadata.modified()?;
+
+        if metadata.is_file()
+            && matches_extension(&entry.path(), allowed_extensions)
+            && now.duration_since(modified)?.gt(&max_age)
+        {
+            info!("Would delete: {:?}", entry.path());
         }
     }
 
     Ok(())
 }
 
-fn delete_file(entry: &DirEntry) -> io::Result<()> {
+/// 递归删除目录下的过期文件，并自底向上删除空的子目录；根据文件修改时间来判断
+/// 无法读取的文件会被跳过
+pub fn clean_old_files_recursive(
+    folder_path: &str,
+    max_age: Duration,
+) -> anyhow::Result<CleanStats> {
+    let folder = Path::new(folder_path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Provided path is not a directory"));
+    }
+
+    let now = SystemTime::now();
+    let mut stats = CleanStats::default();
+
+    for entry in WalkDir::new(folder).min_depth(1) {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("跳过无法读取的文件：{}", err);
+                continue;
+            }
+        };
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let (size, modified) = match entry
+            .metadata()
+            .map_err(io::Error::from)
+            .and_then(|m| Ok((m.len(), m.modified()?)))
+        {
+            Ok(t) => t,
+            Err(err) => {
+                warn!("跳过无法读取的文件 {:?}：{}", entry.path(), err);
+                stats.files_skipped += 1;
+                continue;
+            }
+        };
+
+        if now.duration_since(modified).unwrap_or_default() > max_age {
+            info!("Deleting timeout file: {:?}", entry.path());
+            match fs::remove_file(entry.path()) {
+                Ok(()) => {
+                    stats.files_deleted += 1;
+                    stats.bytes_freed += size;
+                }
+                Err(err) => {
+                    warn!("删除文件 {:?} 失败：{}", entry.path(), err);
+                    stats.files_skipped += 1;
+                }
+            }
+        } else {
+            stats.files_skipped += 1;
+        }
+    }
+
+    // contents_first 保证子目录先于父目录处理
+    for entry in WalkDir::new(folder).min_depth(1).contents_first(true) {
+        let Ok(entry) = entry else {
+            continue;
+        };
+
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+
+        let is_empty = fs::read_dir(entry.path())
+            .map(|mut dir| dir.next().is_none())
+            .unwrap_or(false);
+
+        if is_empty {
+            match fs::remove_dir(entry.path()) {
+                Ok(()) => stats.dirs_removed += 1,
+                Err(err) => warn!("删除目录 {:?} 失败：{}", entry.path(), err),
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// 目录下文件总大小超过 `max_total_bytes` 时，按修改时间从旧到新删除文件，直到总大小低于上限
+/// 无法读取或者删除失败的文件会被跳过
+pub fn clean_by_size(folder_path: &str, max_total_bytes: u64) -> anyhow::Result<CleanStats> {
+    clean_by_size_inner(folder_path, max_total_bytes, None, false)
+}
+
+fn clean_by_size_inner(
+    folder_path: &str,
+    max_total_bytes: u64,
+    allowed_extensions: Option<&[&str]>,
+    dry_run: bool,
+) -> anyhow::Result<CleanStats> {
+    let folder = Path::new(folder_path);
+    if !folder.is_dir() {
+        return Err(anyhow::anyhow!("Provided path is not a directory"));
+    }
+
+    let mut total_bytes = 0;
+    // 可以删除的文件：路径、大小、修改时间
+    let mut candidates = vec![];
+    let mut stats = CleanStats::default();
+
+    for entry in fs::read_dir(folder)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("跳过无法读取的文件：{}", err);
+                continue;
+            }
+        };
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(err) => {
+                warn!("跳过无法读取的文件 {:?}：{}", entry.path(), err);
+                stats.files_skipped += 1;
+                continue;
+            }
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+
+        total_bytes += metadata.len();
+
+        if !matches_extension(&entry.path(), allowed_extensions) {
+            continue;
+        }
+        match metadata.modified() {
+            Ok(modified) => candidates.push((entry.path(), metadata.len(), modified)),
+            Err(err) => {
+                warn!("跳过无法读取修改时间的文件 {:?}：{}", entry.path(), err);
+                stats.files_skipped += 1;
+            }
+        }
+    }
+
+    candidates.sort_by_key(|(_, _, modified)| *modified);
+
+    for (path, size, _) in candidates {
+        if total_bytes <= max_total_bytes {
+            break;
+        }
+
+        if dry_run {
+            info!("Would delete: {:?}", path);
+        } else {
+            info!("Deleting oversize file: {:?}", path);
+            if let Err(err) = fs::remove_file(&path) {
+                warn!("删除文件 {:?} 失败：{}", path, err);
+                stats.files_skipped += 1;
+                continue;
+            }
+            stats.files_deleted += 1;
+            stats.bytes_freed += size;
+        }
+        total_bytes -= size;
+    }
+
+    Ok(stats)
+}
+
+/// 判断文件后缀是否在允许列表中；None 表示全部允许
+fn matches_extension(path: &Path, allowed_extensions: Option<&[&str]>) -> bool {
+    let Some(allowed) = allowed_extensions else {
+        return true;
+    };
+
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| {
+            allowed
+                .iter()
+                .any(|a| a.trim_start_matches('.').eq_ignore_ascii_case(ext))
+        })
+        .unwrap_or(false)
+}
+
+/// 删除普通文件或符号链接；返回是否删除
+fn delete_file(entry: &DirEntry) -> io::Result<bool> {
     let path = entry.path();
-    if path.is_file() {
+    let file_type = entry.file_type()?;
+    if file_type.is_file() || file_type.is_symlink() {
         println!("Deleting timeout file: {:?}", path);
         fs::remove_file(path)?;
+        return Ok(true);
     }
-    Ok(())
+    Ok(false)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use std::fs::File;
+    use std::path::PathBuf;
     use std::time::Duration;
 
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("stats_clean_{name}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn create_file(dir: &Path, name: &str, age: Duration) -> PathBuf {
+        let path = dir.join(name);
+        let file = File::create(&path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+        path
+    }
+
     #[test]
     fn test_clean_old_files() {
+        let dir = test_dir("mtime");
+        let old = create_file(&dir, "old.json", Duration::from_secs(120));
+        let new = create_file(&dir, "new.json", Duration::ZERO);
+        fs::write(&old, [0u8; 10]).unwrap();
+        File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(120))
+            .unwrap();
+
+        // 修改时间晚于当前时间的文件不影响其他文件的清理
+        let future = dir.join("future.json");
+        File::create(&future)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(3600))
+            .unwrap();
+
+        let stats = clean_old_files(dir.to_str().unwrap(), Duration::from_secs(30), None).unwrap();
+
+        assert_eq!(
+            stats,
+            CleanStats {
+                files_deleted: 1,
+                files_skipped: 2,
+                dirs_removed: 0,
+                bytes_freed: 10,
+            }
+        );
+        assert!(!old.exists());
+        assert!(new.exists());
+        assert!(future.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_old_files_creation_time() {
+        // 不支持创建时间的文件系统使用修改时间，不会跳过所有文件
+        let dir = test_dir("ctime");
+        create_file(&dir, "a.json", Duration::ZERO);
+        create_file(&dir, "b.json", Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(20));
+
+        let stats = clean_old_files_with_mode(
+            dir.to_str().unwrap(),
+            Duration::from_millis(10),
+            CleanMode::CreationTime,
+            None,
+        )
+        .unwrap();
+        assert_eq!(stats.files_deleted, 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_old_files_extensions() {
+        let dir = test_dir("extensions");
+        let json = create_file(&dir, "stats.JSON", Duration::from_secs(120));
+        let lock = create_file(&dir, "stats.lock", Duration::from_secs(120));
+        let no_ext = create_file(&dir, "stats", Duration::from_secs(120));
+
         clean_old_files(
-            "/Users/yaoyonglong/Desktop/doc/work/vida/rust/terminals/general_spider/data/stats",
+            dir.to_str().unwrap(),
             Duration::from_secs(30),
+            Some(&["json", ".log"]),
         )
         .unwrap();
+
+        assert!(!json.exists());
+        assert!(lock.exists());
+        assert!(no_ext.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_clean_old_files_symlink() {
+        let dir = test_dir("symlink");
+        let target_dir = test_dir("symlink_target");
+        let target = create_file(&target_dir, "target.json", Duration::ZERO);
+        let link = dir.join("link.json");
+        let dangling = dir.join("dangling.json");
+        let sub_dir = dir.join("sub");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        std::os::unix::fs::symlink(dir.join("missing.json"), &dangling).unwrap();
+        fs::create_dir(&sub_dir).unwrap();
+
+        // 符号链接本身的时间无法直接修改，等待一段时间后按 0 过期时间清理
+        std::thread::sleep(Duration::from_millis(20));
+        clean_old_files(dir.to_str().unwrap(), Duration::ZERO, None).unwrap();
+
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert!(fs::symlink_metadata(&dangling).is_err());
+        assert!(target.exists());
+        assert!(sub_dir.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_dir_all(target_dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_old_files_recursive() {
+        let dir = test_dir("recursive");
+        let old_dir = dir.join("2024-01-15");
+        let new_dir = dir.join("2024-01-16").join("nested");
+        fs::create_dir_all(&old_dir).unwrap();
+        fs::create_dir_all(&new_dir).unwrap();
+
+        create_file(&old_dir, "a.json", Duration::from_secs(120));
+        create_file(&old_dir, "b.json", Duration::from_secs(120));
+        let old = create_file(&new_dir, "c.json", Duration::from_secs(120));
+        let new = create_file(&new_dir, "d.json", Duration::ZERO);
+
+        let stats =
+            clean_old_files_recursive(dir.to_str().unwrap(), Duration::from_secs(30)).unwrap();
+
+        assert_eq!(
+            stats,
+            CleanStats {
+                files_deleted: 3,
+                files_skipped: 1,
+                dirs_removed: 1,
+                bytes_freed: 0,
+            }
+        );
+        assert!(!old_dir.exists());
+        assert!(!old.exists());
+        assert!(new.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_old_files_dry_run() {
+        let dir = test_dir("dry_run");
+        let old = create_file(&dir, "old.json", Duration::from_secs(120));
+
+        clean_old_files_dry_run(dir.to_str().unwrap(), Duration::from_secs(30), None).unwrap();
+
+        assert!(old.exists());
+        assert!(
+            clean_old_files_dry_run(old.to_str().unwrap(), Duration::from_secs(30), None).is_err()
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_by_size() {
+        let dir = test_dir("size");
+        let mut files = vec![];
+        for (i, age) in [400, 300, 200, 100].into_iter().enumerate() {
+            let path = create_file(&dir, &format!("{i}.json"), Duration::from_secs(age));
+            fs::write(&path, [0u8; 100]).unwrap();
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(age))
+                .unwrap();
+            files.push(path);
+        }
+
+        let stats = clean_by_size(dir.to_str().unwrap(), 250).unwrap();
+
+        assert_eq!(stats.files_deleted, 2);
+        assert_eq!(stats.bytes_freed, 200);
+        assert!(!files[0].exists());
+        assert!(!files[1].exists());
+        assert!(files[2].exists());
+        assert!(files[3].exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_config_run() {
+        let dir = test_dir("config");
+        let old = create_file(&dir, "old.json", Duration::from_secs(120));
+        let new = create_file(&dir, "new.json", Duration::ZERO);
+        fs::write(&new, [0u8; 100]).unwrap();
+
+        let config = CleanConfig {
+            paths: vec![dir.to_str().unwrap().to_string(), "/not/exists".to_string()],
+            max_age: Some(Duration::from_secs(30)),
+            max_total_bytes: Some(50),
+            dry_run: false,
+            allowed_extensions: None,
+        };
+
+        let results = config.run();
+        assert_eq!(results.len(), 2);
+        let stats = results[0].1.as_ref().unwrap();
+        assert_eq!(stats.files_deleted, 2);
+        assert_eq!(stats.bytes_freed, 100);
+        assert_eq!(results[1].0, "/not/exists");
+        assert!(results[1].1.is_err());
+        assert!(!old.exists());
+        assert!(!new.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_stats_add() {
+        let a = CleanStats {
+            files_deleted: 1,
+            files_skipped: 2,
+            dirs_removed: 3,
+            bytes_freed: 4,
+        };
+        let total = [a.clone(), a]
+            .into_iter()
+            .fold(CleanStats::default(), |acc, s| acc + s);
+        assert_eq!(
+            total,
+            CleanStats {
+                files_deleted: 2,
+                files_skipped: 4,
+                dirs_removed: 6,
+                bytes_freed: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn test_clean_old_files_not_dir() {
+        let dir = test_dir("not_dir");
+        let file = create_file(&dir, "file.json", Duration::ZERO);
+
+        assert!(clean_old_files(file.to_str().unwrap(), Duration::from_secs(30), None).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
     }
 }
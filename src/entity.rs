@@ -1,40 +1,582 @@
-use serde::{Deserialize, Deserializer, Serialize};
-use std::collections::HashMap;
+use crate::clean::CleanStats;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 
 // 请求统计配置信息
+// 字段名使用 camelCase；同时兼容 toml 中常用的 snake_case
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct RequestStatsConfig {
     // 推送目标主机信息
     pub target: Vec<String>,
+    // 使用独立周期推送的目标；推送当前统计周期的快照，不清空统计数据
+    #[serde(default)]
+    pub targets: Vec<TargetConfig>,
     // 上报周期
-    #[serde(deserialize_with = "deserialize_duration")]
+    #[serde(
+        alias = "reporting_cycle",
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
     pub reporting_cycle: Duration,
 
     // hosts 测试的默认端口
-    #[serde(default = "default_host_test_port")]
+    #[serde(alias = "host_test_port", default = "default_host_test_port")]
     pub host_test_port: u16,
+
+    // hosts 测试的连接超时时间
+    #[serde(
+        alias = "host_ping_timeout",
+        default = "default_host_ping_timeout",
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub host_ping_timeout: Duration,
+
+    // 每个周期对每个 host 测试的次数；最大 10 次
+    #[serde(alias = "host_ping_samples", default = "default_host_ping_samples")]
+    pub host_ping_samples: u8,
+
+    // hosts 测试的方式
+    #[serde(alias = "host_ping_method", default)]
+    pub host_ping_method: PingMethod,
+
+    // 端口测试使用的协议；只在 `host_ping_method` 为 tcp 时生效
+    #[serde(alias = "host_ping_protocol", default)]
+    pub host_ping_protocol: PingProtocol,
+
+    // 每种错误类型保留最近的错误上下文条数
+    #[serde(alias = "recent_errors_limit", default = "default_recent_errors_limit")]
+    pub recent_errors_limit: usize,
+
+    // 当前周期没有请求时不推送统计信息
+    #[serde(alias = "skip_empty_cycles", default)]
+    pub skip_empty_cycles: bool,
+
+    // 第一次上报的随机抖动上限，避免多个实例同时推送
+    #[serde(
+        alias = "reporting_jitter",
+        default,
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub reporting_jitter: Duration,
+
+    // 系统资源中是否包含每个 CPU 核心的使用率
+    #[serde(alias = "include_per_core_cpu", default)]
+    pub include_per_core_cpu: bool,
+
+    // 推送数据的压缩方式；压缩后推送 `{"encoding": "gzip", "data": "<base64>"}`
+    #[serde(alias = "compression", default)]
+    pub compression: Option<PushCompression>,
+
+    // 推送时使用 `PushEnvelope` 包装统计信息；会改变推送的数据格式
+    #[serde(alias = "use_envelope", default)]
+    pub use_envelope: bool,
+
+    // 推送的统计信息中是否包含和上一个周期相比的变化
+    #[serde(alias = "include_diff", default)]
+    pub include_diff: bool,
+
+    // 日志中使用单行摘要（`Stats` 的 Display）输出统计信息；默认使用 `to_line_summary` 的 key=value 格式
+    #[serde(alias = "compact_log", default)]
+    pub compact_log: bool,
+
+    // 推送统计信息（成功或失败）的日志级别；上报周期较短时可以调低，避免日志过多
+    #[serde(alias = "log_level", default)]
+    pub log_level: LogLevel,
+
+    // 是否采集系统资源；关闭后系统资源数据全部为默认值
+    #[serde(
+        alias = "collect_system_resources",
+        default = "default_collect_system_resources"
+    )]
+    pub collect_system_resources: bool,
+
+    // 系统资源的刷新间隔；后台定时刷新，上报时读取最近一次刷新的结果
+    #[serde(
+        alias = "system_refresh_interval",
+        default = "default_system_refresh_interval",
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub system_refresh_interval: Duration,
+
+    // 分组统计（如代理）的标签数量上限；超过上限的标签统一计入 `other`
+    #[serde(alias = "max_labels", default = "default_max_labels")]
+    pub max_labels: usize,
+
+    // 统计快照文件路径；设置后持久化当前周期的统计数据，启动时从中恢复
+    #[serde(alias = "snapshot_path", default)]
+    pub snapshot_path: Option<PathBuf>,
+
+    // 快照写入间隔；上报任务按间隔定时写入，更新统计数据时不写入
+    #[serde(
+        alias = "snapshot_interval",
+        default = "default_snapshot_interval",
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub snapshot_interval: Duration,
+
+    // 内存中保留最近几个周期的统计信息，用于趋势分析；0 表示不保留
+    #[serde(alias = "history_size", default)]
+    pub history_size: usize,
+
+    // 统计信息中比例和平均值保留的小数位数，包括 hosts 的平均延迟和会话汇总的平均错误率；最多 15 位
+    #[serde(alias = "stats_precision", default = "default_stats_precision")]
+    pub stats_precision: u8,
+
+    // 一个周期内的最大连续失败次数超过该值时触发告警回调；None 表示不检查
+    #[serde(alias = "consecutive_failure_alarm", default)]
+    pub consecutive_failure_alarm: Option<u32>,
+
+    // 推送的统计信息（包括独立周期的推送目标）中的请求频率使用本周期的每秒请求数（取整数部分）；
+    // `current_base` 返回的请求频率使用上一个周期的每秒请求数（四舍五入）
+    #[serde(alias = "auto_request_frequency", default)]
+    pub auto_request_frequency: bool,
+
+    // 延迟直方图的桶边界（毫秒），如 `[1.0, 5.0, 10.0, 50.0, 100.0, 500.0, 1000.0]`；None 表示不统计
+    #[serde(alias = "latency_buckets", default)]
+    pub latency_buckets: Option<Vec<f64>>,
+
+    // 健康检查允许的上一个周期的错误率上限
+    #[serde(
+        alias = "health_max_error_rate",
+        default = "default_health_max_error_rate"
+    )]
+    pub health_max_error_rate: f64,
+
+    // hosts 连续测试失败（所有测试都超时或者失败）该次数后不再测试；None 表示不剔除
+    #[serde(alias = "evict_failing_hosts_after", default)]
+    pub evict_failing_hosts_after: Option<u32>,
+
+    // 剔除的 hosts 在该时间之后重新测试；None 表示不再测试，直到重新初始化
+    #[serde(
+        alias = "evict_retry_after",
+        default,
+        serialize_with = "serialize_optional_duration",
+        deserialize_with = "deserialize_optional_duration"
+    )]
+    pub evict_retry_after: Option<Duration>,
+
+    // 自定义的请求结果（`RequestResult::Custom`）是否计为错误
+    #[serde(
+        alias = "custom_results_are_errors",
+        default = "default_custom_results_are_errors"
+    )]
+    pub custom_results_are_errors: bool,
 }
 
 fn default_host_test_port() -> u16 {
     443
 }
 
+fn default_host_ping_timeout() -> Duration {
+    Duration::from_secs(3)
+}
+
+fn default_host_ping_samples() -> u8 {
+    1
+}
+
+fn default_recent_errors_limit() -> usize {
+    5
+}
+
+fn default_max_labels() -> usize {
+    100
+}
+
+fn default_collect_system_resources() -> bool {
+    true
+}
+
+fn default_snapshot_interval() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_system_refresh_interval() -> Duration {
+    crate::system_poller::DEFAULT_REFRESH_INTERVAL
+}
+
+fn default_custom_results_are_errors() -> bool {
+    true
+}
+
+fn default_stats_precision() -> u8 {
+    3
+}
+
+fn default_health_max_error_rate() -> f64 {
+    0.5
+}
+
+// 开发环境使用的默认配置；可选项和反序列化的默认值一致
+impl Default for RequestStatsConfig {
+    fn default() -> Self {
+        Self {
+            target: vec!["ws://127.0.0.1:5003".to_string()],
+            targets: Vec::new(),
+            reporting_cycle: Duration::from_secs(60),
+            host_test_port: default_host_test_port(),
+            host_ping_timeout: default_host_ping_timeout(),
+            host_ping_samples: default_host_ping_samples(),
+            host_ping_method: PingMethod::default(),
+            host_ping_protocol: PingProtocol::default(),
+            recent_errors_limit: default_recent_errors_limit(),
+            skip_empty_cycles: false,
+            reporting_jitter: Duration::ZERO,
+            include_per_core_cpu: false,
+            compression: None,
+            use_envelope: false,
+            include_diff: false,
+            compact_log: false,
+            log_level: LogLevel::default(),
+            collect_system_resources: default_collect_system_resources(),
+            system_refresh_interval: default_system_refresh_interval(),
+            max_labels: default_max_labels(),
+            snapshot_path: None,
+            snapshot_interval: default_snapshot_interval(),
+            history_size: 0,
+            stats_precision: default_stats_precision(),
+            consecutive_failure_alarm: None,
+            auto_request_frequency: false,
+            latency_buckets: None,
+            health_max_error_rate: default_health_max_error_rate(),
+            evict_failing_hosts_after: None,
+            evict_retry_after: None,
+            custom_results_are_errors: default_custom_results_are_errors(),
+        }
+    }
+}
+
+impl RequestStatsConfig {
+    /// 从 toml 字符串读取配置
+    pub fn from_toml(s: &str) -> anyhow::Result<RequestStatsConfig> {
+        Ok(toml::from_str(s)?)
+    }
+
+    /// 从 toml 文件读取配置
+    pub fn from_toml_file(path: &Path) -> anyhow::Result<RequestStatsConfig> {
+        let s = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::anyhow!("读取配置文件 {path:?} 失败：{err}"))?;
+        Self::from_toml(&s)
+    }
+
+    /// 从环境变量读取配置
+    /// - `STATS_TARGET`：推送地址，多个用逗号分隔
+    /// - `STATS_REPORTING_CYCLE`：上报周期，如 `30s`
+    /// - `STATS_HOST_TEST_PORT`：hosts 测试端口
+    /// - `STATS_HOST_PING_TIMEOUT`：hosts 测试超时时间，如 `3s`
+    ///
+    /// 未设置的可选项使用和反序列化相同的默认值
+    pub fn from_env() -> anyhow::Result<RequestStatsConfig> {
+        Self::from_env_with(|name| std::env::var(name).ok())
+    }
+
+    /// 和 `from_env` 相同，通过 `lookup` 读取变量；用于测试或其他配置来源
+    pub fn from_env_with(
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<RequestStatsConfig> {
+        let mut value = serde_json::Map::new();
+
+        let target =
+            lookup("STATS_TARGET").ok_or_else(|| anyhow::anyhow!("缺少环境变量 STATS_TARGET"))?;
+        let target: Vec<&str> = target
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .collect();
+        value.insert("target".to_string(), target.into());
+
+        let reporting_cycle = lookup("STATS_REPORTING_CYCLE")
+            .ok_or_else(|| anyhow::anyhow!("缺少环境变量 STATS_REPORTING_CYCLE"))?;
+        value.insert("reportingCycle".to_string(), reporting_cycle.into());
+
+        if let Some(port) = lookup("STATS_HOST_TEST_PORT") {
+            let port: u16 = port
+                .trim()
+                .parse()
+                .map_err(|err| anyhow::anyhow!("STATS_HOST_TEST_PORT 格式错误：{err}"))?;
+            value.insert("hostTestPort".to_string(), port.into());
+        }
+
+        if let Some(timeout) = lookup("STATS_HOST_PING_TIMEOUT") {
+            value.insert("hostPingTimeout".to_string(), timeout.into());
+        }
+
+        Ok(serde_json::from_value(value.into())?)
+    }
+
+    /// 检查配置，返回所有不合法的配置项
+    /// - 至少有一个推送地址（`target` 或 `targets`），且地址可以解析
+    /// - 上报周期和独立推送周期大于 0
+    /// - 使用 tcp 测试延迟时 `host_test_port` 大于 0
+    /// - `host_ping_samples` 在 1 到 10 之间，`stats_precision` 不超过 15
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let errors = self.validation_errors(true, true);
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(errors),
+        }
+    }
+
+    /// 使用自定义上报方式时不要求推送地址；没有需要测试的 hosts 时不检查 `host_test_port`
+    pub(crate) fn validation_errors(&self, require_target: bool, pings_hosts: bool) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if require_target && self.target.is_empty() && self.targets.is_empty() {
+            errors.push("target 不能为空".to_string());
+        }
+        for url in &self.target {
+            if let Err(err) = check_target_url(url) {
+                errors.push(format!("target 地址 {url:?} 格式错误：{err}"));
+            }
+        }
+        for target in &self.targets {
+            if let Err(err) = check_target_url(&target.url) {
+                errors.push(format!("targets 地址 {:?} 格式错误：{err}", target.url));
+            }
+            if target.cycle.is_zero() {
+                errors.push(format!("targets 地址 {:?} 的 cycle 必须大于 0", target.url));
+            }
+        }
+
+        if self.reporting_cycle.is_zero() {
+            errors.push("reporting_cycle 必须大于 0".to_string());
+        }
+        if self.system_refresh_interval.is_zero() {
+            errors.push("system_refresh_interval 必须大于 0".to_string());
+        }
+        if self.snapshot_path.is_some() && self.snapshot_interval.is_zero() {
+            errors.push("snapshot_interval 必须大于 0".to_string());
+        }
+        if pings_hosts && self.host_ping_method == PingMethod::Tcp && self.host_test_port == 0 {
+            errors.push("host_test_port 必须大于 0".to_string());
+        }
+        if !(1..=crate::MAX_HOST_PING_SAMPLES).contains(&self.host_ping_samples) {
+            errors.push(format!(
+                "host_ping_samples 必须在 1 到 {} 之间",
+                crate::MAX_HOST_PING_SAMPLES
+            ));
+        }
+        if self.stats_precision > crate::MAX_STATS_PRECISION {
+            errors.push(format!(
+                "stats_precision 不能超过 {}",
+                crate::MAX_STATS_PRECISION
+            ));
+        }
+
+        errors
+    }
+}
+
+/// 检查推送地址可以解析，并且包含 host
+fn check_target_url(url: &str) -> anyhow::Result<()> {
+    let request = url.into_client_request()?;
+    anyhow::ensure!(request.uri().host().is_some(), "缺少 host");
+    Ok(())
+}
+
+// 推送数据的压缩方式
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PushCompression {
+    // 需要开启 gzip feature
+    Gzip,
+    // 需要开启 zstd feature
+    Zstd,
+}
+
+impl PushCompression {
+    /// 压缩方式的名称，和推送数据中的 `encoding` 一致
+    pub fn name(&self) -> &'static str {
+        match self {
+            PushCompression::Gzip => "gzip",
+            PushCompression::Zstd => "zstd",
+        }
+    }
+
+    /// 是否开启了对应的 feature
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            PushCompression::Gzip => cfg!(feature = "gzip"),
+            PushCompression::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+}
+
+// host 延迟测试方式
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum PingMethod {
+    // tcp 建立连接的耗时
+    #[default]
+    Tcp,
+    // icmp echo 的耗时；需要开启 icmp feature
+    Icmp,
+}
+
+// 端口延迟测试使用的协议
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum PingProtocol {
+    // tcp 建立连接的耗时
+    #[default]
+    Tcp,
+    // 发送 `PING\n` 到 udp echo 服务，收到任意响应的耗时
+    UdpEcho,
+    // 先测试 tcp，失败时使用 udp echo
+    Auto,
+}
+
+// 使用独立周期推送的目标
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TargetConfig {
+    // 推送地址
+    pub url: String,
+    // 推送周期
+    #[serde(
+        serialize_with = "serialize_duration",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub cycle: Duration,
+    // 是否包含系统资源数据；关闭后系统资源数据为默认值
+    #[serde(
+        alias = "include_system_resources",
+        default = "default_include_target_data"
+    )]
+    pub include_system_resources: bool,
+    // 是否测试 hosts 延迟
+    #[serde(alias = "include_host_ping", default = "default_include_target_data")]
+    pub include_host_ping: bool,
+}
+
+fn default_include_target_data() -> bool {
+    true
+}
+
+// 日志级别；对应 `tracing` 的日志级别
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// 从 `as u8` 的值转换；无效的值使用默认级别
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            0 => LogLevel::Trace,
+            1 => LogLevel::Debug,
+            3 => LogLevel::Warn,
+            4 => LogLevel::Error,
+            _ => LogLevel::Info,
+        }
+    }
+}
+
+// host 延迟测试参数
+#[derive(Debug, Clone)]
+pub struct HostPingInfo {
+    // 需要测试的 hosts
+    pub hosts: Vec<String>,
+    // 测试端口
+    pub port: u16,
+    // 单次连接超时时间
+    pub timeout: Duration,
+    // 每个 host 测试的次数
+    pub samples: u8,
+    // 测试方式
+    pub method: PingMethod,
+    // 端口测试使用的协议；只在 `method` 为 tcp 时生效
+    pub protocol: PingProtocol,
+    // 平均延迟保留的小数位数；和 `RequestStatsConfig.stats_precision` 一致
+    pub precision: u8,
+}
+
+// 单个 host 的延迟统计（单位：毫秒）
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct HostPingStats {
+    // 最小延迟
+    pub min: f64,
+    // 最大延迟
+    pub max: f64,
+    // 平均延迟
+    pub avg: f64,
+    // p95 延迟
+    pub p95: f64,
+    // 失败（超时）的次数
+    pub lost: u8,
+}
+
 /// 请求结果的枚举类型
+/// 错误类型可以附带上下文信息（如 url、错误信息），会采样保留最近的几条
+/// 内置类型不能满足需要时使用 `Custom`，编号由调用方定义，通过 `register_custom_result` 注册名称
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
 pub enum RequestResult {
-    Successful,         // 处理成功+请求成功  这个是没有命中缓存的
-    SuccessfulAndCache, // 处理成功+请求成功 + 命中缓存
-    ParseError,         // 解析错误
-    TimeoutError,       // 超时错误
-    ConnectionError,    // 连接错误
-    StatusCodeError,    // 状态码异常
+    Successful,                        // 处理成功+请求成功  这个是没有命中缓存的
+    SuccessfulAndCache,                // 处理成功+请求成功 + 命中缓存
+    ParseError(Option<Box<str>>),      // 解析错误
+    TimeoutError(Option<Box<str>>),    // 超时错误
+    ConnectionError(Option<Box<str>>), // 连接错误
+    StatusCodeError(Option<Box<str>>), // 状态码异常
+    ProxyError(Option<Box<str>>),      // 代理错误（如 407 代理认证失败）
+    RateLimited(Option<Box<str>>),     // 被限流（如 429）
+    CaptchaDetected(Option<Box<str>>), // 触发验证码
+    Custom(u8), // 自定义的请求结果；是否计为错误由 `custom_results_are_errors` 决定
+}
+
+impl RequestResult {
+    /// 错误类型的名称，和 `ExceptionTypes` 序列化后的字段名一致；成功和自定义的结果返回 None
+    pub fn error_kind(&self) -> Option<&'static str> {
+        match self {
+            RequestResult::Successful
+            | RequestResult::SuccessfulAndCache
+            | RequestResult::Custom(_) => None,
+            RequestResult::ParseError(_) => Some("parseError"),
+            RequestResult::TimeoutError(_) => Some("timeoutError"),
+            RequestResult::ConnectionError(_) => Some("connectionError"),
+            RequestResult::StatusCodeError(_) => Some("statusCodeError"),
+            RequestResult::ProxyError(_) => Some("proxyError"),
+            RequestResult::RateLimited(_) => Some("rateLimited"),
+            RequestResult::CaptchaDetected(_) => Some("captchaDetected"),
+        }
+    }
+
+    /// 错误附带的上下文信息
+    pub fn context(&self) -> Option<&str> {
+        match self {
+            RequestResult::Successful
+            | RequestResult::SuccessfulAndCache
+            | RequestResult::Custom(_) => None,
+            RequestResult::ParseError(ctx)
+            | RequestResult::TimeoutError(ctx)
+            | RequestResult::ConnectionError(ctx)
+            | RequestResult::StatusCodeError(ctx)
+            | RequestResult::ProxyError(ctx)
+            | RequestResult::RateLimited(ctx)
+            | RequestResult::CaptchaDetected(ctx) => ctx.as_deref(),
+        }
+    }
 }
 
 // 用于序列化和反序列化的导入
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 // 表示资源使用情况的结构体
 pub struct Usage {
     // 已使用的资源量
@@ -44,21 +586,128 @@ pub struct Usage {
 }
 
 // 系统资源信息结构体
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
 pub struct SystemResources {
-    // CPU 使用率
-    #[serde(rename = "cpuUsage")]
-    pub cpu_usage: String,
+    // CPU 使用率（百分比，0.0 - 100.0）
+    #[serde(rename = "cpuUsage", serialize_with = "serialize_finite_f32")]
+    pub cpu_usage: f32,
     // 内存使用情况
     #[serde(rename = "memoryUsage")]
     pub memory_usage: Usage,
     // 磁盘使用情况
     #[serde(rename = "diskUsage")]
     pub disk_usage: Usage,
+    // 每个 CPU 核心的使用率；需要开启 include_per_core_cpu
+    #[serde(rename = "cpuCores", default, skip_serializing_if = "Vec::is_empty")]
+    pub cpu_cores: Vec<CpuCoreUsage>,
+    // 网络 IO；距离上一次获取的增量
+    #[serde(rename = "networkIo", default)]
+    pub network_io: NetworkIO,
+    // 磁盘 IO 吞吐；距离上一次获取的平均值
+    #[serde(rename = "diskIo", default)]
+    pub disk_io: DiskIO,
+    // 当前进程的内存使用情况
+    #[serde(rename = "processMemory", default)]
+    pub process_memory: ProcessMemory,
+    // 当前进程的线程数；不支持的平台为 0
+    #[serde(rename = "threadCount", default)]
+    pub thread_count: u32,
+    // 当前进程打开的文件描述符数；不支持的平台为 0
+    #[serde(rename = "openFdCount", default)]
+    pub open_fd_count: u32,
+}
+
+// 进程内存使用情况（单位：MB）
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessMemory {
+    // 常驻内存
+    #[serde(rename = "rssMb")]
+    pub rss_mb: u64,
+    // 虚拟内存
+    #[serde(rename = "virtualMb")]
+    pub virtual_mb: u64,
+}
+
+// 磁盘 IO 吞吐（单位：字节/秒）
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DiskIO {
+    // 每秒读取字节数
+    #[serde(rename = "readBytesPerSec")]
+    pub read_bytes_per_sec: u64,
+    // 每秒写入字节数
+    #[serde(rename = "writeBytesPerSec")]
+    pub write_bytes_per_sec: u64,
+}
+
+// 磁盘 IO 累计读数
+#[derive(Clone, Debug)]
+pub struct DiskIOSnapshot {
+    // 累计读取字节数
+    pub read_bytes: u64,
+    // 累计写入字节数
+    pub written_bytes: u64,
+    // 读取时间
+    pub at: Instant,
+}
+
+impl DiskIOSnapshot {
+    /// 计算和上一次读数之间的平均吞吐；计数器重置或者时间间隔为 0 时记为 0
+    pub fn throughput(&self, prev: &DiskIOSnapshot) -> DiskIO {
+        let secs = self.at.saturating_duration_since(prev.at).as_secs_f64();
+        if secs <= 0.0 {
+            return DiskIO::default();
+        }
+
+        DiskIO {
+            read_bytes_per_sec: (self.read_bytes.saturating_sub(prev.read_bytes) as f64 / secs)
+                as u64,
+            write_bytes_per_sec: (self.written_bytes.saturating_sub(prev.written_bytes) as f64
+                / secs) as u64,
+        }
+    }
+}
+
+// 网络 IO 统计（单位：字节 / 个）
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct NetworkIO {
+    // 接收字节数
+    #[serde(rename = "bytesReceived")]
+    pub bytes_received: u64,
+    // 发送字节数
+    #[serde(rename = "bytesSent")]
+    pub bytes_sent: u64,
+    // 接收包数
+    #[serde(rename = "packetsReceived")]
+    pub packets_received: u64,
+    // 发送包数
+    #[serde(rename = "packetsSent")]
+    pub packets_sent: u64,
+}
+
+impl NetworkIO {
+    /// 计算和上一次读数的差值；网卡计数器重置时差值记为 0
+    pub fn delta(&self, prev: &NetworkIO) -> NetworkIO {
+        NetworkIO {
+            bytes_received: self.bytes_received.saturating_sub(prev.bytes_received),
+            bytes_sent: self.bytes_sent.saturating_sub(prev.bytes_sent),
+            packets_received: self.packets_received.saturating_sub(prev.packets_received),
+            packets_sent: self.packets_sent.saturating_sub(prev.packets_sent),
+        }
+    }
+}
+
+// 单个 CPU 核心的使用情况
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct CpuCoreUsage {
+    // 核心序号
+    pub index: usize,
+    // 使用率（百分比，0.0 - 100.0）
+    #[serde(serialize_with = "serialize_finite_f32")]
+    pub usage: f32,
 }
 
 // 异常类型统计结构体
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub struct ExceptionTypes {
     // 连接错误次数
     #[serde(rename = "connectionError")]
@@ -72,10 +721,142 @@ pub struct ExceptionTypes {
     // 状态码异常次数
     #[serde(rename = "statusCodeError")]
     pub status_code_error: i64,
+    // 代理错误次数
+    #[serde(rename = "proxyError", default)]
+    pub proxy_error: i64,
+    // 被限流次数
+    #[serde(rename = "rateLimited", default)]
+    pub rate_limited: i64,
+    // 触发验证码次数
+    #[serde(rename = "captchaDetected", default)]
+    pub captcha_detected: i64,
+}
+
+impl ExceptionTypes {
+    /// 所有类型的错误总数
+    pub fn total(&self) -> i64 {
+        self.connection_error
+            + self.timeout_error
+            + self.parse_error
+            + self.status_code_error
+            + self.proxy_error
+            + self.rate_limited
+            + self.captcha_detected
+    }
+}
+
+// 推送数据的外层结构；需要开启 use_envelope
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PushEnvelope<T> {
+    // 推送时间（毫秒级时间戳）；和 `timePeriod.end` 不同
+    pub sent_at_ms: i64,
+    // 推送序号；和 `payload.sequenceNumber` 一致
+    #[serde(default)]
+    pub sequence_number: u64,
+    // `payload` 的结构版本
+    pub schema_version: u32,
+    pub payload: T,
+}
+
+impl<T: Serialize> PushEnvelope<T> {
+    /// 使用当前时间包装推送数据
+    pub fn new(payload: T) -> Self {
+        Self {
+            sent_at_ms: crate::get_now_millis(),
+            sequence_number: 0,
+            schema_version: STATS_SCHEMA_VERSION,
+            payload,
+        }
+    }
+}
+
+// 两个周期之间统计数据的变化（当前周期 - 上一个周期）
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StatsDiff {
+    // 错误率变化
+    #[serde(serialize_with = "serialize_finite")]
+    pub error_rate_delta: f64,
+    // 平均请求延迟变化（毫秒）
+    #[serde(serialize_with = "serialize_finite")]
+    pub latency_delta_ms: f64,
+    // 请求数变化
+    pub request_count_delta: i64,
+    // 每秒请求数变化
+    #[serde(serialize_with = "serialize_finite")]
+    pub requests_per_second_delta: f64,
+    // 缓存命中率变化
+    #[serde(serialize_with = "serialize_finite")]
+    pub cache_hit_rate_delta: f64,
+    // 错误数变化
+    pub error_count_delta: i64,
+    // CPU 使用率变化（百分比）
+    #[serde(serialize_with = "serialize_finite_f32")]
+    pub cpu_usage_delta: f32,
+}
+
+impl StatsDiff {
+    /// 错误相关的指标是否上升
+    pub fn errors_increased(&self) -> bool {
+        self.error_rate_delta > 0.0 || self.error_count_delta > 0
+    }
+}
+
+// 如：`errors: +2.0%  latency: -10.0ms  requests: +120  rps: +1.2  cache: +0.5%`
+impl std::fmt::Display for StatsDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "errors: {:+.1}%  latency: {:+.1}ms  requests: {:+}  rps: {:+.1}  cache: {:+.1}%",
+            self.error_rate_delta * 100.0,
+            self.latency_delta_ms,
+            self.request_count_delta,
+            self.requests_per_second_delta,
+            self.cache_hit_rate_delta * 100.0
+        )
+    }
+}
+
+// 单个阶段的耗时（毫秒）
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct StageDuration {
+    // 平均耗时
+    #[serde(serialize_with = "serialize_finite")]
+    pub avg: f64,
+}
+
+// 单个代理的统计
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxyBreakdown {
+    // 请求数
+    pub requests: i64,
+    // 错误率
+    #[serde(serialize_with = "serialize_finite")]
+    pub error_rate: f64,
+    // 平均请求延迟（毫秒）
+    #[serde(serialize_with = "serialize_finite")]
+    pub avg_latency_ms: f64,
+}
+
+// 重试统计
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RetryStats {
+    // 重试总次数
+    pub total_retries: i64,
+    // 有重试的请求中最终成功的比例
+    #[serde(serialize_with = "serialize_finite")]
+    pub retry_success_rate: f64,
+    // 有重试的请求占总请求数的比例
+    #[serde(serialize_with = "serialize_finite")]
+    pub retry_rate: f64,
 }
 
 // 时间周期结构体
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
 pub struct TimePeriod {
     // 开始时间（毫秒级时间戳）
     pub start: i64,
@@ -83,8 +864,57 @@ pub struct TimePeriod {
     pub end: i64,
 }
 
+impl TimePeriod {
+    /// 开始时间格式化为 ISO 8601，如 `2024-01-15T18:05:00+08:00`；`tz_offset_hours` 为相对 UTC 的小时数
+    pub fn format_start(&self, tz_offset_hours: i32) -> String {
+        format_iso8601(self.start, tz_offset_hours)
+    }
+
+    /// 结束时间格式化为 ISO 8601，如 `2024-01-15T18:10:00+08:00`；`tz_offset_hours` 为相对 UTC 的小时数
+    pub fn format_end(&self, tz_offset_hours: i32) -> String {
+        format_iso8601(self.end, tz_offset_hours)
+    }
+}
+
+/// 毫秒级时间戳格式化为指定时区的 ISO 8601（精确到秒）；时区偏移限制在 ±23 小时
+fn format_iso8601(millis: i64, tz_offset_hours: i32) -> String {
+    let offset = tz_offset_hours.clamp(-23, 23);
+    let secs = millis.div_euclid(1000) + offset as i64 * 3600;
+    let (days, secs_of_day) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+
+    // 从 1970-01-01 起的天数转换为年月日（Howard Hinnant 的 civil_from_days 算法）
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let zone = match offset {
+        0 => "Z".to_string(),
+        _ => format!(
+            "{}{:02}:00",
+            if offset < 0 { '-' } else { '+' },
+            offset.abs()
+        ),
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        zone
+    )
+}
+
 // 统计信息结构体
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct StatsBase {
     // 服务器名称
     #[serde(rename = "serverName")]
@@ -103,19 +933,417 @@ pub struct StatsBase {
     #[serde(rename = "requestFrequency")]
     #[serde(default)]
     pub request_frequency: i64,
+
+    // 自定义的元数据（如 `"dataCenter": "us-east-1"`）；序列化时展开到顶层
+    #[serde(flatten, default)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+// 合并多个来源的统计数据时，爬虫名称和项目代号不区分大小写
+impl PartialEq for StatsBase {
+    fn eq(&self, other: &Self) -> bool {
+        self.server_name == other.server_name
+            && self.scraper_name.to_lowercase() == other.scraper_name.to_lowercase()
+            && self.project_code.to_lowercase() == other.project_code.to_lowercase()
+            && self.scraper_type == other.scraper_type
+            && self.request_frequency == other.request_frequency
+            && self.extra == other.extra
+    }
 }
+
+impl StatsBase {
+    /// 通过 builder 构造 `StatsBase`
+    pub fn builder() -> StatsBaseBuilder {
+        StatsBaseBuilder::default()
+    }
+
+    /// 从环境变量读取
+    /// - `STATS_SERVER_NAME`：服务器名称，未设置时使用 `HOSTNAME`
+    /// - `STATS_SCRAPER_NAME`：爬虫名称，必须设置
+    /// - `STATS_PROJECT_CODE`：项目代号，必须设置
+    /// - `STATS_SCRAPER_TYPE`：爬虫类型
+    /// - `STATS_REQUEST_FREQUENCY`：请求频率，默认为 0
+    pub fn from_env() -> anyhow::Result<StatsBase> {
+        Self::from_env_with(|name| std::env::var(name).ok())
+    }
+
+    /// 和 `from_env` 相同，通过 `lookup` 读取变量；用于测试或其他配置来源
+    pub fn from_env_with(lookup: impl Fn(&str) -> Option<String>) -> anyhow::Result<StatsBase> {
+        let var = |name: &str| {
+            lookup(name)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let required = |name: &str| var(name).ok_or_else(|| anyhow::anyhow!("缺少环境变量 {name}"));
+
+        let request_frequency = match var("STATS_REQUEST_FREQUENCY") {
+            Some(v) => v
+                .parse()
+                .map_err(|err| anyhow::anyhow!("STATS_REQUEST_FREQUENCY 格式错误：{err}"))?,
+            None => 0,
+        };
+
+        Ok(StatsBase {
+            server_name: var("STATS_SERVER_NAME")
+                .or_else(|| var("HOSTNAME"))
+                .unwrap_or_default(),
+            scraper_name: required("STATS_SCRAPER_NAME")?,
+            project_code: required("STATS_PROJECT_CODE")?,
+            scraper_type: var("STATS_SCRAPER_TYPE").unwrap_or_default(),
+            request_frequency,
+            extra: HashMap::new(),
+        })
+    }
+}
+
+// StatsBase 构造器；未设置的字段为空字符串或 0
+#[derive(Debug, Clone, Default)]
+pub struct StatsBaseBuilder {
+    base: StatsBase,
+}
+
+impl StatsBaseBuilder {
+    // 服务器名称
+    pub fn server_name(mut self, s: impl Into<String>) -> Self {
+        self.base.server_name = s.into();
+        self
+    }
+
+    // 爬虫名称
+    pub fn scraper_name(mut self, s: impl Into<String>) -> Self {
+        self.base.scraper_name = s.into();
+        self
+    }
+
+    // 项目代号
+    pub fn project_code(mut self, s: impl Into<String>) -> Self {
+        self.base.project_code = s.into();
+        self
+    }
+
+    // 爬虫类型
+    pub fn scraper_type(mut self, s: impl Into<String>) -> Self {
+        self.base.scraper_type = s.into();
+        self
+    }
+
+    // 请求频率（每秒请求次数）
+    pub fn request_frequency(mut self, v: i64) -> Self {
+        self.base.request_frequency = v;
+        self
+    }
+
+    // 自定义的元数据；无法序列化的值记为 null
+    pub fn extra(mut self, key: &str, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).unwrap_or_default();
+        self.base.extra.insert(key.to_string(), value);
+        self
+    }
+
+    pub fn build(self) -> StatsBase {
+        self.base
+    }
+}
+
+/// 统计周期内的累计值；多个进程的统计数据可以直接相加
+///
+/// 通过 `RequestStats::values` 或者 `RequestStats::borrow_inner` 获取，
+/// 可以用于自定义的汇总逻辑，如直接写入时序数据库；使用 `RequestStats::from` 转换回统计实例
+#[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct InnerStatsVal {
+    // 总请求数
+    pub total_requests: i64,
+    // 成功请求数
+    pub successful_requests: i64,
+    // 命中缓存的次数；在请求成功的情况下才统计
+    pub cache_hit: i64,
+    // 复用连接池中连接的请求数
+    pub connection_reused_count: i64,
+    // 解析失败次数
+    pub parse_errors: i64,
+    // 超时错误次数
+    pub timeout_errors: i64,
+    // 连接失败次数
+    pub connection_errors: i64,
+    pub status_code_error: i64,
+    // 代理错误次数
+    pub proxy_errors: i64,
+    // 被限流次数
+    pub rate_limited: i64,
+    // 触发验证码次数
+    pub captcha_detected: i64,
+    // HTTP 状态码统计（键为状态码，值为出现次数）
+    pub http_status_codes: HashMap<u16, i64>,
+    // 总请求延迟（毫秒）
+    pub total_latency: i64,
+    // 请求总字节数
+    pub total_request_bytes: u64,
+    // 响应总字节数
+    pub total_response_bytes: u64,
+    // DNS 解析总耗时（微秒）
+    pub total_dns_latency_us: i64,
+    // 统计了 DNS 解析耗时的请求数
+    pub dns_resolved_count: i64,
+    // 限速队列等待总耗时（微秒）
+    pub total_queue_delay_us: u64,
+    // 统计了限速队列等待耗时的请求数
+    pub queued_count: i64,
+    // 请求各阶段的耗时统计（键为阶段名称）
+    pub stage_durations: HashMap<String, StageStats>,
+    // 代理统计（键为代理标识）
+    pub proxy_stats: HashMap<String, ProxyStats>,
+    // 重试总次数
+    pub total_retries: i64,
+    // 有重试的请求数
+    pub retried_requests: i64,
+    // 重试之后成功的请求数
+    pub retry_success: i64,
+    // 最近的错误上下文（键为错误类型）
+    #[serde(deserialize_with = "deserialize_recent_errors")]
+    pub recent_errors: HashMap<&'static str, VecDeque<Box<str>>>,
+    // 统计周期内最长的连续失败次数
+    pub max_consecutive_failures: i64,
+    // 延迟直方图每个桶的请求数（不累计）；最后一个为超过所有边界的请求数
+    pub latency_histogram: Vec<i64>,
+    // 自定义请求结果的次数（键为自定义的编号）
+    pub custom_results: HashMap<u8, i64>,
+}
+
+// 单个代理的统计数据
+#[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ProxyStats {
+    // 请求数
+    pub requests: i64,
+    // 错误数
+    pub errors: i64,
+    // 总请求延迟（微秒）
+    pub total_latency_us: i64,
+}
+
+/// 统计周期中途的调试视图；通过 `RequestStats::debug_snapshot` 获取，不会重置统计周期
+#[derive(Clone, PartialEq)]
+pub struct InnerStatsDebug {
+    // 总请求数
+    pub total_requests: i64,
+    // 总请求延迟（微秒）
+    pub total_latency_us: i64,
+    // 当前统计周期的开始时间（毫秒级时间戳）
+    pub start_time: i64,
+    // 当前统计周期已经运行的时间（毫秒）
+    pub elapsed_ms: i64,
+    // HTTP 状态码统计（键为状态码，值为出现次数）
+    pub http_status_codes: HashMap<u16, i64>,
+}
+
+impl std::fmt::Debug for InnerStatsDebug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let avg_latency_ms = match self.total_requests {
+            0 => 0.0,
+            n => self.total_latency_us as f64 / n as f64 / 1000.0,
+        };
+        write!(
+            f,
+            "[since {}, {:.1}s] requests: {}  avg_latency: {:.1}ms  status:",
+            format_millis(self.start_time),
+            self.elapsed_ms as f64 / 1000.0,
+            self.total_requests,
+            avg_latency_ms
+        )?;
+
+        // 按状态码排序，保证输出顺序稳定
+        let mut codes: Vec<_> = self.http_status_codes.iter().collect();
+        codes.sort();
+        if codes.is_empty() {
+            return write!(f, " -");
+        }
+        for (code, count) in codes {
+            write!(f, " {code}={count}")?;
+        }
+        Ok(())
+    }
+}
+
+// 单个阶段的耗时统计
+#[derive(Default, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StageStats {
+    // 记录次数
+    pub count: i64,
+    // 总耗时（微秒）
+    pub total_us: i64,
+}
+
+/// 反序列化最近的错误上下文；键转换为 `RequestResult::error_kind` 中的静态字符串，忽略未知的错误类型
+fn deserialize_recent_errors<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<&'static str, VecDeque<Box<str>>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    const KINDS: [&str; 7] = [
+        "parseError",
+        "timeoutError",
+        "connectionError",
+        "statusCodeError",
+        "proxyError",
+        "rateLimited",
+        "captchaDetected",
+    ];
+
+    let value: HashMap<String, VecDeque<Box<str>>> = Deserialize::deserialize(deserializer)?;
+    Ok(value
+        .into_iter()
+        .filter_map(|(kind, errors)| KINDS.iter().find(|k| **k == kind).map(|k| (*k, errors)))
+        .collect())
+}
+
+// 相加时每种错误类型保留的最近错误上下文条数
+pub(crate) const DEFAULT_RECENT_ERRORS_LIMIT: usize = 5;
+
+impl std::ops::Add for InnerStatsVal {
+    type Output = InnerStatsVal;
+
+    fn add(mut self, other: InnerStatsVal) -> Self::Output {
+        self += other;
+        self
+    }
+}
+
+impl std::ops::AddAssign for InnerStatsVal {
+    fn add_assign(&mut self, other: InnerStatsVal) {
+        self.merge(&other, DEFAULT_RECENT_ERRORS_LIMIT);
+    }
+}
+
+impl InnerStatsVal {
+    /// 合并其他实例的统计数据
+    pub(crate) fn merge(&mut self, other: &InnerStatsVal, recent_errors_limit: usize) {
+        self.total_requests += other.total_requests;
+        self.successful_requests += other.successful_requests;
+        self.cache_hit += other.cache_hit;
+        self.parse_errors += other.parse_errors;
+        self.timeout_errors += other.timeout_errors;
+        self.connection_errors += other.connection_errors;
+        self.status_code_error += other.status_code_error;
+        self.proxy_errors += other.proxy_errors;
+        self.rate_limited += other.rate_limited;
+        self.captcha_detected += other.captcha_detected;
+        // 耗时和流量是累加值，输入异常时可能溢出
+        self.total_latency = self.total_latency.saturating_add(other.total_latency);
+        self.total_request_bytes = self
+            .total_request_bytes
+            .saturating_add(other.total_request_bytes);
+        self.total_response_bytes = self
+            .total_response_bytes
+            .saturating_add(other.total_response_bytes);
+        self.total_retries += other.total_retries;
+        self.total_dns_latency_us = self
+            .total_dns_latency_us
+            .saturating_add(other.total_dns_latency_us);
+        self.dns_resolved_count += other.dns_resolved_count;
+        self.total_queue_delay_us = self
+            .total_queue_delay_us
+            .saturating_add(other.total_queue_delay_us);
+        self.queued_count += other.queued_count;
+        self.connection_reused_count += other.connection_reused_count;
+
+        for (name, stage) in &other.stage_durations {
+            let merged = self.stage_durations.entry(name.clone()).or_default();
+            merged.count += stage.count;
+            merged.total_us = merged.total_us.saturating_add(stage.total_us);
+        }
+
+        for (id, proxy) in &other.proxy_stats {
+            let merged = self.proxy_stats.entry(id.clone()).or_default();
+            merged.requests += proxy.requests;
+            merged.errors += proxy.errors;
+            merged.total_latency_us = merged
+                .total_latency_us
+                .saturating_add(proxy.total_latency_us);
+        }
+        self.retried_requests += other.retried_requests;
+        self.retry_success += other.retry_success;
+        if self.latency_histogram.len() < other.latency_histogram.len() {
+            self.latency_histogram
+                .resize(other.latency_histogram.len(), 0);
+        }
+        for (merged, count) in self
+            .latency_histogram
+            .iter_mut()
+            .zip(&other.latency_histogram)
+        {
+            *merged += count;
+        }
+        // 连续失败次数不能相加，取最大值
+        self.max_consecutive_failures = self
+            .max_consecutive_failures
+            .max(other.max_consecutive_failures);
+
+        for (code, count) in &other.http_status_codes {
+            *self.http_status_codes.entry(*code).or_insert(0) += count;
+        }
+
+        for (discriminant, count) in &other.custom_results {
+            *self.custom_results.entry(*discriminant).or_insert(0) += count;
+        }
+
+        for (kind, errors) in &other.recent_errors {
+            let merged = self.recent_errors.entry(kind).or_default();
+            merged.extend(errors.iter().cloned());
+            while merged.len() > recent_errors_limit {
+                merged.pop_front();
+            }
+        }
+    }
+}
+
+/// 当前 `Stats` 的结构版本；字段有不兼容的变更时递增
+///
+/// 版本变更记录：
+/// - 1：初始版本
+/// - 2：`systemResources.cpuUsage` 从 `"42.35%"` 格式的字符串改为数值 `42.35`
+pub const STATS_SCHEMA_VERSION: u32 = 2;
+
 // 统计信息结构体
-#[derive(Serialize, Clone, Debug)]
+// 反序列化时忽略未知字段，缺失的字段使用默认值
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
 pub struct Stats<'a> {
+    // 结构版本
+    #[serde(rename = "schemaVersion")]
+    pub schema_version: u32,
+
     #[serde(flatten)]
-    pub base: &'a StatsBase,
+    pub base: Cow<'a, StatsBase>,
 
     // 时间周期
     #[serde(rename = "timePeriod")]
     pub time_period: TimePeriod,
+
+    // 第几个统计周期；初始化之后第一次推送为 1，用于发现缺失的数据
+    #[serde(rename = "cycleCount")]
+    pub cycle_count: u64,
+
+    // 统计实例的初始化时间（毫秒级时间戳）；变化时说明进程重启过
+    #[serde(rename = "restartedAt")]
+    pub restarted_at: i64,
+
+    // 推送序号；每个推送通道从 1 开始递增，接收端发现不连续时说明丢失了推送数据
+    // 没有经过推送通道时为 0，不输出
+    #[serde(rename = "sequenceNumber", skip_serializing_if = "is_zero")]
+    pub sequence_number: u64,
+
+    // 获取 hosts 或者 base 的回调失败，使用的是上一次成功获取的值；正常时不输出
+    #[serde(skip_serializing_if = "is_zero")]
+    pub stale: bool,
     // 错误率（百分比）
-    #[serde(rename = "errorRate")]
+    #[serde(rename = "errorRate", serialize_with = "serialize_finite")]
     pub error_rate: f64,
+    // 每种错误类型的错误率（键和 `exceptionTypes` 的字段名一致）
+    #[serde(rename = "errorRateByType", serialize_with = "serialize_finite_map")]
+    pub error_rate_by_type: HashMap<String, f64>,
     // 异常类型统计
     #[serde(rename = "exceptionTypes")]
     pub exception_types: ExceptionTypes,
@@ -125,24 +1353,661 @@ pub struct Stats<'a> {
     // 请求总次数
     #[serde(rename = "totalRequests")]
     pub total_requests: i64,
+
+    // 成功的请求数（包含命中缓存的请求）
+    #[serde(rename = "successfulRequests")]
+    pub successful_requests: i64,
+
+    // 失败的请求数；所有错误类型的次数之和
+    #[serde(rename = "failedRequests")]
+    pub failed_requests: i64,
+    // 每秒请求数
+    #[serde(rename = "requestsPerSecond", serialize_with = "serialize_finite")]
+    pub requests_per_second: f64,
+    // 每秒成功请求数
+    #[serde(
+        rename = "successfulRequestsPerSecond",
+        serialize_with = "serialize_finite"
+    )]
+    pub successful_requests_per_second: f64,
     // 缓存命中率（百分比）
-    #[serde(rename = "cacheHitRate")]
+    #[serde(rename = "cacheHitRate", serialize_with = "serialize_finite")]
     pub cache_hit_rate: f64,
     // 缓存命中次数
     #[serde(rename = "cacheHit")]
     pub cache_hit: i64,
+    // 连接复用率；较低时说明连接没有保持 keep-alive，频繁建立新连接
+    #[serde(rename = "connectionReuseRate", serialize_with = "serialize_finite")]
+    pub connection_reuse_rate: f64,
     // HTTP 状态码统计（键为状态码，值为次数）
     #[serde(rename = "httpStatusCodes")]
     pub http_status_codes: HashMap<String, i64>,
+    // HTTP 状态码分组统计（键为 `1xx` - `5xx` 和 `other`，值为次数）
+    #[serde(rename = "httpStatusGroups")]
+    pub http_status_groups: HashMap<String, i64>,
+    // 延迟直方图（键为桶的上边界，单位毫秒，最后一个为 `+Inf`；值为延迟小于等于边界的累计请求数）
+    // 和 Prometheus 的 histogram 一致；需要设置 `RequestStatsConfig.latency_buckets`
+    #[serde(rename = "latencyHistogram", skip_serializing_if = "HashMap::is_empty")]
+    pub latency_histogram: HashMap<String, i64>,
+    // 自定义请求结果统计（键为自定义的编号，值为次数）
+    #[serde(rename = "customResults", skip_serializing_if = "HashMap::is_empty")]
+    pub custom_results: HashMap<String, i64>,
+    // 重试统计；和第一次请求的数据分开统计
+    #[serde(rename = "retryStats")]
+    pub retry_stats: RetryStats,
+    // 代理统计（键为代理标识，直连为 `direct`）
+    #[serde(rename = "proxyBreakdown")]
+    pub proxy_breakdown: HashMap<String, ProxyBreakdown>,
+    // 请求各阶段的耗时（键为阶段名称）；使用 `RequestSpan::checkpoint` 记录
+    #[serde(rename = "stageDurations", skip_serializing_if = "HashMap::is_empty")]
+    pub stage_durations: HashMap<String, StageDuration>,
+    // 平均 DNS 解析耗时（毫秒）；没有统计 DNS 解析耗时时不输出
+    #[serde(
+        rename = "avgDnsLatencyMs",
+        skip_serializing_if = "is_zero",
+        serialize_with = "serialize_finite"
+    )]
+    pub avg_dns_latency_ms: f64,
+    // 平均限速队列等待耗时（毫秒）；不包含在平均请求延迟中，没有统计时不输出
+    #[serde(
+        rename = "avgQueueDelayMs",
+        skip_serializing_if = "is_zero",
+        serialize_with = "serialize_finite"
+    )]
+    pub avg_queue_delay_ms: f64,
+    // 请求总字节数；没有使用流量统计时不输出
+    #[serde(rename = "totalRequestBytes", skip_serializing_if = "is_zero")]
+    pub total_request_bytes: u64,
+    // 响应总字节数
+    #[serde(rename = "totalResponseBytes", skip_serializing_if = "is_zero")]
+    pub total_response_bytes: u64,
+    // 平均响应字节数
+    #[serde(
+        rename = "avgResponseBytes",
+        skip_serializing_if = "is_zero",
+        serialize_with = "serialize_finite"
+    )]
+    pub avg_response_bytes: f64,
     // 平均请求延迟（毫秒）
-    #[serde(rename = "averageRequestLatency")]
+    #[serde(rename = "averageRequestLatency", serialize_with = "serialize_finite")]
     pub average_request_latency: f64,
-    // 主机延迟（键为主机地址，值为延迟时间，单位：毫秒）
+
+    // 统计周期内最长的连续失败次数；用于发现服务中断
+    #[serde(rename = "maxConsecutiveFailures")]
+    pub max_consecutive_failures: i64,
+    // 主机延迟（键为主机地址，值为延迟统计，单位：毫秒）
     #[serde(rename = "hostsPingDelay")]
-    pub hosts_ping_delay: HashMap<String, f64>,
+    pub hosts_ping_delay: HashMap<String, HostPingStats>,
     // 系统资源使用情况
     #[serde(rename = "systemResources")]
     pub system_resources: SystemResources,
+    // 最近的错误上下文（键为错误类型，值为最近的几条上下文）
+    #[serde(rename = "recentErrors")]
+    pub recent_errors: HashMap<String, Vec<String>>,
+    // 和上一个周期相比的变化；需要开启 include_diff
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<StatsDiff>,
+    // 当前周期的文件清理结果；没有配置清理目录时为 None
+    #[serde(rename = "cleanStats", skip_serializing_if = "Option::is_none")]
+    pub clean_stats: Option<CleanStats>,
+}
+
+impl Stats<'_> {
+    /// 计算和上一个周期相比的变化
+    pub fn diff(&self, previous: &Stats) -> StatsDiff {
+        StatsDiff {
+            error_rate_delta: self.error_rate - previous.error_rate,
+            latency_delta_ms: self.average_request_latency - previous.average_request_latency,
+            request_count_delta: self.total_requests - previous.total_requests,
+            requests_per_second_delta: self.requests_per_second - previous.requests_per_second,
+            cache_hit_rate_delta: self.cache_hit_rate - previous.cache_hit_rate,
+            error_count_delta: self.exception_types.total() - previous.exception_types.total(),
+            cpu_usage_delta: self.system_resources.cpu_usage - previous.system_resources.cpu_usage,
+        }
+    }
+
+    /// 适合日志聚合工具的单行摘要，字段顺序固定：
+    /// `stats server=s1 scraper=spider1 total=1234 errors=0.012 lat_avg=45.3ms lat_p95=120.0ms cache=0.234 uptime=3600s`
+    ///
+    /// 值中的空白字符替换为 `_`，空值和无法计算的值输出为 `-`；
+    /// `lat_p95` 是延迟直方图中包含 95% 请求的桶边界，没有设置桶边界时为 `-`
+    pub fn to_line_summary(&self) -> String {
+        let token = |value: &str| match value.trim() {
+            "" => "-".to_string(),
+            value => value.replace(char::is_whitespace, "_"),
+        };
+        let p95 = self
+            .latency_p95_bucket()
+            .map_or_else(|| "-".to_string(), |p95| format!("{p95:.1}ms"));
+
+        format!(
+            "stats server={} scraper={} total={} errors={:.3} lat_avg={:.1}ms lat_p95={} cache={:.3} uptime={}s",
+            token(&self.base.server_name),
+            token(&self.base.scraper_name),
+            self.total_requests,
+            self.error_rate,
+            self.average_request_latency,
+            p95,
+            self.cache_hit_rate,
+            self.runtime_duration
+        )
+    }
+
+    /// 延迟直方图中累计请求数达到 95% 的最小桶边界（毫秒）；落在 `+Inf` 桶中时为 None
+    fn latency_p95_bucket(&self) -> Option<f64> {
+        let total = *self.latency_histogram.get("+Inf")?;
+        if total <= 0 {
+            return None;
+        }
+
+        let target = (total as f64 * 0.95).ceil() as i64;
+        let mut buckets: Vec<(f64, i64)> = self
+            .latency_histogram
+            .iter()
+            .filter_map(|(bound, count)| Some((bound.parse::<f64>().ok()?, *count)))
+            .filter(|(bound, _)| bound.is_finite())
+            .collect();
+        buckets.sort_by(|a, b| a.0.total_cmp(&b.0));
+        buckets
+            .into_iter()
+            .find(|(_, count)| *count >= target)
+            .map(|(bound, _)| bound)
+    }
+
+    /// 所有数值字段，键和序列化后的字段名一致，值统一转换为 f64
+    /// 嵌套结构体的字段使用 `.` 连接，如 `exceptionTypes.connectionError`；
+    /// 键不固定的字段（如 `httpStatusCodes`、`proxyBreakdown`）不包含在内
+    pub fn numeric_fields(&self) -> impl Iterator<Item = (&'static str, f64)> {
+        let e = &self.exception_types;
+        let r = &self.retry_stats;
+        let s = &self.system_resources;
+        [
+            ("schemaVersion", self.schema_version as f64),
+            ("requestFrequency", self.base.request_frequency as f64),
+            ("timePeriod.start", self.time_period.start as f64),
+            ("timePeriod.end", self.time_period.end as f64),
+            ("cycleCount", self.cycle_count as f64),
+            ("restartedAt", self.restarted_at as f64),
+            ("sequenceNumber", self.sequence_number as f64),
+            ("errorRate", self.error_rate),
+            ("exceptionTypes.connectionError", e.connection_error as f64),
+            ("exceptionTypes.timeoutError", e.timeout_error as f64),
+            ("exceptionTypes.parseError", e.parse_error as f64),
+            ("exceptionTypes.statusCodeError", e.status_code_error as f64),
+            ("exceptionTypes.proxyError", e.proxy_error as f64),
+            ("exceptionTypes.rateLimited", e.rate_limited as f64),
+            ("exceptionTypes.captchaDetected", e.captcha_detected as f64),
+            ("runtimeDuration", self.runtime_duration as f64),
+            ("totalRequests", self.total_requests as f64),
+            ("successfulRequests", self.successful_requests as f64),
+            ("failedRequests", self.failed_requests as f64),
+            ("requestsPerSecond", self.requests_per_second),
+            (
+                "successfulRequestsPerSecond",
+                self.successful_requests_per_second,
+            ),
+            ("cacheHitRate", self.cache_hit_rate),
+            ("cacheHit", self.cache_hit as f64),
+            ("connectionReuseRate", self.connection_reuse_rate),
+            ("retryStats.totalRetries", r.total_retries as f64),
+            ("retryStats.retrySuccessRate", r.retry_success_rate),
+            ("retryStats.retryRate", r.retry_rate),
+            ("avgDnsLatencyMs", self.avg_dns_latency_ms),
+            ("avgQueueDelayMs", self.avg_queue_delay_ms),
+            ("totalRequestBytes", self.total_request_bytes as f64),
+            ("totalResponseBytes", self.total_response_bytes as f64),
+            ("avgResponseBytes", self.avg_response_bytes),
+            ("averageRequestLatency", self.average_request_latency),
+            (
+                "maxConsecutiveFailures",
+                self.max_consecutive_failures as f64,
+            ),
+            ("systemResources.cpuUsage", s.cpu_usage as f64),
+            (
+                "systemResources.memoryUsage.used",
+                s.memory_usage.used as f64,
+            ),
+            (
+                "systemResources.memoryUsage.total",
+                s.memory_usage.total as f64,
+            ),
+            ("systemResources.diskUsage.used", s.disk_usage.used as f64),
+            ("systemResources.diskUsage.total", s.disk_usage.total as f64),
+        ]
+        .into_iter()
+    }
+
+    /// 不是有限值（NaN 或者无穷大）的浮点数字段，包括 `numeric_fields` 不包含的键不固定的字段，
+    /// 如 `errorRateByType.timeoutError`、`proxyBreakdown.proxy-a.avgLatencyMs`、`diff.latencyDeltaMs`
+    pub(crate) fn non_finite_fields(&self) -> Vec<(String, f64)> {
+        let nested = self
+            .error_rate_by_type
+            .iter()
+            .map(|(k, v)| (format!("errorRateByType.{k}"), *v))
+            .chain(self.proxy_breakdown.iter().flat_map(|(k, p)| {
+                [
+                    (format!("proxyBreakdown.{k}.errorRate"), p.error_rate),
+                    (format!("proxyBreakdown.{k}.avgLatencyMs"), p.avg_latency_ms),
+                ]
+            }))
+            .chain(
+                self.stage_durations
+                    .iter()
+                    .map(|(k, d)| (format!("stageDurations.{k}.avg"), d.avg)),
+            )
+            .chain(self.system_resources.cpu_cores.iter().map(|c| {
+                (
+                    format!("systemResources.cpuCores.{}", c.index),
+                    c.usage as f64,
+                )
+            }))
+            .chain(self.diff.iter().flat_map(|d| {
+                [
+                    ("diff.errorRateDelta".to_string(), d.error_rate_delta),
+                    ("diff.latencyDeltaMs".to_string(), d.latency_delta_ms),
+                    (
+                        "diff.requestsPerSecondDelta".to_string(),
+                        d.requests_per_second_delta,
+                    ),
+                    ("diff.cacheHitRateDelta".to_string(), d.cache_hit_rate_delta),
+                    ("diff.cpuUsageDelta".to_string(), d.cpu_usage_delta as f64),
+                ]
+            }));
+
+        self.numeric_fields()
+            .map(|(k, v)| (k.to_string(), v))
+            .chain(nested)
+            .filter(|(_, v)| !v.is_finite())
+            .collect()
+    }
+
+    /// 错误率
+    pub fn error_rate(&self) -> f64 {
+        self.error_rate
+    }
+
+    /// 请求总次数
+    pub fn total_requests(&self) -> i64 {
+        self.total_requests
+    }
+
+    /// 平均请求延迟（毫秒）
+    pub fn average_latency_ms(&self) -> f64 {
+        self.average_request_latency
+    }
+
+    /// 缓存命中率
+    pub fn cache_hit_rate(&self) -> f64 {
+        self.cache_hit_rate
+    }
+
+    /// 每秒请求数
+    pub fn requests_per_second(&self) -> f64 {
+        self.requests_per_second
+    }
+
+    /// 所有类型的错误总数
+    pub fn error_count(&self) -> i64 {
+        self.exception_types.total()
+    }
+
+    /// 连接错误次数
+    pub fn connection_errors(&self) -> i64 {
+        self.exception_types.connection_error
+    }
+
+    /// 超时错误次数
+    pub fn timeout_errors(&self) -> i64 {
+        self.exception_types.timeout_error
+    }
+
+    /// 解析错误次数
+    pub fn parse_errors(&self) -> i64 {
+        self.exception_types.parse_error
+    }
+
+    /// 状态码异常次数
+    pub fn status_code_errors(&self) -> i64 {
+        self.exception_types.status_code_error
+    }
+
+    /// 代理错误次数
+    pub fn proxy_errors(&self) -> i64 {
+        self.exception_types.proxy_error
+    }
+
+    /// 被限流次数
+    pub fn rate_limited(&self) -> i64 {
+        self.exception_types.rate_limited
+    }
+
+    /// 触发验证码次数
+    pub fn captcha_detected(&self) -> i64 {
+        self.exception_types.captcha_detected
+    }
+
+    /// CPU 使用率（百分比）
+    pub fn cpu_usage(&self) -> f64 {
+        self.system_resources.cpu_usage as f64
+    }
+
+    /// 是否兼容指定的结构版本；兼容当前版本和上一个版本
+    pub fn is_compatible(schema_version: u32) -> bool {
+        schema_version == STATS_SCHEMA_VERSION
+            || schema_version.checked_add(1) == Some(STATS_SCHEMA_VERSION)
+    }
+
+    /// 复制一份不带生命周期的统计信息
+    pub fn to_owned(&self) -> OwnedStats {
+        self.clone().into_owned()
+    }
+
+    /// 转换成不带生命周期的统计信息
+    pub fn into_owned(self) -> OwnedStats {
+        OwnedStats(Stats {
+            base: Cow::Owned(self.base.into_owned()),
+            ..self
+        })
+    }
+}
+
+/// 不带生命周期的统计信息；base 为 owned，可以存入集合或者跨线程传递
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(transparent)]
+pub struct OwnedStats(Stats<'static>);
+
+impl Deref for OwnedStats {
+    type Target = Stats<'static>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for OwnedStats {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<OwnedStats> for Stats<'static> {
+    fn from(stats: OwnedStats) -> Self {
+        stats.0
+    }
+}
+
+// 终端中使用的单行摘要，如：
+// `[2024-01-15 10:00–10:05] requests: 1,234  errors: 1.2%  avg_latency: 45.3ms  cache: 23.4%`
+impl std::fmt::Display for Stats<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let start = format_millis(self.time_period.start);
+        let end = format_millis(self.time_period.end);
+        // 同一天时结束时间只显示时分；输出的是毫秒数时不省略
+        let same_day = start.contains(' ') && start.get(..10) == end.get(..10);
+        let end = match same_day {
+            true => end.get(11..).unwrap_or(&end),
+            false => &end,
+        };
+
+        write!(
+            f,
+            "[{}–{}] requests: {}  errors: {:.1}%  avg_latency: {:.1}ms  cache: {:.1}%",
+            start,
+            end,
+            format_thousands(self.total_requests),
+            self.error_rate * 100.0,
+            self.average_request_latency,
+            self.cache_hit_rate * 100.0
+        )
+    }
+}
+
+impl std::fmt::Display for OwnedStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+// 直接使用字段构造 JSON；结果和 `serde_json::to_value` 一致，性能对比见 `benches/stats_value.rs`
+impl From<&Stats<'_>> for serde_json::Value {
+    fn from(stats: &Stats<'_>) -> Self {
+        use serde_json::{Map, Value};
+
+        fn to_value<T: Serialize>(value: &T) -> Value {
+            serde_json::to_value(value).unwrap_or_default()
+        }
+
+        fn object<const N: usize>(fields: [(&str, Value); N]) -> Value {
+            Value::Object(
+                fields
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+            )
+        }
+
+        let base = &stats.base;
+        let e = &stats.exception_types;
+        let mut map = Map::new();
+        let mut insert = |k: &str, v: Value| {
+            map.insert(k.to_string(), v);
+        };
+
+        insert("schemaVersion", stats.schema_version.into());
+        insert("serverName", base.server_name.as_str().into());
+        insert("scraperName", base.scraper_name.as_str().into());
+        insert("projectCode", base.project_code.as_str().into());
+        insert("scraperType", base.scraper_type.as_str().into());
+        insert("requestFrequency", base.request_frequency.into());
+        for (k, v) in &base.extra {
+            insert(k, v.clone());
+        }
+        insert(
+            "timePeriod",
+            object([
+                ("start", stats.time_period.start.into()),
+                ("end", stats.time_period.end.into()),
+            ]),
+        );
+        insert("cycleCount", stats.cycle_count.into());
+        insert("restartedAt", stats.restarted_at.into());
+        if !is_zero(&stats.sequence_number) {
+            insert("sequenceNumber", stats.sequence_number.into());
+        }
+        if stats.stale {
+            insert("stale", true.into());
+        }
+        insert("errorRate", finite_or_zero(stats.error_rate).into());
+        insert(
+            "errorRateByType",
+            Value::Object(
+                stats
+                    .error_rate_by_type
+                    .iter()
+                    .map(|(k, v)| (k.clone(), finite_or_zero(*v).into()))
+                    .collect(),
+            ),
+        );
+        insert(
+            "exceptionTypes",
+            object([
+                ("connectionError", e.connection_error.into()),
+                ("timeoutError", e.timeout_error.into()),
+                ("parseError", e.parse_error.into()),
+                ("statusCodeError", e.status_code_error.into()),
+                ("proxyError", e.proxy_error.into()),
+                ("rateLimited", e.rate_limited.into()),
+                ("captchaDetected", e.captcha_detected.into()),
+            ]),
+        );
+        insert("runtimeDuration", stats.runtime_duration.into());
+        insert("totalRequests", stats.total_requests.into());
+        insert("successfulRequests", stats.successful_requests.into());
+        insert("failedRequests", stats.failed_requests.into());
+        insert(
+            "requestsPerSecond",
+            finite_or_zero(stats.requests_per_second).into(),
+        );
+        insert(
+            "successfulRequestsPerSecond",
+            finite_or_zero(stats.successful_requests_per_second).into(),
+        );
+        insert("cacheHitRate", finite_or_zero(stats.cache_hit_rate).into());
+        insert("cacheHit", stats.cache_hit.into());
+        insert(
+            "connectionReuseRate",
+            finite_or_zero(stats.connection_reuse_rate).into(),
+        );
+        for (k, codes) in [
+            ("httpStatusCodes", &stats.http_status_codes),
+            ("httpStatusGroups", &stats.http_status_groups),
+        ] {
+            insert(
+                k,
+                Value::Object(
+                    codes
+                        .iter()
+                        .map(|(k, v)| (k.clone(), (*v).into()))
+                        .collect(),
+                ),
+            );
+        }
+        insert(
+            "retryStats",
+            object([
+                ("totalRetries", stats.retry_stats.total_retries.into()),
+                (
+                    "retrySuccessRate",
+                    finite_or_zero(stats.retry_stats.retry_success_rate).into(),
+                ),
+                (
+                    "retryRate",
+                    finite_or_zero(stats.retry_stats.retry_rate).into(),
+                ),
+            ]),
+        );
+        if !stats.latency_histogram.is_empty() {
+            insert(
+                "latencyHistogram",
+                Value::Object(
+                    stats
+                        .latency_histogram
+                        .iter()
+                        .map(|(k, v)| (k.clone(), (*v).into()))
+                        .collect(),
+                ),
+            );
+        }
+        if !stats.custom_results.is_empty() {
+            insert("customResults", to_value(&stats.custom_results));
+        }
+        insert("proxyBreakdown", to_value(&stats.proxy_breakdown));
+        if !stats.stage_durations.is_empty() {
+            insert("stageDurations", to_value(&stats.stage_durations));
+        }
+        if !is_zero(&stats.avg_dns_latency_ms) {
+            insert(
+                "avgDnsLatencyMs",
+                finite_or_zero(stats.avg_dns_latency_ms).into(),
+            );
+        }
+        if !is_zero(&stats.avg_queue_delay_ms) {
+            insert(
+                "avgQueueDelayMs",
+                finite_or_zero(stats.avg_queue_delay_ms).into(),
+            );
+        }
+        if !is_zero(&stats.total_request_bytes) {
+            insert("totalRequestBytes", stats.total_request_bytes.into());
+        }
+        if !is_zero(&stats.total_response_bytes) {
+            insert("totalResponseBytes", stats.total_response_bytes.into());
+        }
+        if !is_zero(&stats.avg_response_bytes) {
+            insert(
+                "avgResponseBytes",
+                finite_or_zero(stats.avg_response_bytes).into(),
+            );
+        }
+        insert(
+            "averageRequestLatency",
+            finite_or_zero(stats.average_request_latency).into(),
+        );
+        insert(
+            "maxConsecutiveFailures",
+            stats.max_consecutive_failures.into(),
+        );
+        insert("hostsPingDelay", to_value(&stats.hosts_ping_delay));
+        insert("systemResources", to_value(&stats.system_resources));
+        insert(
+            "recentErrors",
+            Value::Object(
+                stats
+                    .recent_errors
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone().into()))
+                    .collect(),
+            ),
+        );
+        if let Some(diff) = &stats.diff {
+            insert("diff", to_value(diff));
+        }
+        if let Some(clean_stats) = &stats.clean_stats {
+            insert("cleanStats", to_value(clean_stats));
+        }
+
+        Value::Object(map)
+    }
+}
+
+// 按字段名处理统计信息；键和 `Serialize` 的字段名一致，嵌套的结构体为 JSON 对象
+impl From<&Stats<'_>> for HashMap<String, serde_json::Value> {
+    fn from(stats: &Stats<'_>) -> Self {
+        match serde_json::Value::from(stats) {
+            serde_json::Value::Object(map) => map.into_iter().collect(),
+            _ => HashMap::new(),
+        }
+    }
+}
+
+impl From<&OwnedStats> for HashMap<String, serde_json::Value> {
+    fn from(stats: &OwnedStats) -> Self {
+        Self::from(&stats.0)
+    }
+}
+
+// humantime 只能格式化 10000 年之前的时间，超出时 `to_string` 会 panic
+const MAX_FORMAT_MILLIS: i64 = 253_402_300_800_000;
+
+/// 毫秒级时间戳格式化为 `2024-01-15 10:00`（UTC）；超出可以格式化的范围时直接输出毫秒数
+fn format_millis(millis: i64) -> String {
+    if millis >= MAX_FORMAT_MILLIS {
+        return millis.to_string();
+    }
+
+    let time = std::time::UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64);
+    let s = humantime::format_rfc3339_seconds(time).to_string();
+    match (s.get(..10), s.get(11..16)) {
+        (Some(date), Some(time)) => format!("{date} {time}"),
+        _ => millis.to_string(),
+    }
+}
+
+/// 数字加上千分位分隔符，如 `1,234`
+fn format_thousands(n: i64) -> String {
+    let digits = n.unsigned_abs().to_string();
+    let mut s = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            s.push(',');
+        }
+        s.push(c);
+    }
+
+    match n < 0 {
+        true => format!("-{s}"),
+        false => s,
+    }
 }
 
 fn deserialize_duration<'de, D>(deserializer: D) -> Result<Duration, D::Error>
@@ -156,6 +2021,77 @@ where
         .into())
 }
 
+/// 序列化为 humantime 格式的字符串，如 `30s`、`5m`；和 `deserialize_duration` 对应
+fn serialize_duration<S>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(&humantime::format_duration(*duration))
+}
+
+fn serialize_optional_duration<S>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match duration {
+        Some(duration) => serialize_duration(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// 非有限值（NaN 或者无穷大）按 0.0 处理并记录警告，避免输出无效的 JSON
+fn finite_or_zero(value: f64) -> f64 {
+    if value.is_finite() {
+        value
+    } else {
+        tracing::warn!("统计信息中的浮点数不是有限值：{}，按 0.0 输出", value);
+        0.0
+    }
+}
+
+fn serialize_finite<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(finite_or_zero(*value))
+}
+
+fn serialize_finite_f32<S>(value: &f32, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f32(finite_or_zero(*value as f64) as f32)
+}
+
+fn serialize_finite_map<S>(map: &HashMap<String, f64>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(map.iter().map(|(k, v)| (k, finite_or_zero(*v))))
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+fn deserialize_optional_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+    let value: Option<String> = Deserialize::deserialize(deserializer)?;
+    value
+        .map(|value| {
+            humantime::Duration::from_str(&value)
+                .map(Into::into)
+                .map_err(|err| D::Error::custom(err.to_string()))
+        })
+        .transpose()
+}
+
 #[derive(Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub enum OutRespInfo {
@@ -218,3 +2154,413 @@ impl<'de> Deserialize<'de> for OutRespInfo {
         deserializer.deserialize_any(OutRespInfoVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use std::fmt::Debug;
+
+    /// 序列化为 JSON 再反序列化，结果和原值一致
+    fn round_trip<T>(value: &T)
+    where
+        T: Serialize + DeserializeOwned + PartialEq + Debug,
+    {
+        let json = serde_json::to_string(value).unwrap();
+        let parsed: T = serde_json::from_str(&json).unwrap();
+        assert_eq!(&parsed, value, "JSON：{json}");
+    }
+
+    fn stats_base() -> StatsBase {
+        StatsBase::builder()
+            .server_name("server-1")
+            .scraper_name("amazon")
+            .project_code("P001")
+            .scraper_type("list")
+            .request_frequency(10)
+            .extra("dataCenter", "us-east-1")
+            .extra("shard", 3)
+            .build()
+    }
+
+    fn system_resources() -> SystemResources {
+        SystemResources {
+            cpu_usage: 42.35,
+            memory_usage: Usage {
+                used: 2048,
+                total: 8192,
+            },
+            disk_usage: Usage {
+                used: 0,
+                total: u64::MAX,
+            },
+            cpu_cores: vec![CpuCoreUsage {
+                index: 0,
+                usage: 12.5,
+            }],
+            network_io: NetworkIO {
+                bytes_received: 1024,
+                bytes_sent: 512,
+                packets_received: 10,
+                packets_sent: 5,
+            },
+            disk_io: DiskIO {
+                read_bytes_per_sec: 100,
+                write_bytes_per_sec: 200,
+            },
+            process_memory: ProcessMemory {
+                rss_mb: 128,
+                virtual_mb: 1024,
+            },
+            thread_count: 8,
+            open_fd_count: 64,
+        }
+    }
+
+    fn exception_types() -> ExceptionTypes {
+        ExceptionTypes {
+            connection_error: 1,
+            timeout_error: 2,
+            parse_error: 3,
+            status_code_error: 4,
+            proxy_error: 5,
+            rate_limited: 6,
+            captcha_detected: 7,
+        }
+    }
+
+    fn full_stats(base: &StatsBase) -> Stats<'_> {
+        Stats {
+            schema_version: STATS_SCHEMA_VERSION,
+            base: Cow::Borrowed(base),
+            time_period: TimePeriod {
+                start: 1_700_000_000_000,
+                end: 1_700_000_060_000,
+            },
+            cycle_count: 3,
+            restarted_at: 1_699_999_000_000,
+            sequence_number: 7,
+            stale: true,
+            error_rate: 0.28,
+            error_rate_by_type: [("timeoutError".to_string(), 0.02)].into(),
+            exception_types: exception_types(),
+            runtime_duration: 1000,
+            total_requests: 100,
+            successful_requests: 72,
+            failed_requests: 28,
+            requests_per_second: 1.67,
+            successful_requests_per_second: 1.2,
+            cache_hit_rate: 0.5,
+            cache_hit: 36,
+            connection_reuse_rate: 0.9,
+            http_status_codes: [("200".to_string(), 72), ("500".to_string(), 4)].into(),
+            http_status_groups: [("2xx".to_string(), 72)].into(),
+            latency_histogram: [("100".to_string(), 50), ("+Inf".to_string(), 100)].into(),
+            custom_results: [("1".to_string(), 2)].into(),
+            retry_stats: RetryStats {
+                total_retries: 5,
+                retry_success_rate: 0.6,
+                retry_rate: 0.05,
+            },
+            proxy_breakdown: [(
+                "direct".to_string(),
+                ProxyBreakdown {
+                    requests: 100,
+                    error_rate: 0.28,
+                    avg_latency_ms: 120.5,
+                },
+            )]
+            .into(),
+            stage_durations: [("parse".to_string(), StageDuration { avg: 3.5 })].into(),
+            avg_dns_latency_ms: 2.0,
+            avg_queue_delay_ms: 15.0,
+            total_request_bytes: 1000,
+            total_response_bytes: 100_000,
+            avg_response_bytes: 1000.0,
+            average_request_latency: 120.5,
+            max_consecutive_failures: 4,
+            hosts_ping_delay: [(
+                "example.com".to_string(),
+                HostPingStats {
+                    min: 1.0,
+                    max: 3.0,
+                    avg: 2.0,
+                    p95: 3.0,
+                    lost: 1,
+                },
+            )]
+            .into(),
+            system_resources: system_resources(),
+            recent_errors: [(
+                "timeoutError".to_string(),
+                vec!["https://a.com".to_string()],
+            )]
+            .into(),
+            diff: Some(StatsDiff {
+                error_rate_delta: -0.1,
+                request_count_delta: 20,
+                ..Default::default()
+            }),
+            clean_stats: None,
+        }
+    }
+
+    #[test]
+    fn test_round_trip_small_types() {
+        round_trip(&TimePeriod {
+            start: 1_700_000_000_000,
+            end: 1_700_000_060_000,
+        });
+        round_trip(&TimePeriod::default());
+        round_trip(&Usage {
+            used: 1,
+            total: u64::MAX,
+        });
+        round_trip(&Usage::default());
+        round_trip(&exception_types());
+        round_trip(&ExceptionTypes::default());
+        round_trip(&system_resources());
+        round_trip(&SystemResources::default());
+        for info in [
+            OutRespInfo::None,
+            OutRespInfo::Url,
+            OutRespInfo::Head,
+            OutRespInfo::Body,
+        ] {
+            round_trip(&info);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_stats_base() {
+        round_trip(&stats_base());
+        round_trip(&StatsBase::default());
+
+        // 自定义元数据展开到顶层
+        let value = serde_json::to_value(stats_base()).unwrap();
+        assert_eq!(value["dataCenter"], "us-east-1");
+        assert_eq!(value["serverName"], "server-1");
+    }
+
+    #[test]
+    fn test_round_trip_config() {
+        round_trip(&RequestStatsConfig::default());
+        round_trip(&RequestStatsConfig {
+            target: vec![],
+            targets: vec![TargetConfig {
+                url: "ws://collector:5003".to_string(),
+                cycle: Duration::from_secs(10),
+                include_system_resources: false,
+                include_host_ping: true,
+            }],
+            reporting_cycle: Duration::MAX,
+            host_ping_timeout: Duration::from_millis(1500),
+            reporting_jitter: Duration::ZERO,
+            compression: Some(PushCompression::Zstd),
+            log_level: LogLevel::Debug,
+            snapshot_path: Some(PathBuf::from("/tmp/stats.json")),
+            snapshot_interval: Duration::MAX,
+            consecutive_failure_alarm: Some(0),
+            latency_buckets: Some(vec![]),
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_time_period_format() {
+        let period = TimePeriod {
+            start: 1_705_313_100_000,
+            end: 1_705_313_400_999,
+        };
+        assert_eq!(period.format_start(0), "2024-01-15T10:05:00Z");
+        assert_eq!(period.format_start(8), "2024-01-15T18:05:00+08:00");
+        assert_eq!(period.format_end(8), "2024-01-15T18:10:00+08:00");
+        assert_eq!(period.format_end(-11), "2024-01-14T23:10:00-11:00");
+
+        // 闰年和时间戳之前的时间
+        assert_eq!(format_iso8601(951_782_400_000, 0), "2000-02-29T00:00:00Z");
+        assert_eq!(format_iso8601(-1, -5), "1969-12-31T18:59:59-05:00");
+    }
+
+    #[test]
+    fn test_line_summary() {
+        let base = StatsBase::builder()
+            .server_name("s1")
+            .scraper_name("spider 1")
+            .build();
+        let mut stats = Stats {
+            base: Cow::Borrowed(&base),
+            total_requests: 1234,
+            error_rate: 0.0123,
+            average_request_latency: 45.33,
+            cache_hit_rate: 0.234,
+            runtime_duration: 3600,
+            latency_histogram: [("50", 1000), ("120.5", 1200), ("500", 1230), ("+Inf", 1234)]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.to_line_summary(),
+            "stats server=s1 scraper=spider_1 total=1234 errors=0.012 lat_avg=45.3ms \
+             lat_p95=120.5ms cache=0.234 uptime=3600s"
+        );
+
+        // 没有直方图和空值
+        stats.latency_histogram.clear();
+        stats.base = Cow::Owned(StatsBase::default());
+        let summary = stats.to_line_summary();
+        assert!(summary.starts_with("stats server=- scraper=- total=1234 "));
+        assert!(summary.contains(" lat_p95=- "));
+        assert!(!summary.contains('\n'));
+    }
+
+    #[test]
+    fn test_numeric_fields() {
+        let stats = Stats {
+            error_rate: 0.023,
+            average_request_latency: 45.3,
+            total_requests: 1234,
+            exception_types: ExceptionTypes {
+                connection_error: 3,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let fields: HashMap<_, _> = stats.numeric_fields().collect();
+        assert_eq!(fields["errorRate"], 0.023);
+        assert_eq!(fields["averageRequestLatency"], 45.3);
+        assert_eq!(fields["totalRequests"], 1234.0);
+        assert_eq!(fields["exceptionTypes.connectionError"], 3.0);
+        assert_eq!(fields["avgDnsLatencyMs"], 0.0);
+        assert_eq!(fields["sequenceNumber"], 0.0);
+
+        // 键和序列化后的字段名一致
+        let base = stats_base();
+        let stats = full_stats(&base);
+        let value = serde_json::to_value(&stats).unwrap();
+        for (name, v) in stats.numeric_fields() {
+            let mut field = &value;
+            for key in name.split('.') {
+                field = &field[key];
+            }
+            assert_eq!(field.as_f64(), Some(v), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_non_finite_serialized_as_zero() {
+        let stats = Stats {
+            error_rate: f64::NAN,
+            average_request_latency: f64::INFINITY,
+            avg_queue_delay_ms: f64::NEG_INFINITY,
+            ..Default::default()
+        };
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(value["errorRate"], 0.0);
+        assert_eq!(value["averageRequestLatency"], 0.0);
+        assert_eq!(value["avgQueueDelayMs"], 0.0);
+        assert_eq!(serde_json::Value::from(&stats), value);
+        assert!(serde_json::to_string(&stats)
+            .unwrap()
+            .contains("\"errorRate\":0.0"));
+
+        // 嵌套的浮点数同样按 0.0 输出
+        let stats = Stats {
+            error_rate_by_type: [("timeoutError".to_string(), f64::NAN)].into(),
+            proxy_breakdown: [(
+                "proxy-a".to_string(),
+                ProxyBreakdown {
+                    requests: 1,
+                    error_rate: f64::INFINITY,
+                    avg_latency_ms: f64::NAN,
+                },
+            )]
+            .into(),
+            stage_durations: [("parse".to_string(), StageDuration { avg: f64::NAN })].into(),
+            retry_stats: RetryStats {
+                retry_rate: f64::NAN,
+                ..Default::default()
+            },
+            system_resources: SystemResources {
+                cpu_usage: f32::NAN,
+                ..Default::default()
+            },
+            diff: Some(StatsDiff {
+                latency_delta_ms: f64::NEG_INFINITY,
+                cpu_usage_delta: f32::INFINITY,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut invalid: Vec<_> = stats
+            .non_finite_fields()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        invalid.sort();
+        assert_eq!(
+            invalid,
+            [
+                "diff.cpuUsageDelta",
+                "diff.latencyDeltaMs",
+                "errorRateByType.timeoutError",
+                "proxyBreakdown.proxy-a.avgLatencyMs",
+                "proxyBreakdown.proxy-a.errorRate",
+                "retryStats.retryRate",
+                "stageDurations.parse.avg",
+                "systemResources.cpuUsage",
+            ]
+        );
+
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(value["errorRateByType"]["timeoutError"], 0.0);
+        assert_eq!(value["proxyBreakdown"]["proxy-a"]["errorRate"], 0.0);
+        assert_eq!(value["proxyBreakdown"]["proxy-a"]["avgLatencyMs"], 0.0);
+        assert_eq!(value["stageDurations"]["parse"]["avg"], 0.0);
+        assert_eq!(value["retryStats"]["retryRate"], 0.0);
+        assert_eq!(value["systemResources"]["cpuUsage"], 0.0);
+        assert_eq!(value["diff"]["latencyDeltaMs"], 0.0);
+        assert_eq!(value["diff"]["cpuUsageDelta"], 0.0);
+        assert_eq!(serde_json::Value::from(&stats), value);
+    }
+
+    #[test]
+    fn test_stats_to_map() {
+        let base = stats_base();
+        let stats = full_stats(&base);
+        let map = HashMap::<String, serde_json::Value>::from(&stats);
+        assert_eq!(map.get("errorRate").and_then(|v| v.as_f64()), Some(0.28));
+        assert_eq!(map["totalRequests"], 100);
+        assert_eq!(map["serverName"], "server-1");
+        assert_eq!(map["dataCenter"], "us-east-1");
+        assert_eq!(map["exceptionTypes"]["connectionError"], 1);
+        assert_eq!(map["systemResources"]["memoryUsage"]["used"], 2048);
+        assert_eq!(map["timePeriod"]["start"], 1_700_000_000_000i64);
+
+        // 和序列化的结果一致
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(serde_json::Value::Object(map.into_iter().collect()), value);
+        let owned = stats.into_owned();
+        assert_eq!(
+            HashMap::<String, serde_json::Value>::from(&owned)["cacheHit"],
+            36
+        );
+    }
+
+    #[test]
+    fn test_round_trip_stats() {
+        let base = stats_base();
+        let stats = full_stats(&base);
+        round_trip(&stats.into_owned());
+
+        // 空集合和零值；省略的字段使用默认值
+        round_trip(&Stats::default().into_owned());
+        let empty = Stats {
+            base: Cow::Borrowed(&base),
+            ..Default::default()
+        };
+        round_trip(&empty.into_owned());
+    }
+}
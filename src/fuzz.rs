@@ -0,0 +1,56 @@
+// 模糊测试入口
+// 只在 `cfg(fuzzing)` 时编译，cargo fuzz 会自动设置；运行方式（需要 nightly 和 cargo-fuzz）：
+//
+//     cargo install cargo-fuzz
+//     cargo +nightly fuzz run update_stats
+//
+// fuzz/fuzz_targets/update_stats.rs 直接调用 `fuzz_update_stats`
+//
+// 检查的内容见 `testing::assert_stats_valid`；极端输入的单元测试在 testing.rs 中，不需要 cfg(fuzzing)
+use crate::testing::assert_stats_valid;
+use crate::{RequestInfo, RequestResult, StatsBase, SPIDER_STATS};
+use arbitrary::{Arbitrary, Unstructured};
+
+// 单次 `update_stats_with` 的参数
+#[derive(Debug, Arbitrary)]
+struct UpdateInput {
+    request_time: i64,
+    response_time: i64,
+    status_code: u16,
+    result: RequestResult,
+    retry_count: u8,
+    request_bytes: u64,
+    response_bytes: u64,
+    dns_duration_us: Option<u64>,
+    queue_delay_us: Option<u64>,
+    connection_reused: bool,
+}
+
+/// 用任意输入调用 `SPIDER_STATS.update_stats_with`，然后检查统计数据始终有效
+pub fn fuzz_update_stats(data: &[u8]) {
+    let mut u = Unstructured::new(data);
+    let Ok(inputs) = Vec::<UpdateInput>::arbitrary(&mut u) else {
+        return;
+    };
+
+    for input in inputs {
+        SPIDER_STATS.update_stats_with(
+            input.request_time,
+            input.response_time,
+            input.status_code,
+            input.result,
+            &RequestInfo {
+                retry_count: input.retry_count,
+                request_bytes: input.request_bytes,
+                response_bytes: input.response_bytes,
+                proxy_id: None,
+                dns_duration_us: input.dns_duration_us,
+                queue_delay_us: input.queue_delay_us,
+                connection_reused: input.connection_reused,
+            },
+        );
+    }
+
+    let base = StatsBase::default();
+    assert_stats_valid(&SPIDER_STATS.snapshot(&base));
+}
@@ -0,0 +1,293 @@
+// 使用 gRPC 推送统计信息；需要开启 grpc feature
+// 推送地址格式为 `grpc://host:port/pkg.Service/Method`，使用 proto/stats.proto 中的 `stats.Stats` 作为请求
+use crate::reporter::StatsReporter;
+use crate::{OwnedStats, Stats};
+use anyhow::{anyhow, Result};
+use futures_util::future::BoxFuture;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::{Channel, Endpoint};
+
+// 连接和单次推送的超时时间
+const GRPC_TIMEOUT: Duration = Duration::from_secs(10);
+
+// build.rs 根据 proto/stats.proto 生成的代码
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/stats.rs"));
+}
+
+// 推送到 gRPC 目标；第一次推送时建立连接，之后复用
+// 连接断开后 `Channel` 会自动重连
+pub struct GrpcPushHandle {
+    endpoint: Endpoint,
+    path: PathAndQuery,
+    channel: OnceCell<Channel>,
+    targets: Vec<String>,
+}
+
+impl GrpcPushHandle {
+    /// 解析推送地址；不会立即建立连接
+    pub fn new(url: &str) -> Result<GrpcPushHandle> {
+        let (authority, method) = url
+            .strip_prefix("grpc://")
+            .and_then(|rest| rest.split_once('/'))
+            .ok_or_else(|| {
+                anyhow!("gRPC 推送地址格式错误：{url}，应该为 grpc://host:port/pkg.Service/Method")
+            })?;
+        anyhow::ensure!(
+            !authority.is_empty()
+                && method.split_once('/').is_some_and(|(service, method)| {
+                    !service.is_empty() && !method.is_empty() && !method.contains('/')
+                }),
+            "gRPC 推送地址格式错误：{url}，应该为 grpc://host:port/pkg.Service/Method"
+        );
+
+        let endpoint = Endpoint::from_shared(format!("http://{authority}"))?
+            .connect_timeout(GRPC_TIMEOUT)
+            .timeout(GRPC_TIMEOUT);
+        Ok(GrpcPushHandle {
+            endpoint,
+            path: PathAndQuery::try_from(format!("/{method}"))?,
+            channel: OnceCell::new(),
+            targets: vec![url.to_string()],
+        })
+    }
+}
+
+impl StatsReporter for GrpcPushHandle {
+    fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let channel = self
+                .channel
+                .get_or_try_init(|| self.endpoint.connect())
+                .await?
+                .clone();
+            let mut client = tonic::client::Grpc::new(channel);
+            client.ready().await?;
+
+            let codec = ProstCodec::<proto::Stats, proto::PushResponse>::default();
+            let request = tonic::Request::new(proto::Stats::from(&**stats));
+            client.unary(request, self.path.clone(), codec).await?;
+            Ok(())
+        })
+    }
+
+    fn target_urls(&self) -> &[String] {
+        &self.targets
+    }
+}
+
+/// 转换 map 的值
+fn map_values<V, T>(map: &HashMap<String, V>, f: impl Fn(&V) -> T) -> HashMap<String, T> {
+    map.iter().map(|(k, v)| (k.clone(), f(v))).collect()
+}
+
+impl From<&Stats<'_>> for proto::Stats {
+    fn from(stats: &Stats) -> Self {
+        let base = &stats.base;
+        let e = &stats.exception_types;
+        let s = &stats.system_resources;
+        let usage = |u: &crate::Usage| proto::Usage {
+            used: u.used,
+            total: u.total,
+        };
+
+        proto::Stats {
+            schema_version: stats.schema_version,
+            base: Some(proto::StatsBase {
+                server_name: base.server_name.clone(),
+                scraper_name: base.scraper_name.clone(),
+                project_code: base.project_code.clone(),
+                scraper_type: base.scraper_type.clone(),
+                request_frequency: base.request_frequency,
+                // 字符串直接使用原值，其他类型使用 JSON 格式
+                extra: map_values(&base.extra, |v| {
+                    v.as_str()
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| v.to_string())
+                }),
+            }),
+            time_period: Some(proto::TimePeriod {
+                start: stats.time_period.start,
+                end: stats.time_period.end,
+            }),
+            cycle_count: stats.cycle_count,
+            restarted_at: stats.restarted_at,
+            sequence_number: stats.sequence_number,
+            stale: stats.stale,
+            error_rate: stats.error_rate,
+            error_rate_by_type: stats.error_rate_by_type.clone(),
+            exception_types: Some(proto::ExceptionTypes {
+                connection_error: e.connection_error,
+                timeout_error: e.timeout_error,
+                parse_error: e.parse_error,
+                status_code_error: e.status_code_error,
+                proxy_error: e.proxy_error,
+                rate_limited: e.rate_limited,
+                captcha_detected: e.captcha_detected,
+            }),
+            runtime_duration: stats.runtime_duration,
+            total_requests: stats.total_requests,
+            successful_requests: stats.successful_requests,
+            failed_requests: stats.failed_requests,
+            requests_per_second: stats.requests_per_second,
+            successful_requests_per_second: stats.successful_requests_per_second,
+            cache_hit_rate: stats.cache_hit_rate,
+            cache_hit: stats.cache_hit,
+            connection_reuse_rate: stats.connection_reuse_rate,
+            http_status_codes: stats.http_status_codes.clone(),
+            http_status_groups: stats.http_status_groups.clone(),
+            latency_histogram: stats.latency_histogram.clone(),
+            custom_results: stats.custom_results.clone(),
+            retry_stats: Some(proto::RetryStats {
+                total_retries: stats.retry_stats.total_retries,
+                retry_success_rate: stats.retry_stats.retry_success_rate,
+                retry_rate: stats.retry_stats.retry_rate,
+            }),
+            proxy_breakdown: map_values(&stats.proxy_breakdown, |p| proto::ProxyBreakdown {
+                requests: p.requests,
+                error_rate: p.error_rate,
+                avg_latency_ms: p.avg_latency_ms,
+            }),
+            stage_durations: map_values(&stats.stage_durations, |d| d.avg),
+            avg_dns_latency_ms: stats.avg_dns_latency_ms,
+            avg_queue_delay_ms: stats.avg_queue_delay_ms,
+            total_request_bytes: stats.total_request_bytes,
+            total_response_bytes: stats.total_response_bytes,
+            avg_response_bytes: stats.avg_response_bytes,
+            average_request_latency: stats.average_request_latency,
+            max_consecutive_failures: stats.max_consecutive_failures,
+            hosts_ping_delay: map_values(&stats.hosts_ping_delay, |h| proto::HostPingStats {
+                min: h.min,
+                max: h.max,
+                avg: h.avg,
+                p95: h.p95,
+                lost: h.lost as u32,
+            }),
+            system_resources: Some(proto::SystemResources {
+                cpu_usage: s.cpu_usage,
+                memory_usage: Some(usage(&s.memory_usage)),
+                disk_usage: Some(usage(&s.disk_usage)),
+                cpu_cores: s
+                    .cpu_cores
+                    .iter()
+                    .map(|c| proto::CpuCoreUsage {
+                        index: c.index as u64,
+                        usage: c.usage,
+                    })
+                    .collect(),
+                network_io: Some(proto::NetworkIo {
+                    bytes_received: s.network_io.bytes_received,
+                    bytes_sent: s.network_io.bytes_sent,
+                    packets_received: s.network_io.packets_received,
+                    packets_sent: s.network_io.packets_sent,
+                }),
+                disk_io: Some(proto::DiskIo {
+                    read_bytes_per_sec: s.disk_io.read_bytes_per_sec,
+                    write_bytes_per_sec: s.disk_io.write_bytes_per_sec,
+                }),
+                process_memory: Some(proto::ProcessMemory {
+                    rss_mb: s.process_memory.rss_mb,
+                    virtual_mb: s.process_memory.virtual_mb,
+                }),
+                thread_count: s.thread_count,
+                open_fd_count: s.open_fd_count,
+            }),
+            recent_errors: map_values(&stats.recent_errors, |values| proto::StringList {
+                values: values.clone(),
+            }),
+            diff: stats.diff.as_ref().map(|d| proto::StatsDiff {
+                error_rate_delta: d.error_rate_delta,
+                latency_delta_ms: d.latency_delta_ms,
+                request_count_delta: d.request_count_delta,
+                requests_per_second_delta: d.requests_per_second_delta,
+                cache_hit_rate_delta: d.cache_hit_rate_delta,
+                error_count_delta: d.error_count_delta,
+                cpu_usage_delta: d.cpu_usage_delta,
+            }),
+            clean_stats: stats.clean_stats.as_ref().map(|c| proto::CleanStats {
+                files_deleted: c.files_deleted,
+                files_skipped: c.files_skipped,
+                dirs_removed: c.dirs_removed,
+                bytes_freed: c.bytes_freed,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HostPingStats, StatsBase};
+    use prost::Message;
+
+    #[test]
+    fn test_grpc_push_handle_url() {
+        let handle = GrpcPushHandle::new("grpc://collector:50051/stats.Collector/Push").unwrap();
+        assert_eq!(handle.path.as_str(), "/stats.Collector/Push");
+        assert_eq!(handle.endpoint.uri().to_string(), "http://collector:50051/");
+        assert_eq!(
+            handle.target_urls(),
+            ["grpc://collector:50051/stats.Collector/Push"]
+        );
+
+        for url in [
+            "ws://collector:50051/stats.Collector/Push",
+            "grpc://collector:50051",
+            "grpc://collector:50051/stats.Collector",
+            "grpc://collector:50051/stats.Collector/Push/extra",
+            "grpc:///stats.Collector/Push",
+        ] {
+            assert!(GrpcPushHandle::new(url).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_stats_to_proto() {
+        let base = StatsBase::builder()
+            .server_name("server-1")
+            .extra("shard", 3)
+            .extra("region", "us-east")
+            .build();
+        let stats = Stats {
+            base: std::borrow::Cow::Borrowed(&base),
+            total_requests: 10,
+            error_rate: 0.1,
+            hosts_ping_delay: [(
+                "example.com".to_string(),
+                HostPingStats {
+                    avg: 2.0,
+                    lost: 1,
+                    ..Default::default()
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+
+        let message = proto::Stats::from(&stats);
+        let decoded = proto::Stats::decode(message.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(decoded.total_requests, 10);
+        assert_eq!(decoded.error_rate, 0.1);
+        let decoded_base = decoded.base.unwrap();
+        assert_eq!(decoded_base.server_name, "server-1");
+        assert_eq!(decoded_base.extra["shard"], "3");
+        assert_eq!(decoded_base.extra["region"], "us-east");
+        assert_eq!(decoded.hosts_ping_delay["example.com"].lost, 1);
+        assert!(decoded.diff.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_grpc_report_unreachable() {
+        let handle = GrpcPushHandle::new("grpc://127.0.0.1:1/stats.Collector/Push").unwrap();
+        let stats = Arc::new(Stats::default().into_owned());
+        assert!(handle.report(stats).await.is_err());
+        // 连接失败时不缓存连接，下一次推送重新连接
+        assert!(handle.channel.get().is_none());
+    }
+}
@@ -0,0 +1,366 @@
+// 健康检查；用于 Kubernetes 的存活和就绪探针
+use crate::alert::AlertField;
+use crate::{Stats, SPIDER_STATS_PUSH};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sysinfo::{System, SystemExt};
+
+// 内存使用率上限
+const MAX_MEMORY_USAGE: f64 = 0.9;
+
+// 当前的上报任务的心跳；还没有启动过上报任务时为 None
+// 每个上报任务使用自己的心跳，重新初始化时原来的任务退出不会影响新的任务
+pub(crate) static REPORTING_HEARTBEAT: Lazy<Mutex<Option<Arc<ReportingHeartbeat>>>> =
+    Lazy::new(Default::default);
+
+// 上报任务的心跳；每完成一个上报周期记录一次
+pub(crate) struct ReportingHeartbeat {
+    // 任务是否在运行；任务退出时设置为 false
+    alive: AtomicBool,
+    // 上一次完成上报周期的时间；还没有完成时为任务启动的时间
+    last_tick: Mutex<Instant>,
+    // 超过这个时间没有完成上报周期时认为任务已经卡住，如测试 hosts 或者回调阻塞
+    max_silence: Duration,
+}
+
+impl ReportingHeartbeat {
+    pub(crate) fn new(max_silence: Duration) -> Self {
+        Self {
+            alive: AtomicBool::new(true),
+            last_tick: Mutex::new(Instant::now()),
+            max_silence,
+        }
+    }
+
+    /// 记录完成了一个上报周期
+    pub(crate) fn tick(&self) {
+        *self.last_tick.lock() = Instant::now();
+    }
+
+    /// 标记任务已经退出
+    pub(crate) fn stop(&self) {
+        self.alive.store(false, Ordering::Relaxed);
+    }
+
+    fn check(&self, now: Instant) -> HealthCheck {
+        if !self.alive.load(Ordering::Relaxed) {
+            return HealthCheck::new("reporting_task", false, Some("上报任务未运行".into()));
+        }
+
+        let silence = now.saturating_duration_since(*self.last_tick.lock());
+        if silence > self.max_silence {
+            let message = format!("上报任务已经 {} 秒没有完成上报周期", silence.as_secs());
+            return HealthCheck::new("reporting_task", false, Some(message));
+        }
+        HealthCheck::new("reporting_task", true, None)
+    }
+}
+
+// 上一个上报周期的错误率；还没有完成上报周期时为 None
+pub(crate) static LAST_ERROR_RATE: Lazy<Mutex<Option<f64>>> = Lazy::new(Default::default);
+
+// 健康检查允许的错误率上限
+pub(crate) static MAX_ERROR_RATE: Lazy<Mutex<f64>> = Lazy::new(|| Mutex::new(0.5));
+
+/// 健康检查结果
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct HealthReport {
+    // 所有检查项都通过时为 true
+    pub healthy: bool,
+    // 每个检查项的结果
+    pub checks: Vec<HealthCheck>,
+}
+
+/// 单个检查项的结果
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct HealthCheck {
+    // 检查项名称
+    pub name: String,
+    // 是否通过
+    pub ok: bool,
+    // 未通过的原因或者附加信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl HealthCheck {
+    fn new(name: &str, ok: bool, message: Option<String>) -> HealthCheck {
+        HealthCheck {
+            name: name.to_string(),
+            ok,
+            message,
+        }
+    }
+}
+
+impl HealthReport {
+    /// 根据检查项生成结果
+    pub fn new(checks: Vec<HealthCheck>) -> HealthReport {
+        HealthReport {
+            healthy: checks.iter().all(|check| check.ok),
+            checks,
+        }
+    }
+
+    /// 转换为 HTTP 响应的状态码和 JSON 内容；健康时返回 200，否则返回 503
+    pub fn to_json_response(&self) -> (u16, String) {
+        let status = if self.healthy { 200 } else { 503 };
+        let body = serde_json::to_string(self).unwrap_or_default();
+        (status, body)
+    }
+}
+
+/// 执行内置的健康检查
+/// - `push_channel`：推送通道已经初始化且没有关闭
+/// - `reporting_task`：上报任务在运行，且最近 2 个上报周期内完成过上报
+/// - `memory`：系统内存使用率低于 90%
+/// - `error_rate`：上一个上报周期的错误率低于配置的上限
+pub fn health_check() -> HealthReport {
+    HealthReport::new(vec![
+        check_push_channel(),
+        check_reporting_task(),
+        check_memory(),
+        check_error_rate(),
+    ])
+}
+
+fn check_push_channel() -> HealthCheck {
+    let push = SPIDER_STATS_PUSH.lock().clone();
+    match push {
+        None => HealthCheck::new("push_channel", false, Some("推送通道未初始化".into())),
+        Some(handle) if handle.is_closed() => {
+            HealthCheck::new("push_channel", false, Some("推送通道已关闭".into()))
+        }
+        Some(_) => HealthCheck::new("push_channel", true, None),
+    }
+}
+
+fn check_reporting_task() -> HealthCheck {
+    let heartbeat = REPORTING_HEARTBEAT.lock().clone();
+    match heartbeat {
+        Some(heartbeat) => heartbeat.check(Instant::now()),
+        None => HealthCheck::new("reporting_task", false, Some("上报任务未运行".into())),
+    }
+}
+
+fn check_memory() -> HealthCheck {
+    let mut system = System::new();
+    system.refresh_memory();
+    check_memory_usage(system.used_memory(), system.total_memory())
+}
+
+fn check_memory_usage(used: u64, total: u64) -> HealthCheck {
+    if total == 0 {
+        return HealthCheck::new("memory", true, Some("无法获取内存信息".into()));
+    }
+
+    let usage = used as f64 / total as f64;
+    let message = Some(format!("内存使用率 {:.1}%", usage * 100.0));
+    HealthCheck::new("memory", usage < MAX_MEMORY_USAGE, message)
+}
+
+fn check_error_rate() -> HealthCheck {
+    check_error_rate_with(*LAST_ERROR_RATE.lock(), *MAX_ERROR_RATE.lock())
+}
+
+fn check_error_rate_with(error_rate: Option<f64>, max_error_rate: f64) -> HealthCheck {
+    match error_rate {
+        None => HealthCheck::new("error_rate", true, Some("还没有完成上报周期".into())),
+        Some(rate) => HealthCheck::new(
+            "error_rate",
+            rate < max_error_rate,
+            Some(format!("错误率 {:.1}%", rate * 100.0)),
+        ),
+    }
+}
+
+// 统计数据的健康阈值；`Option` 的阈值为 None 时不检查
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HealthThresholds {
+    // 错误率上限
+    pub max_error_rate: f64,
+    // 平均请求延迟上限（毫秒）
+    pub max_avg_latency_ms: f64,
+    // 缓存命中率下限
+    pub min_cache_hit_rate: Option<f64>,
+    // 统计周期内最长连续失败次数上限
+    pub max_consecutive_failures: Option<u64>,
+}
+
+impl Default for HealthThresholds {
+    fn default() -> Self {
+        Self {
+            max_error_rate: 0.05,
+            max_avg_latency_ms: 1000.0,
+            min_cache_hit_rate: None,
+            max_consecutive_failures: None,
+        }
+    }
+}
+
+// 不满足健康阈值的统计字段
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct HealthViolation {
+    pub field: AlertField,
+    // 当前值
+    pub value: f64,
+    // 阈值
+    pub threshold: f64,
+}
+
+impl Stats<'_> {
+    /// 所有开启的健康阈值都满足时返回 true
+    pub fn is_healthy(&self, thresholds: &HealthThresholds) -> bool {
+        self.health_report(thresholds).is_empty()
+    }
+
+    /// 返回所有不满足的健康阈值，用于诊断
+    pub fn health_report(&self, thresholds: &HealthThresholds) -> Vec<HealthViolation> {
+        // 字段，阈值，当前值超过阈值（而不是低于阈值）时不健康
+        let checks = [
+            (AlertField::ErrorRate, Some(thresholds.max_error_rate), true),
+            (
+                AlertField::AverageLatency,
+                Some(thresholds.max_avg_latency_ms),
+                true,
+            ),
+            (
+                AlertField::CacheHitRate,
+                thresholds.min_cache_hit_rate,
+                false,
+            ),
+            (
+                AlertField::ConsecutiveFailures,
+                thresholds.max_consecutive_failures.map(|v| v as f64),
+                true,
+            ),
+        ];
+
+        checks
+            .into_iter()
+            .filter_map(|(field, threshold, is_max)| {
+                let threshold = threshold?;
+                let value = field.value(self);
+                let violated = match is_max {
+                    true => value > threshold,
+                    false => value < threshold,
+                };
+                violated.then_some(HealthViolation {
+                    field,
+                    value,
+                    threshold,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health_report() {
+        let report = HealthReport::new(vec![
+            check_memory_usage(50, 100),
+            check_error_rate_with(None, 0.5),
+            check_error_rate_with(Some(0.1), 0.5),
+        ]);
+        assert!(report.healthy);
+        let (status, body) = report.to_json_response();
+        assert_eq!(status, 200);
+        let value: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(value["healthy"], true);
+        assert_eq!(value["checks"][0]["name"], "memory");
+
+        let report = HealthReport::new(vec![
+            check_memory_usage(95, 100),
+            check_error_rate_with(Some(0.6), 0.5),
+        ]);
+        assert!(!report.healthy);
+        assert!(report.checks.iter().all(|check| !check.ok));
+        assert_eq!(report.to_json_response().0, 503);
+    }
+
+    #[test]
+    fn test_reporting_heartbeat() {
+        let heartbeat = ReportingHeartbeat::new(Duration::from_secs(20));
+        let now = Instant::now();
+        assert!(heartbeat.check(now).ok);
+
+        // 超过 `max_silence` 没有完成上报周期
+        let check = heartbeat.check(now + Duration::from_secs(30));
+        assert!(!check.ok);
+        assert!(check.message.unwrap().contains("没有完成上报周期"));
+
+        heartbeat.tick();
+        assert!(heartbeat.check(Instant::now()).ok);
+
+        heartbeat.stop();
+        let check = heartbeat.check(Instant::now());
+        assert!(!check.ok);
+        assert_eq!(check.message.as_deref(), Some("上报任务未运行"));
+    }
+
+    #[test]
+    fn test_health_check() {
+        let report = health_check();
+        let names: Vec<_> = report.checks.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["push_channel", "reporting_task", "memory", "error_rate"]
+        );
+    }
+
+    #[test]
+    fn test_stats_health_thresholds() {
+        let mut stats = Stats {
+            error_rate: 0.01,
+            average_request_latency: 200.0,
+            cache_hit_rate: 0.3,
+            max_consecutive_failures: 2,
+            ..Default::default()
+        };
+        let thresholds = HealthThresholds::default();
+        assert!(stats.is_healthy(&thresholds));
+
+        // 只检查开启的阈值
+        let thresholds = HealthThresholds {
+            min_cache_hit_rate: Some(0.5),
+            max_consecutive_failures: Some(5),
+            ..Default::default()
+        };
+        stats.error_rate = 0.2;
+        stats.max_consecutive_failures = 5;
+        assert!(!stats.is_healthy(&thresholds));
+        assert_eq!(
+            stats.health_report(&thresholds),
+            vec![
+                HealthViolation {
+                    field: AlertField::ErrorRate,
+                    value: 0.2,
+                    threshold: 0.05,
+                },
+                HealthViolation {
+                    field: AlertField::CacheHitRate,
+                    value: 0.3,
+                    threshold: 0.5,
+                },
+            ]
+        );
+
+        let thresholds: HealthThresholds =
+            serde_json::from_str(r#"{"maxAvgLatencyMs":100.0}"#).unwrap();
+        assert_eq!(thresholds.max_error_rate, 0.05);
+        stats.error_rate = 0.0;
+        assert_eq!(
+            stats.health_report(&thresholds)[0].field,
+            AlertField::AverageLatency
+        );
+    }
+}
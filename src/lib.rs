@@ -1,32 +1,85 @@
 use anyhow::{anyhow, Result};
 use once_cell::sync::{Lazy, OnceCell};
 use parking_lot::Mutex;
-use std::collections::HashMap;
-use std::net::{IpAddr, SocketAddr, TcpStream};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::hash::BuildHasher;
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
 use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant, SystemTime};
-use sysinfo::{CpuExt, DiskExt, System, SystemExt};
 use tokio::runtime::Runtime;
-use tokio::sync::broadcast::Sender;
-use tracing::{error, info};
-mod clean;
+use tokio::sync::broadcast;
+use tracing::{debug, error, info, trace, warn};
+mod accumulator;
+pub mod alert;
+pub mod budget;
+pub mod clean;
 pub mod entity;
+#[cfg(fuzzing)]
+pub mod fuzz;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod health;
+mod logging;
+pub mod middleware;
+pub mod prelude;
 pub mod push;
+pub mod reporter;
+mod request_span;
+mod sliding;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod system_poller;
+#[cfg(any(test, fuzzing, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "tracing-layer")]
+mod tracing_layer;
+pub mod trend;
 mod websocket;
 
+pub use accumulator::{SessionSummary, StatsAccumulator};
 pub use entity::*;
+pub use logging::log_request;
+pub use request_span::RequestSpan;
+pub use sliding::SlidingWindowStats;
+pub use system_poller::{SystemPoller, SYSTEM_POLLER};
+#[cfg(feature = "tracing-layer")]
+pub use tracing_layer::StatsTracingLayer;
 
 // 使用泛型 T 的包装类型
 pub struct Global<T>(OnceCell<T>);
 
+// OnceCell 保证值只会被初始化一次，初始化之后只提供共享引用；
+// 和 OnceCell 一样，跨线程共享时要求 T 同时满足 Send + Sync（值可能在其他线程初始化）
+unsafe impl<T: Send + Sync> Sync for Global<T> {}
+unsafe impl<T: Send> Send for Global<T> {}
+
 // 为泛型实现 Deref trait
+// 未初始化时直接 panic；不确定是否初始化时使用 `try_deref`
 impl<T> Deref for Global<T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        unsafe { self.0.get_unchecked() }
+        match self.0.get() {
+            Some(v) => v,
+            None => panic!(
+                "Global<{}> 未初始化，请先调用 init",
+                std::any::type_name::<T>()
+            ),
+        }
+    }
+}
+
+impl<T> Default for Global<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -47,23 +100,141 @@ impl<T> Global<T> {
         self.0.get()
     }
 
+    // 安全解引用的方法；未初始化时返回 None
+    pub fn try_deref(&self) -> Option<&T> {
+        self.0.get()
+    }
+
     // 检查是否已初始化
     pub fn is_initialized(&self) -> bool {
         self.0.get().is_some()
     }
+
+    // 获取值；未初始化时使用 `f` 初始化
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        self.0.get_or_init(f)
+    }
 }
 
 /// 爬虫统计
-pub(crate) static SPIDER_STATS: Lazy<RequestStats> = Lazy::new(|| RequestStats::new());
+pub(crate) static SPIDER_STATS: Lazy<RequestStats> = Lazy::new(RequestStats::new);
+
+/// 会话汇总；每次推送统计信息时自动累计
+pub static GLOBAL_ACCUMULATOR: Lazy<Mutex<StatsAccumulator>> =
+    Lazy::new(|| Mutex::new(StatsAccumulator::new()));
+
+// 获取 hosts 和 base 的回调；`reinit_spider_vars` 时会被替换
+type GetHostsCall = Arc<dyn Fn() -> Result<Vec<String>> + Send + Sync>;
+type GetBaseCall = Arc<dyn Fn() -> StatsBase + Send + Sync>;
+
+// 推送通道；`reinit_spider_vars` 时会被替换，使用时先 clone 出来，不要持有锁推送
+pub(crate) static SPIDER_STATS_PUSH: Lazy<Mutex<Option<Arc<dyn push::PushHandle + Send + Sync>>>> =
+    Lazy::new(Default::default);
+
+// `init_spider_vars_with_reporter` 使用的上报通道；`reinit_spider_vars` 时继续使用，不替换为 websocket 推送
+static CUSTOM_REPORTER_PUSH: Lazy<Mutex<Option<Arc<dyn push::PushHandle + Send + Sync>>>> =
+    Lazy::new(Default::default);
+
+pub(crate) static GET_HOSTS: Lazy<Mutex<Option<GetHostsCall>>> = Lazy::new(Default::default);
+
+pub(crate) static GET_BASE: Lazy<Mutex<Option<GetBaseCall>>> = Lazy::new(Default::default);
+
+// 回调上一次成功返回的值；回调失败（`GET_BASE` panic）时使用，推送的数据标记为 `stale`
+static LAST_GOOD_HOSTS: Lazy<Mutex<Option<Vec<String>>>> = Lazy::new(Default::default);
+static LAST_GOOD_BASE: Lazy<Mutex<Option<StatsBase>>> = Lazy::new(Default::default);
+
+// 通过 `set_base` / `update_base` 设置的 base；设置后优先于 `GET_BASE`
+pub(crate) static CURRENT_BASE: Lazy<Mutex<Option<StatsBase>>> = Lazy::new(Default::default);
+
+// 是否根据上一个周期的每秒请求数自动计算请求频率
+pub(crate) static AUTO_REQUEST_FREQUENCY: AtomicBool = AtomicBool::new(false);
+// 自动计算的请求频率；设置后覆盖 base 中的请求频率
+pub(crate) static OBSERVED_REQUEST_FREQUENCY: Lazy<Mutex<Option<i64>>> =
+    Lazy::new(Default::default);
+
+// 自定义请求结果的名称（键为自定义的编号）；通过 `register_custom_result` 注册
+static CUSTOM_RESULT_NAMES: Lazy<Mutex<HashMap<u8, String>>> = Lazy::new(Default::default);
+
+/// 错误预算；初始化之后每个上报周期自动记录
+pub static ERROR_BUDGET: Global<budget::ErrorBudgetTracker> = Global::new();
+
+// 进程内的统计信息广播；推送之前发送，同一进程中的消费者不需要反序列化推送的 JSON
+// 第一次订阅或者推送时创建，重新初始化时不会替换
+pub static SPIDER_STATS_TYPED: Global<broadcast::Sender<OwnedStats>> = Global::new();
+
+// 进程内广播的容量；接收端处理太慢时丢失最早的数据
+const TYPED_STATS_CAPACITY: usize = 16;
+
+pub(crate) static SHUTDOWN_HANDLE: Lazy<Mutex<Option<ShutdownHandle>>> =
+    Lazy::new(Default::default);
+
+// 是否已经调用过 `init_spider_vars`；初始化和重新初始化时持有锁，避免并发初始化
+static SPIDER_INITIALIZED: Mutex<bool> = Mutex::new(false);
+
+// 系统资源中是否包含每个 CPU 核心的使用率
+pub(crate) static INCLUDE_PER_CORE_CPU: AtomicBool = AtomicBool::new(false);
+
+// 推送数据的压缩方式
+pub(crate) static PUSH_COMPRESSION: Lazy<Mutex<Option<PushCompression>>> =
+    Lazy::new(Default::default);
+
+// 推送时使用 `PushEnvelope` 包装统计信息
+pub(crate) static USE_ENVELOPE: AtomicBool = AtomicBool::new(false);
+
+// 日志中使用单行摘要（`Stats` 的 Display）输出统计信息；默认使用 `to_line_summary` 的 key=value 格式
+pub(crate) static COMPACT_LOG: AtomicBool = AtomicBool::new(false);
+// 推送统计信息的日志级别；`LogLevel as u8`
+pub(crate) static PUSH_LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+// 是否采集系统资源
+pub(crate) static COLLECT_SYSTEM_RESOURCES: AtomicBool = AtomicBool::new(true);
+
+// 内存中保留的历史统计信息条数；0 表示不保留
+pub(crate) static HISTORY_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+// 最近几个周期推送的统计信息，按时间顺序
+pub(crate) static STATS_HISTORY: Lazy<Mutex<VecDeque<OwnedStats>>> = Lazy::new(Default::default);
 
-pub(crate) static SPIDER_STATS_PUSH: Global<Sender<String>> = Global::new();
+// 上一次获取的网络 IO 累计值，用于计算增量
+pub(crate) static LAST_NETWORK_IO: Lazy<Mutex<Option<NetworkIO>>> = Lazy::new(Default::default);
 
-pub(crate) static GET_HOSTS: Global<Box<dyn Fn() -> Result<Vec<String>> + Send + Sync>> =
-    Global::new();
+// 上一次获取的磁盘 IO 累计值，用于计算吞吐
+pub(crate) static LAST_DISK_IO: Lazy<Mutex<Option<DiskIOSnapshot>>> = Lazy::new(Default::default);
 
-pub(crate) static GET_BASE: Global<Box<dyn Fn() -> StatsBase + Send + Sync>> = Global::new();
+// 工作线程数和线程名称可以通过环境变量 `STATS_RUNTIME_THREADS`、`STATS_RUNTIME_NAME` 设置
+pub(crate) static GLOBAL_RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    let num = runtime_threads(std::env::var("STATS_RUNTIME_THREADS").ok().as_deref());
+    let th_name = std::env::var("STATS_RUNTIME_NAME").unwrap_or_else(|_| "stats-util".to_string());
+    get_new_rn(num, &th_name)
+});
 
-pub(crate) static GLOBAL_RUNTIME: Lazy<Runtime> = Lazy::new(|| get_new_rn(3, "util"));
+// 日志中显示时间使用的时区（相对 UTC 的小时数）；启动时从环境变量 `STATS_TZ_OFFSET` 读取一次
+pub(crate) static TZ_OFFSET_HOURS: Lazy<i32> =
+    Lazy::new(|| tz_offset_hours(std::env::var("STATS_TZ_OFFSET").ok().as_deref()));
+
+/// 解析时区偏移；未设置或者格式错误时使用 UTC
+fn tz_offset_hours(value: Option<&str>) -> i32 {
+    match value.map(|v| v.trim().parse::<i32>()) {
+        Some(Ok(offset)) if (-23..=23).contains(&offset) => offset,
+        Some(_) => {
+            warn!("STATS_TZ_OFFSET 格式错误，使用默认值 0");
+            0
+        }
+        None => 0,
+    }
+}
+
+/// 解析运行时的工作线程数；未设置或者格式错误时使用默认值 3
+fn runtime_threads(value: Option<&str>) -> usize {
+    match value.map(|v| v.trim().parse::<usize>()) {
+        Some(Ok(num)) if num > 0 => num,
+        Some(_) => {
+            warn!("STATS_RUNTIME_THREADS 格式错误，使用默认值 3");
+            3
+        }
+        None => 3,
+    }
+}
 
 fn get_new_rn(num: usize, th_name: &str) -> Runtime {
     let rn = tokio::runtime::Builder::new_multi_thread()
@@ -75,12 +246,58 @@ fn get_new_rn(num: usize, th_name: &str) -> Runtime {
     rn
 }
 
+/// 生成 [0, jitter) 范围内的随机时长；使用当前时间的纳秒数作为随机源
+fn random_jitter(jitter: Duration) -> Duration {
+    let jitter_nanos = jitter.as_nanos();
+    if jitter_nanos == 0 {
+        return Duration::ZERO;
+    }
+
+    let now_nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+
+    Duration::from_nanos((now_nanos % jitter_nanos) as u64)
+}
+
+/// 快速生成 [0, 1) 范围内的伪随机数；每个线程使用独立的 XorShift64 状态
+fn fast_random() -> f64 {
+    thread_local! {
+        // RandomState 每次创建使用不同的随机 key；状态不能为 0
+        static STATE: std::cell::Cell<u64> = std::cell::Cell::new(
+            std::collections::hash_map::RandomState::new().hash_one(thread::current().id()) | 1,
+        );
+    }
+
+    STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        (x >> 11) as f64 / (1u64 << 53) as f64
+    })
+}
+
+/// 时钟；返回毫秒级时间戳，用于替换统计中使用的系统时间，如测试中使用固定的时间
+pub trait Clock: Fn() -> i64 + Send + Sync {}
+
+impl<T: Fn() -> i64 + Send + Sync> Clock for T {}
+
 fn get_now_millis() -> i64 {
     SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_millis() as i64
 }
+
+fn get_now_micros() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as i64
+}
 // 初始化爬虫推送
 pub fn init_spider_vars(
     config: RequestStatsConfig,
@@ -88,393 +305,4545 @@ pub fn init_spider_vars(
     get_base_call: Box<dyn Fn() -> StatsBase + Send + Sync>,
     get_host_call: Box<dyn Fn() -> Result<Vec<String>> + Send + Sync>,
 
-    // 清理过期文件目录; 过期时间；这个是根据文件创建时间来判断的
-    clean_paths: Option<(Vec<String>, Duration)>,
-) -> Result<()> {
-    let s = push::load_broadcast_chan(config.target.clone());
-
-    SPIDER_STATS_PUSH
-        .init(s)
-        .map_err(|err| anyhow!("{:?}", err))?;
+    // 文件清理配置；每个周期按顺序执行
+    clean_configs: Vec<clean::CleanConfig>,
+) -> Result<ShutdownHandle> {
+    init_spider_vars_with_alerts(config, get_base_call, get_host_call, clean_configs, None)
+}
 
-    GET_HOSTS
-        .init(get_host_call)
-        .map_err(|e| anyhow!("设置 get host call 失败"))?;
+// 初始化爬虫推送；每次推送统计数据之后检查告警规则
+pub fn init_spider_vars_with_alerts(
+    config: RequestStatsConfig,
+    get_base_call: Box<dyn Fn() -> StatsBase + Send + Sync>,
+    get_host_call: Box<dyn Fn() -> Result<Vec<String>> + Send + Sync>,
 
-    GET_BASE
-        .init(get_base_call)
-        .map_err(|e| anyhow!("设置 get base call 失败"))?;
+    // 文件清理配置；每个周期按顺序执行
+    clean_configs: Vec<clean::CleanConfig>,
 
-    // 开启线程；定时去发送任务信息
-    thread::spawn(move || loop {
-        thread::sleep(config.reporting_cycle);
+    // 告警配置; 告警回调
+    alerts: Option<(alert::AlertConfig, alert::AlertCallback)>,
+) -> Result<ShutdownHandle> {
+    let mut initialized = SPIDER_INITIALIZED.lock();
+    anyhow::ensure!(
+        !*initialized,
+        "统计上报已经初始化；重新配置请使用 reinit_spider_vars"
+    );
+    check_config(&config, true, &get_host_call)?;
 
-        let host = match GET_HOSTS() {
-            Ok(s) => Some((s, config.host_test_port)),
-            Err(err) => {
-                error!("获取 hosts 数据失败：{}", err);
-                None
-            }
-        };
+    let push = Arc::from(push::load_push_handle(config.target.clone()));
+    let handle = start_spider_reporting(
+        config,
+        get_base_call,
+        get_host_call,
+        clean_configs,
+        alerts,
+        push,
+    )?;
+    *CUSTOM_REPORTER_PUSH.lock() = None;
+    *initialized = true;
+    Ok(handle)
+}
 
-        let base = GET_BASE();
+// 初始化爬虫推送；使用 `reporter` 上报统计信息，不使用 `config.target` 推送到 websocket
+pub fn init_spider_vars_with_reporter(
+    config: RequestStatsConfig,
+    get_base_call: Box<dyn Fn() -> StatsBase + Send + Sync>,
+    get_host_call: Box<dyn Fn() -> Result<Vec<String>> + Send + Sync>,
 
-        send_stats(&base, host);
+    // 文件清理配置；每个周期按顺序执行
+    clean_configs: Vec<clean::CleanConfig>,
 
-        if let Some((clean_paths, max_ts)) = &clean_paths {
-            for p in clean_paths {
-                if let Err(err) = clean::clean_old_files(p, *max_ts) {
-                    error!("删除 {p} 目录下的过期文件失败 : {}", err);
-                }
-            }
-        }
-    });
+    // 告警配置; 告警回调
+    alerts: Option<(alert::AlertConfig, alert::AlertCallback)>,
 
-    Ok(())
-}
+    // 上报方式；多个上报目标使用 `StatsReporterChain`
+    reporter: Box<dyn reporter::StatsReporter + Send + Sync>,
+) -> Result<ShutdownHandle> {
+    let mut initialized = SPIDER_INITIALIZED.lock();
+    anyhow::ensure!(
+        !*initialized,
+        "统计上报已经初始化；重新配置请使用 reinit_spider_vars"
+    );
+    check_config(&config, false, &get_host_call)?;
 
-// 更新爬虫统计状态
-pub fn update_stats(
-    request_time: i64,
-    response_time: i64,
-    status_code: u16,
-    result: RequestResult, // 使用枚举表示请求结果
-) {
-    SPIDER_STATS.update_stats(request_time, response_time, status_code, result)
+    let push: Arc<dyn push::PushHandle + Send + Sync> =
+        Arc::new(reporter::ReporterPushHandle::new(reporter));
+    let handle = start_spider_reporting(
+        config,
+        get_base_call,
+        get_host_call,
+        clean_configs,
+        alerts,
+        push.clone(),
+    )?;
+    *CUSTOM_REPORTER_PUSH.lock() = Some(push);
+    *initialized = true;
+    Ok(handle)
 }
 
-// 更新爬虫统计状态
-pub fn send_stats(
-    base: &StatsBase,
+// 重新初始化爬虫推送，如重新加载配置之后
+// 先停止当前的上报任务（退出前使用原来的推送目标推送当前周期的统计数据），再替换推送目标并启动新的上报任务；
+// 使用 `init_spider_vars_with_reporter` 初始化时继续使用原来的上报方式，忽略 `config.target`；
+// 没有初始化过时和 `init_spider_vars_with_alerts` 一样
+pub fn reinit_spider_vars(
+    config: RequestStatsConfig,
+    get_base_call: Box<dyn Fn() -> StatsBase + Send + Sync>,
+    get_host_call: Box<dyn Fn() -> Result<Vec<String>> + Send + Sync>,
 
-    // 用于测试 hosts 的延迟
-    host_info: Option<(Vec<String>, u16)>,
-) {
-    let stats = SPIDER_STATS.to_stats_and_reset(base, host_info);
+    // 文件清理配置；每个周期按顺序执行
+    clean_configs: Vec<clean::CleanConfig>,
 
-    let msg = serde_json::to_string(&stats).unwrap();
+    // 告警配置; 告警回调
+    alerts: Option<(alert::AlertConfig, alert::AlertCallback)>,
+) -> Result<ShutdownHandle> {
+    let mut initialized = SPIDER_INITIALIZED.lock();
+    let reporter_push = CUSTOM_REPORTER_PUSH.lock().clone();
+    // 配置不合法时保留当前的上报任务
+    check_config(&config, reporter_push.is_none(), &get_host_call)?;
 
-    if let Err(err) = SPIDER_STATS_PUSH.send(msg) {
-        info!("发送统计信息失败：{}", err);
+    let handle = SHUTDOWN_HANDLE.lock().take();
+    if let Some(handle) = handle {
+        if let Err(err) = handle.shutdown() {
+            warn!("停止统计上报任务失败：{}", err);
+        }
+        // 当前周期的数据已经推送并清空，同步到快照，避免新的上报任务重复恢复
+        SPIDER_STATS.persist_snapshot();
     }
 
-    let msg = serde_json::to_string_pretty(&stats).unwrap();
-    info!("发送统计信息: {}", msg);
+    let push = match reporter_push {
+        Some(push) => push,
+        None => Arc::from(push::load_push_handle(config.target.clone())),
+    };
+    let handle = start_spider_reporting(
+        config,
+        get_base_call,
+        get_host_call,
+        clean_configs,
+        alerts,
+        push,
+    )?;
+    *initialized = true;
+    Ok(handle)
 }
 
-pub struct RequestStats {
-    inner: Mutex<InnerStats>,
+/// 检查配置，所有不合法的配置项合并为一个错误
+fn check_config(
+    config: &RequestStatsConfig,
+    require_target: bool,
+    get_host_call: &(dyn Fn() -> Result<Vec<String>> + Send + Sync),
+) -> Result<()> {
+    // 只在端口为 0 时调用回调；回调没有返回 hosts 时不使用端口
+    let pings_hosts =
+        config.host_test_port != 0 || !matches!(get_host_call(), Ok(hosts) if hosts.is_empty());
+    let errors = config.validation_errors(require_target, pings_hosts);
+    anyhow::ensure!(errors.is_empty(), "配置不合法：{}", errors.join("; "));
+    Ok(())
 }
 
-impl RequestStats {
-    /// 创建一个新的统计实例，并记录初始化时间和开始时间
-    pub fn new() -> Self {
-        Self {
-            inner: Mutex::new(InnerStats::new()),
-        }
+/// 使用空实现初始化全局变量，用于不需要统计的测试；不连接推送服务，也不启动上报任务
+/// 之后全局的统计更新全部丢弃，推送统计信息时不发送到任何目标；再调用 `init_spider_vars` 时恢复统计
+pub fn init_noop_stats() {
+    SPIDER_STATS.noop.store(true, Ordering::Relaxed);
+    *SPIDER_STATS_PUSH.lock() = Some(Arc::new(push::NoopPushHandle));
+    *GET_HOSTS.lock() = Some(Arc::new(|| Ok(Vec::new())));
+    *GET_BASE.lock() = Some(Arc::new(StatsBase::default));
+}
+
+/// 应用配置，设置推送目标和回调，并启动上报任务
+fn start_spider_reporting(
+    config: RequestStatsConfig,
+    get_base_call: Box<dyn Fn() -> StatsBase + Send + Sync>,
+    get_host_call: Box<dyn Fn() -> Result<Vec<String>> + Send + Sync>,
+    clean_configs: Vec<clean::CleanConfig>,
+    alerts: Option<(alert::AlertConfig, alert::AlertCallback)>,
+    push: Arc<dyn push::PushHandle + Send + Sync>,
+) -> Result<ShutdownHandle> {
+    if let Some(compression) = config.compression {
+        anyhow::ensure!(
+            compression.is_enabled(),
+            "推送数据使用 {} 压缩需要开启对应的 feature",
+            compression.name()
+        );
     }
 
-    /// 更新统计信息的方法
-    pub fn update_stats(
-        &self,
-        request_time: i64,
-        response_time: i64,
-        status_code: u16,
-        result: RequestResult, // 使用枚举表示请求结果
-    ) {
-        self.inner
-            .lock()
-            .update_stats(request_time, response_time, status_code, result);
+    SPIDER_STATS.set_recent_errors_limit(config.recent_errors_limit);
+    SPIDER_STATS.set_max_labels(config.max_labels);
+    SPIDER_STATS.set_precision(config.stats_precision);
+    GLOBAL_ACCUMULATOR
+        .lock()
+        .set_precision(config.stats_precision);
+    SPIDER_STATS.set_latency_buckets(config.latency_buckets.clone());
+    INCLUDE_PER_CORE_CPU.store(config.include_per_core_cpu, Ordering::Relaxed);
+    COMPACT_LOG.store(config.compact_log, Ordering::Relaxed);
+    PUSH_LOG_LEVEL.store(config.log_level as u8, Ordering::Relaxed);
+    USE_ENVELOPE.store(config.use_envelope, Ordering::Relaxed);
+    AUTO_REQUEST_FREQUENCY.store(config.auto_request_frequency, Ordering::Relaxed);
+    SPIDER_STATS.set_auto_request_frequency(config.auto_request_frequency);
+    SPIDER_STATS.set_custom_results_are_errors(config.custom_results_are_errors);
+    if !config.auto_request_frequency {
+        *OBSERVED_REQUEST_FREQUENCY.lock() = None;
+    }
+    *PUSH_COMPRESSION.lock() = config.compression;
+    COLLECT_SYSTEM_RESOURCES.store(config.collect_system_resources, Ordering::Relaxed);
+    if config.collect_system_resources {
+        SYSTEM_POLLER.set_interval(config.system_refresh_interval);
     }
+    HISTORY_SIZE.store(config.history_size, Ordering::Relaxed);
+    *health::MAX_ERROR_RATE.lock() = config.health_max_error_rate;
+    // 调用过 `init_noop_stats` 之后再初始化时恢复统计，否则之后的更新全部被丢弃
+    SPIDER_STATS.noop.store(false, Ordering::Relaxed);
 
-    /// 将当前统计数据拼装到 `Stats` 结构体中，并清空当前统计数据
-    /// 统计的时候需要传入 hosts + port 信息
-    pub fn to_stats_and_reset<'a>(
-        &self,
-        base: &'a StatsBase,
+    // 从快照恢复统计数据；之后由上报任务按 `snapshot_interval` 定时写入快照
+    match config.snapshot_path.clone() {
+        Some(path) => SPIDER_STATS.enable_snapshot(path),
+        None => SPIDER_STATS.disable_snapshot(),
+    }
 
-        // 用于测试 hosts 的延迟
-        host_info: Option<(Vec<String>, u16)>,
-    ) -> Stats<'a> {
-        let mut host_ping = HashMap::new();
+    // 替换推送通道；原来的推送通道释放后，已经连接的 websocket 任务发送完剩余的数据后退出
+    *SPIDER_STATS_PUSH.lock() = Some(push);
+    *GET_HOSTS.lock() = Some(Arc::from(get_host_call));
+    *GET_BASE.lock() = Some(Arc::from(get_base_call));
+    // 缓存的是原来的回调返回的值
+    *LAST_GOOD_HOSTS.lock() = None;
+    *LAST_GOOD_BASE.lock() = None;
 
-        if let Some((hosts, port)) = host_info {
-            let timeout = Duration::from_secs(3);
+    let (shutdown_tx, mut shutdown_rx) = tokio::sync::oneshot::channel();
+    // 上报任务正常结束时发送信号，用于 `shutdown` 等待任务结束
+    let (done_tx, done_rx) = mpsc::channel::<()>();
 
-            for host in hosts {
-                let connet_ts = match run_test_tcp(&host, port, timeout) {
-                    Ok(d) => d,
-                    Err(_) => timeout.as_micros() as u64,
-                };
+    // 在 GLOBAL_RUNTIME 中定时去发送任务信息；测试 hosts 和清理文件是阻塞操作，在阻塞线程池中执行
+    // 第一次上报增加随机抖动，避免多个实例同时推送
+    let config = Arc::new(config);
+    let clean_configs = Arc::new(clean_configs);
+    let first_tick = tokio::time::Instant::now()
+        + config.reporting_cycle
+        + random_jitter(config.reporting_jitter);
+    // 超过 2 个上报周期没有完成上报时健康检查失败；第一次上报有随机抖动
+    let heartbeat = Arc::new(health::ReportingHeartbeat::new(
+        config
+            .reporting_cycle
+            .saturating_mul(2)
+            .saturating_add(config.reporting_jitter),
+    ));
+    *health::REPORTING_HEARTBEAT.lock() = Some(heartbeat.clone());
+
+    // 连续失败的 hosts；上报任务和独立周期的推送目标共用
+    let eviction = config.evict_failing_hosts_after.map(|threshold| {
+        Arc::new(Mutex::new(HostEviction::new(
+            threshold,
+            config.evict_retry_after,
+        )))
+    });
 
-                // 0.6ms
-                // 微秒转成毫秒
-                let ms = connet_ts as f64 / 1000.0;
-                host_ping.insert(host, ms);
+    // 独立周期的推送目标各自启动一个任务，随上报任务一起退出
+    let mut target_tasks: Vec<_> = config
+        .targets
+        .iter()
+        .map(|target| {
+            GLOBAL_RUNTIME.spawn(push_target_loop(
+                target.clone(),
+                config.clone(),
+                eviction.clone(),
+            ))
+        })
+        .collect();
+    // 定时写入快照的任务同样随上报任务一起退出
+    if config.snapshot_path.is_some() {
+        target_tasks.push(GLOBAL_RUNTIME.spawn(snapshot_loop(config.snapshot_interval)));
+    }
+
+    GLOBAL_RUNTIME.spawn(async move {
+        // 任务退出（包括 panic）时标记上报任务已停止
+        let alive = ReportingAliveGuard(heartbeat.clone());
+        let _target_tasks = AbortOnDrop(target_tasks);
+
+        // interval 的周期不能为 0
+        let period = config.reporting_cycle.max(Duration::from_millis(1));
+        let mut interval = tokio::time::interval_at(first_tick, period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        // 上一个周期的统计信息，用于计算变化
+        let mut previous: Option<OwnedStats> = None;
+        loop {
+            // 收到退出信号（或者 handle 被释放）时，推送当前周期的数据后退出
+            let shutdown = tokio::select! {
+                _ = interval.tick() => false,
+                _ = &mut shutdown_rx => true,
+            };
+
+            // 先清理文件，清理结果随本周期的统计数据一起推送；退出时不清理
+            let clean_stats = if shutdown || clean_configs.is_empty() {
+                None
+            } else {
+                let clean_configs = clean_configs.clone();
+                tokio::task::spawn_blocking(move || run_clean_configs(&clean_configs))
+                    .await
+                    .map_err(|err| error!("文件清理任务异常：{}", err))
+                    .ok()
+            };
+
+            // 获取 hosts 是阻塞操作；测试 hosts 延迟使用异步连接，不占用阻塞线程
+            let cycle_config = config.clone();
+            let host_info =
+                tokio::task::spawn_blocking(move || cycle_host_info(&cycle_config, shutdown))
+                    .await
+                    .unwrap_or_default();
+            let (host_ping, hosts_stale) = match host_info {
+                Some((info, stale)) => (ping_hosts_with_eviction(info, &eviction).await, stale),
+                None => (HashMap::new(), false),
+            };
+
+            let cycle_config = config.clone();
+            let stats = tokio::task::spawn_blocking(move || {
+                collect_cycle_stats(&cycle_config, host_ping, hosts_stale)
+            })
+            .await;
+            let mut stats = match stats {
+                Ok(stats) => stats,
+                Err(err) => {
+                    error!("统计信息采集失败：{}", err);
+                    None
+                }
+            };
+
+            if let Some(stats) = &mut stats {
+                stats.clean_stats = clean_stats;
+                *health::LAST_ERROR_RATE.lock() = Some(stats.error_rate);
+            }
+
+            if let (true, Some(stats)) = (config.include_diff, &mut stats) {
+                stats.diff = previous.as_ref().map(|previous| stats.diff(previous));
+                previous = Some(stats.clone());
+            }
+
+            match &stats {
+                Some(stats) => push_stats(stats),
+                None => debug!("当前周期没有请求，跳过推送统计信息"),
+            }
+
+            if let (Some(stats), Some(tracker)) = (&stats, ERROR_BUDGET.try_deref()) {
+                tracker.record_stats(stats);
+                if tracker.is_budget_exhausted() {
+                    warn!("错误预算已耗尽：{:?}", tracker.budget());
+                }
+            }
+
+            if let (Some(stats), Some((alert_config, on_alert))) = (&stats, &alerts) {
+                for (rule, value) in alert_config.check(stats) {
+                    on_alert(rule, value);
+                }
+            }
+
+            let alarm = stats
+                .as_ref()
+                .and_then(|stats| consecutive_failure_alarm(&config, stats));
+            if let Some((rule, value)) = alarm {
+                match &alerts {
+                    Some((_, on_alert)) => on_alert(&rule, value),
+                    None => warn!("连续失败 {} 次，超过告警阈值 {}", value, rule.threshold),
+                }
+            }
+
+            heartbeat.tick();
+
+            if shutdown {
+                info!("统计上报任务退出");
+                break;
             }
         }
 
-        let mut data = self.inner.lock();
-        let mut d = data.to_stats_and_reset(base);
-        data.reset();
+        // 先标记为已停止，`shutdown` 返回之后启动的新任务不会被覆盖
+        drop(alive);
+        let _ = done_tx.send(());
+    });
 
-        d.hosts_ping_delay = host_ping;
+    let handle = ShutdownHandle {
+        inner: Arc::new(Mutex::new(Some((shutdown_tx, done_rx)))),
+    };
 
-        d
-    }
+    *SHUTDOWN_HANDLE.lock() = Some(handle.clone());
+
+    Ok(handle)
 }
 
-struct InnerStats {
-    // 对象初始化时间（毫秒级时间戳）
-    pub init_time: i64,
-    // 当前统计周期的开始时间（毫秒级时间戳）
-    pub start_time: i64,
+/// 本周期的最大连续失败次数超过 `consecutive_failure_alarm` 时返回告警规则和最大连续失败次数
+/// 使用周期内的最大值，周期中途已经恢复的连续失败同样告警
+fn consecutive_failure_alarm(
+    config: &RequestStatsConfig,
+    stats: &OwnedStats,
+) -> Option<(alert::AlertRule, f64)> {
+    let threshold = config.consecutive_failure_alarm?;
+    if stats.max_consecutive_failures <= threshold as i64 {
+        return None;
+    }
 
-    pub base: InnerStatsVal,
+    let rule = alert::AlertRule {
+        field: alert::AlertField::ConsecutiveFailures,
+        threshold: threshold as f64,
+        comparator: alert::Comparator::GreaterThan,
+    };
+    Some((rule, stats.max_consecutive_failures as f64))
 }
 
-impl Deref for InnerStats {
-    type Target = InnerStatsVal;
-    fn deref(&self) -> &Self::Target {
-        &self.base
+/// 获取当前周期需要测试的 hosts；`shutdown` 时不再测试 hosts，尽快推送
+/// 获取失败时使用上一次成功获取的 hosts，同时返回 true 表示使用的是缓存的值
+/// 开启 `skip_empty_cycles` 且当前周期没有请求时也不测试
+fn cycle_host_info(config: &RequestStatsConfig, shutdown: bool) -> Option<(HostPingInfo, bool)> {
+    if shutdown || (config.skip_empty_cycles && SPIDER_STATS.lock().total_requests == 0) {
+        return None;
     }
-}
 
-impl DerefMut for InnerStats {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.base
+    let get_hosts = GET_HOSTS.lock().clone()?;
+    let (hosts, stale) = hosts_or_cached(&get_hosts, &LAST_GOOD_HOSTS)?;
+    // icmp 测试不使用端口
+    if !hosts.is_empty() && config.host_test_port == 0 && config.host_ping_method == PingMethod::Tcp
+    {
+        warn!("host_test_port 为 0，tcp 测试 hosts 延迟会失败");
     }
-}
 
-#[derive(Default)]
-struct InnerStatsVal {
-    // 总请求数
-    pub total_requests: i64,
-    // 成功请求数
-    pub successful_requests: i64,
-    // 命中缓存的次数；在请求成功的情况下才统计
-    pub cache_hit: i64,
-    // 解析失败次数
-    pub parse_errors: i64,
-    // 超时错误次数
-    pub timeout_errors: i64,
-    // 连接失败次数
-    pub connection_errors: i64,
-    pub status_code_error: i64,
-    // HTTP 状态码统计（键为状态码，值为出现次数）
-    pub http_status_codes: HashMap<u16, i64>,
-    // 总请求延迟（毫秒）
-    pub total_latency: i64,
+    let info = HostPingInfo {
+        hosts,
+        port: config.host_test_port,
+        timeout: config.host_ping_timeout,
+        samples: config.host_ping_samples,
+        method: config.host_ping_method,
+        protocol: config.host_ping_protocol,
+        precision: config.stats_precision.min(MAX_STATS_PRECISION),
+    };
+    Some((info, stale))
 }
 
-impl InnerStats {
-    /// 创建一个新的统计实例，并记录初始化时间和开始时间
-    fn new() -> Self {
-        let current_time = get_now_millis();
-        Self {
-            init_time: current_time,
-            start_time: current_time,
-            base: Default::default(),
-        }
+/// 采集一个周期的统计数据；hosts 延迟已经测试完成
+/// `hosts_stale` 为 true 时 hosts 延迟使用的是缓存的 hosts
+fn collect_cycle_stats(
+    config: &RequestStatsConfig,
+    host_ping: HashMap<String, HostPingStats>,
+    hosts_stale: bool,
+) -> Option<OwnedStats> {
+    let (base, base_stale) = current_base_with_stale();
+
+    if config.skip_empty_cycles && SPIDER_STATS.lock().total_requests == 0 {
+        return None;
     }
 
-    /// 更新统计信息的方法
-    pub fn update_stats(
-        &mut self,
-        request_time: i64,
-        response_time: i64,
-        status_code: u16,
-        result: RequestResult, // 使用枚举表示请求结果
-    ) {
-        // 增加总请求数
-        self.total_requests += 1;
+    let mut stats = SPIDER_STATS
+        .to_stats_and_reset_with_ping(&base, host_ping)
+        .into_owned();
+    stats.stale = hosts_stale || base_stale;
+    Some(stats)
+}
 
-        // 计算请求延迟
-        let latency = response_time - request_time;
-        self.total_latency += latency;
+// 释放时把上报任务标记为已停止
+struct ReportingAliveGuard(Arc<health::ReportingHeartbeat>);
 
-        // 更新 HTTP 状态码统计
-        // 很多爬虫都是使用0 代替；这里直接忽略0 的情况
-        if status_code != 0 {
-            *self
-                .http_status_codes
-                .entry(status_code.clone())
-                .or_insert(0) += 1;
-        }
+impl Drop for ReportingAliveGuard {
+    fn drop(&mut self) {
+        self.0.stop();
+    }
+}
 
-        // 根据请求结果更新对应的统计数据
-        match result {
-            RequestResult::Successful => {
-                self.successful_requests += 1;
-            }
-            RequestResult::SuccessfulAndCache => {
-                self.successful_requests += 1;
-                self.cache_hit += 1;
-            }
+// 释放时停止所有任务
+struct AbortOnDrop(Vec<tokio::task::JoinHandle<()>>);
 
-            RequestResult::ParseError => {
-                self.parse_errors += 1;
-            }
-            RequestResult::TimeoutError => {
-                self.timeout_errors += 1;
-            }
-            RequestResult::ConnectionError => {
-                self.connection_errors += 1;
-            }
-            RequestResult::StatusCodeError => {
-                self.status_code_error += 1;
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.iter().for_each(|task| task.abort());
+    }
+}
+
+/// 按顺序执行所有清理配置，返回所有目录的清理结果之和
+fn run_clean_configs(clean_configs: &[clean::CleanConfig]) -> clean::CleanStats {
+    let mut total = clean::CleanStats::default();
+    for clean_config in clean_configs {
+        for (p, res) in clean_config.run() {
+            match res {
+                Ok(stats) => {
+                    info!(
+                        "Cleaned {} files, freed {} bytes from {p}",
+                        stats.files_deleted, stats.bytes_freed
+                    );
+                    total = total + stats;
+                }
+                Err(err) => error!("清理 {p} 目录下的文件失败 : {}", err),
             }
         }
     }
+    total
+}
 
-    /// 将当前统计数据拼装到 `Stats` 结构体中，并清空当前统计数据
-    pub fn to_stats_and_reset<'a>(&mut self, base: &'a StatsBase) -> Stats<'a> {
-        // 获取当前时间作为结束时间
-        let end_time = get_now_millis();
+// 退出信号发送端 + 上报任务结束信号接收端
+type ShutdownInner = Option<(tokio::sync::oneshot::Sender<()>, mpsc::Receiver<()>)>;
 
-        // 构造时间周期
-        let time_period = TimePeriod {
-            start: self.start_time,
-            end: end_time,
+/// 用于停止统计上报任务
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    inner: Arc<Mutex<ShutdownInner>>,
+}
+
+impl ShutdownHandle {
+    /// 通知上报任务推送当前周期的统计数据并退出，等待任务结束
+    /// 重复调用不会报错；会阻塞当前线程，不要在 GLOBAL_RUNTIME 的任务中调用
+    pub fn shutdown(&self) -> Result<()> {
+        let Some((sender, done_rx)) = self.inner.lock().take() else {
+            return Ok(());
         };
 
-        self.start_time = end_time;
+        // 任务已经退出时发送会失败，这里忽略
+        let _ = sender.send(());
 
-        // 构造异常类型统计
-        let exception_types = ExceptionTypes {
-            connection_error: self.connection_errors,
-            timeout_error: self.timeout_errors,
-            parse_error: self.parse_errors,
-            status_code_error: self.status_code_error,
-        };
+        // 任务异常退出时发送端被释放，recv 返回 Err
+        done_rx.recv().map_err(|_| anyhow!("统计上报任务异常退出"))
+    }
+}
 
-        // 计算错误率
-        let error_rate = if self.total_requests > 0 {
-            (self.parse_errors
-                + self.timeout_errors
-                + self.connection_errors
-                + self.status_code_error) as f64
-                / self.total_requests as f64
-        } else {
-            0.0
-        };
+// 停止统计上报；退出前会推送当前周期的统计数据
+pub fn shutdown() -> Result<()> {
+    let handle = SHUTDOWN_HANDLE.lock().clone();
+    handle
+        .ok_or_else(|| anyhow!("统计上报未初始化"))?
+        .shutdown()
+}
+
+// 更新爬虫统计状态
+pub fn update_stats(
+    request_time: i64,
+    response_time: i64,
+    status_code: u16,
+    result: RequestResult, // 使用枚举表示请求结果
+) {
+    SPIDER_STATS.update_stats_batch(&[(request_time, response_time, status_code, result)])
+}
+
+/// 更新爬虫统计状态，同时统计请求的附加信息（重试次数、流量、代理、DNS 耗时等）
+pub fn update_stats_with(
+    request_time: i64,
+    response_time: i64,
+    status_code: u16,
+    result: RequestResult,
+    info: &RequestInfo<'_>,
+) {
+    SPIDER_STATS.update_stats_with(request_time, response_time, status_code, result, info)
+}
+
+/// 设置推送统计信息使用的 base，如重新加载配置之后；设置后不再调用 `init_spider_vars` 传入的回调
+pub fn set_base(base: StatsBase) {
+    *CURRENT_BASE.lock() = Some(base);
+}
+
+/// 修改推送统计信息使用的 base；没有调用过 `set_base` 时在当前 base 的基础上修改
+pub fn update_base(f: impl FnOnce(&mut StatsBase)) {
+    // 调用回调时不能持有锁，`current_base` 会再次获取
+    let initial = CURRENT_BASE.lock().is_none().then(current_base);
+    let mut base = CURRENT_BASE.lock();
+    f(base.get_or_insert_with(|| initial.unwrap_or_default()));
+}
+
+/// 获取当前的 base；优先使用 `set_base` 设置的值，其次调用 `init_spider_vars` 传入的回调
+/// 开启 `auto_request_frequency` 时请求频率使用上一个周期的每秒请求数
+pub fn current_base() -> StatsBase {
+    current_base_with_stale().0
+}
+
+/// 获取当前的 base；回调 panic 时使用上一次成功返回的 base，同时返回 true 表示使用的不是最新的值
+fn current_base_with_stale() -> (StatsBase, bool) {
+    let current = CURRENT_BASE.lock().clone();
+    let get_base = GET_BASE.lock().clone();
+    let (mut base, stale) = match (current, get_base) {
+        (Some(base), _) => (base, false),
+        (None, Some(get_base)) => base_or_cached(&get_base, &LAST_GOOD_BASE),
+        (None, None) => (StatsBase::default(), false),
+    };
+
+    if let Some(frequency) = *OBSERVED_REQUEST_FREQUENCY.lock() {
+        base.request_frequency = frequency;
+    }
+    (base, stale)
+}
+
+/// 调用获取 base 的回调并缓存结果；回调 panic 时使用缓存的值，没有缓存时使用默认值
+fn base_or_cached(get_base: &GetBaseCall, cache: &Mutex<Option<StatsBase>>) -> (StatsBase, bool) {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| get_base())) {
+        Ok(base) => {
+            *cache.lock() = Some(base.clone());
+            (base, false)
+        }
+        Err(_) => {
+            warn!("获取 base 的回调 panic，使用上一次获取的 base");
+            (cache.lock().clone().unwrap_or_default(), true)
+        }
+    }
+}
+
+/// 调用获取 hosts 的回调并缓存结果；回调失败时使用缓存的值，没有缓存时返回 None
+fn hosts_or_cached(
+    get_hosts: &GetHostsCall,
+    cache: &Mutex<Option<Vec<String>>>,
+) -> Option<(Vec<String>, bool)> {
+    match get_hosts() {
+        Ok(hosts) => {
+            *cache.lock() = Some(hosts.clone());
+            Some((hosts, false))
+        }
+        Err(err) => match cache.lock().clone() {
+            Some(hosts) => {
+                warn!("获取 hosts 数据失败，使用上一次获取的 hosts：{}", err);
+                Some((hosts, true))
+            }
+            None => {
+                warn!("获取 hosts 数据失败：{}", err);
+                None
+            }
+        },
+    }
+}
+
+// 使用当前的 base 推送统计信息
+pub fn send_current_stats(
+    // 用于测试 hosts 的延迟
+    host_info: Option<HostPingInfo>,
+) {
+    let base = current_base();
+    send_stats(&base, host_info);
+}
+
+// 更新爬虫统计状态
+pub fn send_stats(
+    base: &StatsBase,
+
+    // 用于测试 hosts 的延迟
+    host_info: Option<HostPingInfo>,
+) {
+    let stats = SPIDER_STATS.to_stats_and_reset(base, host_info);
+    push_stats(&stats);
+}
+
+// 当前周期有请求时才推送统计信息；返回是否推送
+pub fn send_stats_if_nonempty(
+    base: &StatsBase,
+
+    // 用于测试 hosts 的延迟
+    host_info: Option<HostPingInfo>,
+) -> bool {
+    match SPIDER_STATS.to_stats_and_reset_if_nonempty(base, host_info) {
+        Some(stats) => {
+            push_stats(&stats);
+            true
+        }
+        None => {
+            debug!("当前周期没有请求，跳过推送统计信息");
+            false
+        }
+    }
+}
+
+/// 注册自定义请求结果（`RequestResult::Custom`）的名称；重复注册时覆盖之前的名称
+/// 注册后 `StatsTracingLayer` 可以通过名称识别 `stats.result`
+pub fn register_custom_result(discriminant: u8, name: &str) {
+    CUSTOM_RESULT_NAMES
+        .lock()
+        .insert(discriminant, name.to_string());
+}
+
+/// 自定义请求结果的名称；没有注册时返回 None
+pub fn custom_result_name(discriminant: u8) -> Option<String> {
+    CUSTOM_RESULT_NAMES.lock().get(&discriminant).cloned()
+}
+
+/// 根据名称查找自定义请求结果的编号；没有注册时返回 None
+pub fn custom_result_discriminant(name: &str) -> Option<u8> {
+    CUSTOM_RESULT_NAMES
+        .lock()
+        .iter()
+        .find_map(|(discriminant, n)| (n == name).then_some(*discriminant))
+}
+
+/// 订阅进程内推送的统计信息；只能收到订阅之后推送的数据
+/// 接收端处理太慢时会收到 `RecvError::Lagged`，丢失最早的数据
+pub fn subscribe_stats() -> broadcast::Receiver<OwnedStats> {
+    typed_stats_sender().subscribe()
+}
+
+fn typed_stats_sender() -> &'static broadcast::Sender<OwnedStats> {
+    SPIDER_STATS_TYPED.get_or_init(|| broadcast::channel(TYPED_STATS_CAPACITY).0)
+}
+
+/// 获取内存中保留的历史统计信息，按时间顺序；需要设置 `RequestStatsConfig.history_size`
+pub fn get_stats_history() -> Vec<OwnedStats> {
+    STATS_HISTORY.lock().iter().cloned().collect()
+}
+
+/// 记录历史统计信息；超过 `history_size` 时丢弃最早的数据
+fn record_history(history: &mut VecDeque<OwnedStats>, stats: &OwnedStats, history_size: usize) {
+    if history_size == 0 {
+        history.clear();
+        return;
+    }
+
+    while history.len() >= history_size {
+        history.pop_front();
+    }
+    history.push_back(stats.clone());
+}
+
+/// 独立的统计实例和推送通道；不使用全局的 `SPIDER_STATS`，多个实例之间互不影响
+/// 用于同一进程中运行多个爬虫，或者并行执行的集成测试
+#[derive(Clone)]
+pub struct SpiderStatsHandle {
+    stats: RequestStats,
+    push: Arc<dyn push::PushHandle + Send + Sync>,
+}
+
+impl SpiderStatsHandle {
+    /// 使用配置中的推送目标创建新的实例
+    pub fn new(config: &RequestStatsConfig) -> Self {
+        let stats = RequestStats::new();
+        stats.set_recent_errors_limit(config.recent_errors_limit);
+        stats.set_max_labels(config.max_labels);
+        stats.set_precision(config.stats_precision);
+        stats.set_custom_results_are_errors(config.custom_results_are_errors);
+        Self {
+            stats,
+            push: Arc::from(push::load_push_handle(config.target.clone())),
+        }
+    }
+
+    /// 使用已有的统计实例和推送通道创建
+    pub fn with_push_handle(
+        stats: RequestStats,
+        push: Arc<dyn push::PushHandle + Send + Sync>,
+    ) -> Self {
+        Self { stats, push }
+    }
+
+    /// 当前实例的统计数据
+    pub fn stats(&self) -> &RequestStats {
+        &self.stats
+    }
+
+    /// 当前实例的推送通道
+    pub fn push_handle(&self) -> &(dyn push::PushHandle + Send + Sync) {
+        &*self.push
+    }
+
+    /// 更新统计信息；参数和 `update_stats` 一致
+    pub fn update_stats(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult,
+    ) {
+        self.stats
+            .update_stats(request_time, response_time, status_code, result)
+    }
+
+    /// 更新统计信息；参数和 `update_stats_with` 一致
+    pub fn update_stats_with(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult,
+        info: &RequestInfo<'_>,
+    ) {
+        self.stats
+            .update_stats_with(request_time, response_time, status_code, result, info)
+    }
+
+    /// 推送当前实例的统计信息并清空；不计入全局的会话汇总和历史数据
+    pub fn send_stats(&self, base: &StatsBase, host_info: Option<HostPingInfo>) {
+        let stats = self.stats.to_stats_and_reset(base, host_info);
+        send_to_push_handle(Some(&*self.push), &stats, stats.to_owned());
+    }
+
+    /// 当前周期有请求时才推送统计信息；返回是否推送
+    pub fn send_stats_if_nonempty(
+        &self,
+        base: &StatsBase,
+        host_info: Option<HostPingInfo>,
+    ) -> bool {
+        match self.stats.to_stats_and_reset_if_nonempty(base, host_info) {
+            Some(stats) => {
+                send_to_push_handle(Some(&*self.push), &stats, stats.to_owned());
+                true
+            }
+            None => {
+                debug!("当前周期没有请求，跳过推送统计信息");
+                false
+            }
+        }
+    }
+}
+
+fn push_stats(stats: &Stats) {
+    record_request_frequency(stats);
+
+    let owned = stats.to_owned();
+    GLOBAL_ACCUMULATOR.lock().push(&owned);
+    record_history(
+        &mut STATS_HISTORY.lock(),
+        &owned,
+        HISTORY_SIZE.load(Ordering::Relaxed),
+    );
+
+    // 没有订阅者时不复制统计信息
+    let typed = typed_stats_sender();
+    if typed.receiver_count() > 0 {
+        let _ = typed.send(owned.clone());
+    }
+
+    let push = SPIDER_STATS_PUSH.lock().clone();
+    send_to_push_handle(push.as_deref(), stats, owned);
+}
+
+/// 按 `PUSH_LOG_LEVEL` 输出推送统计信息相关的日志
+macro_rules! push_log {
+    ($($arg:tt)+) => {
+        match LogLevel::from_u8(PUSH_LOG_LEVEL.load(Ordering::Relaxed)) {
+            LogLevel::Trace => trace!($($arg)+),
+            LogLevel::Debug => debug!($($arg)+),
+            LogLevel::Info => info!($($arg)+),
+            LogLevel::Warn => warn!($($arg)+),
+            LogLevel::Error => error!($($arg)+),
+        }
+    };
+}
+
+/// 开启 `auto_request_frequency` 时记录本周期的每秒请求数（四舍五入），之后的 `current_base` 使用
+fn record_request_frequency(stats: &Stats) {
+    if AUTO_REQUEST_FREQUENCY.load(Ordering::Relaxed) {
+        *OBSERVED_REQUEST_FREQUENCY.lock() = Some(stats.requests_per_second.round() as i64);
+    }
+}
+
+/// 按 `interval` 定时写入统计快照；写文件是阻塞操作，在阻塞线程池中执行
+async fn snapshot_loop(interval: Duration) {
+    // interval 的周期不能为 0
+    let period = interval.max(Duration::from_millis(1));
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if let Err(err) = tokio::task::spawn_blocking(|| SPIDER_STATS.persist_snapshot()).await {
+            warn!("写入统计快照失败：{}", err);
+        }
+    }
+}
+
+/// 按目标自己的周期推送当前统计周期的快照；不清空统计数据，也不计入会话汇总和历史数据
+/// 快照从上报任务上一次推送之后开始累计，比例和每秒请求数等数据不受推送周期影响
+async fn push_target_loop(
+    target: TargetConfig,
+    config: Arc<RequestStatsConfig>,
+    eviction: Option<Arc<Mutex<HostEviction>>>,
+) {
+    // 任务退出（随上报任务一起停止）时推送通道释放，连接不上推送目标的 websocket 任务也随之退出
+    let push = push::load_push_handle(vec![target.url.clone()]);
+
+    // interval 的周期不能为 0
+    let period = target.cycle.max(Duration::from_millis(1));
+    let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+
+        let (host_ping, hosts_stale) = match target.include_host_ping {
+            true => {
+                let cycle_config = config.clone();
+                let host_info =
+                    tokio::task::spawn_blocking(move || cycle_host_info(&cycle_config, false))
+                        .await
+                        .unwrap_or_default();
+                match host_info {
+                    Some((info, stale)) => (ping_hosts_with_eviction(info, &eviction).await, stale),
+                    None => (HashMap::new(), false),
+                }
+            }
+            false => (HashMap::new(), false),
+        };
+
+        let include_system_resources = target.include_system_resources;
+        let stats = tokio::task::spawn_blocking(move || {
+            let (base, base_stale) = current_base_with_stale();
+            let mut stats = SPIDER_STATS.snapshot(&base).into_owned();
+            stats.hosts_ping_delay = host_ping;
+            stats.stale = hosts_stale || base_stale;
+            if !include_system_resources {
+                stats.system_resources = SystemResources::default();
+            }
+            stats
+        })
+        .await;
+
+        match stats {
+            Ok(stats) => {
+                if let Err(err) = push.send(Arc::new(stats)) {
+                    push_log!("推送统计信息到 {} 失败：{}", target.url, err);
+                }
+            }
+            Err(err) => error!("统计信息采集失败：{}", err),
+        }
+    }
+}
+
+/// 推送统计信息并输出日志；`push` 为 None 时只输出日志
+fn send_to_push_handle(
+    push: Option<&(dyn push::PushHandle + Send + Sync)>,
+    stats: &Stats,
+    owned: OwnedStats,
+) {
+    match push {
+        Some(push) => {
+            if let Err(err) = push.send(Arc::new(owned)) {
+                push_log!("发送统计信息失败：{}", err);
+            }
+        }
+        None => push_log!("推送通道未初始化，跳过发送统计信息"),
+    }
+
+    let msg = log_summary(stats, COMPACT_LOG.load(Ordering::Relaxed));
+    let tz_offset = *TZ_OFFSET_HOURS;
+    push_log!(
+        "发送统计信息（{} ~ {}，CPU 使用率 {:.2}%）: {}",
+        stats.time_period.format_start(tz_offset),
+        stats.time_period.format_end(tz_offset),
+        stats.system_resources.cpu_usage,
+        msg
+    );
+}
+
+/// 日志中输出的统计信息；`compact_log` 时使用 `Stats` 的 Display，否则使用 `to_line_summary`
+fn log_summary(stats: &Stats, compact_log: bool) -> String {
+    match compact_log {
+        true => stats.to_string(),
+        false => stats.to_line_summary(),
+    }
+}
+
+/// 请求的附加信息；未知的字段使用默认值
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestInfo<'a> {
+    // 重试次数；0 表示第一次请求
+    pub retry_count: u8,
+    // 请求字节数；未知时为 0
+    pub request_bytes: u64,
+    // 响应字节数；未知时为 0
+    pub response_bytes: u64,
+    // 使用的代理；None 表示直连
+    pub proxy_id: Option<&'a str>,
+    // DNS 解析耗时（微秒）；None 表示不统计
+    pub dns_duration_us: Option<u64>,
+    // 在限速队列中等待的耗时（微秒）；不计入请求延迟
+    pub queue_delay_us: Option<u64>,
+    // 是否复用了连接池中的连接；未知时为 false
+    pub connection_reused: bool,
+}
+
+/// 批量更新的统计项；用于 `update_stats_items`，可以附带请求的附加信息
+#[derive(Debug, Clone)]
+pub struct StatsItem<'a> {
+    pub request_time: i64,
+    pub response_time: i64,
+    pub status_code: u16,
+    pub result: RequestResult,
+    pub info: RequestInfo<'a>,
+}
+
+impl<'a> StatsItem<'a> {
+    /// 没有附加信息的统计项
+    pub fn new(
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult,
+    ) -> Self {
+        Self {
+            request_time,
+            response_time,
+            status_code,
+            result,
+            info: RequestInfo::default(),
+        }
+    }
+
+    /// 设置附加信息
+    pub fn with_info(mut self, info: RequestInfo<'a>) -> Self {
+        self.info = info;
+        self
+    }
+}
+
+// 开启 async-stats 时使用 tokio 的锁，避免在异步任务中阻塞执行线程
+#[cfg(not(feature = "async-stats"))]
+type StatsMutex<T> = Mutex<T>;
+#[cfg(feature = "async-stats")]
+type StatsMutex<T> = tokio::sync::Mutex<T>;
+
+// clone 之后共享同一份统计数据
+#[derive(Clone)]
+pub struct RequestStats {
+    inner: Arc<StatsMutex<InnerStats>>,
+    // 采样率；和 `InnerStats.sample_rate` 一致，在获取锁之前判断是否采样
+    sample_rate: Option<f64>,
+    // 是否丢弃所有更新；clone 之后共享
+    noop: Arc<AtomicBool>,
+    // 已经完成的统计周期数；不随统计周期重置，clone 之后共享
+    cycle_count: Arc<AtomicU64>,
+}
+
+impl Default for RequestStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<InnerStatsVal> for RequestStats {
+    /// 使用已有的累计值创建统计实例，如汇总多个进程的统计数据后生成 `Stats`
+    fn from(base: InnerStatsVal) -> Self {
+        let mut inner = InnerStats::new();
+        inner.base = base;
+        Self {
+            inner: Arc::new(StatsMutex::new(inner)),
+            sample_rate: None,
+            noop: Default::default(),
+            cycle_count: Default::default(),
+        }
+    }
+}
+
+impl RequestStats {
+    /// 创建一个新的统计实例，并记录初始化时间和开始时间
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(StatsMutex::new(InnerStats::new())),
+            sample_rate: None,
+            noop: Default::default(),
+            cycle_count: Default::default(),
+        }
+    }
+
+    /// 使用指定的时钟创建统计实例；统计周期的开始和结束时间都从时钟获取
+    pub fn new_with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner: Arc::new(StatsMutex::new(InnerStats::with_clock(Some(clock)))),
+            sample_rate: None,
+            noop: Default::default(),
+            cycle_count: Default::default(),
+        }
+    }
+
+    /// 创建一个采样统计的实例；每次更新只按 `sample_rate`（0.0 - 1.0）的概率记录
+    /// 获取统计数据时请求数等计数按 `1 / sample_rate` 放大，比例类的数据不变
+    pub fn new_sampled(sample_rate: f64) -> Self {
+        let sample_rate = sample_rate.clamp(0.0, 1.0);
+        let mut inner = InnerStats::new();
+        inner.sample_rate = Some(sample_rate);
+        Self {
+            inner: Arc::new(StatsMutex::new(inner)),
+            sample_rate: Some(sample_rate),
+            noop: Default::default(),
+            cycle_count: Default::default(),
+        }
+    }
+
+    /// 创建一个丢弃所有更新的实例，用于不需要统计的测试；获取的统计数据始终为空
+    pub fn noop() -> Self {
+        let stats = Self::new();
+        stats.noop.store(true, Ordering::Relaxed);
+        stats
+    }
+
+    /// 是否是丢弃所有更新的实例
+    pub fn is_noop(&self) -> bool {
+        self.noop.load(Ordering::Relaxed)
+    }
+
+    /// 当前这次更新是否需要记录
+    fn should_sample(&self) -> bool {
+        if self.is_noop() {
+            return false;
+        }
+
+        match self.sample_rate {
+            Some(rate) => fast_random() < rate,
+            None => true,
+        }
+    }
+
+    /// 创建统计实例并开启快照持久化；快照文件存在时从中恢复统计数据
+    /// 更新统计数据时不写入快照，需要定时调用 `persist_snapshot`；重置统计周期时会同步写入
+    pub fn with_snapshot_path(path: impl Into<PathBuf>) -> Self {
+        let stats = Self::new();
+        stats.enable_snapshot(path.into());
+        stats
+    }
+
+    /// 开启快照持久化；快照文件存在时合并其中的统计数据
+    /// 更新统计数据时不写入快照，需要定时调用 `persist_snapshot`
+    pub fn enable_snapshot(&self, path: PathBuf) {
+        let mut inner = self.lock();
+        if path.exists() {
+            match read_snapshot(&path) {
+                Ok(snapshot) => {
+                    inner.start_time = inner.start_time.min(snapshot.start_time);
+                    let limit = inner.recent_errors_limit;
+                    inner.base.merge(&snapshot.stats, limit);
+                    info!("从快照 {:?} 恢复统计数据", path);
+                }
+                Err(err) => warn!("读取统计快照 {:?} 失败：{}", path, err),
+            }
+        }
+
+        inner.snapshot_path = Some(path);
+    }
+
+    /// 关闭快照；不删除已经写入的快照文件
+    pub fn disable_snapshot(&self) {
+        let mut inner = self.lock();
+        inner.snapshot_path = None;
+    }
+
+    /// 立即写入快照；未开启快照时不做任何操作
+    pub fn persist_snapshot(&self) {
+        self.lock().persist_snapshot();
+    }
+
+    /// 同步获取锁
+    #[cfg(not(feature = "async-stats"))]
+    fn lock(&self) -> impl DerefMut<Target = InnerStats> + '_ {
+        self.inner.lock()
+    }
+
+    /// 同步获取锁；没有竞争时直接获取
+    /// `blocking_lock` 在异步上下文中会 panic：多线程运行时中使用 `block_in_place` 让出工作线程，
+    /// 单线程运行时中不能让出，在新线程中等待锁
+    #[cfg(feature = "async-stats")]
+    fn lock(&self) -> impl DerefMut<Target = InnerStats> + '_ {
+        use tokio::runtime::{Handle, RuntimeFlavor};
+
+        if let Ok(guard) = self.inner.try_lock() {
+            return guard;
+        }
+        match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            Ok(RuntimeFlavor::MultiThread) => {
+                tokio::task::block_in_place(|| self.inner.blocking_lock())
+            }
+            Ok(_) => thread::scope(|s| {
+                s.spawn(|| self.inner.blocking_lock())
+                    .join()
+                    .expect("等待统计锁的线程异常退出")
+            }),
+            Err(_) => self.inner.blocking_lock(),
+        }
+    }
+
+    /// 更新统计信息的方法
+    pub fn update_stats(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult, // 使用枚举表示请求结果
+    ) {
+        self.update_stats_with(
+            request_time,
+            response_time,
+            status_code,
+            result,
+            &RequestInfo::default(),
+        )
+    }
+
+    /// 更新统计信息，同时统计请求的附加信息
+    pub fn update_stats_with(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult,
+        info: &RequestInfo<'_>,
+    ) {
+        if !self.should_sample() {
+            return;
+        }
+
+        let mut inner = self.lock();
+        inner.update_stats_with(request_time, response_time, status_code, &result, info);
+    }
+
+    /// 更新统计信息并累计每个阶段的耗时（微秒）；用于 `RequestSpan`
+    pub(crate) fn update_stats_with_stages(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult,
+        stages: &HashMap<String, i64>,
+    ) {
+        if !self.should_sample() {
+            return;
+        }
+
+        let mut inner = self.lock();
+        inner.update_stats(request_time, response_time, status_code, &result);
+        for (name, duration) in stages {
+            let stage = inner.stage_durations.entry(name.clone()).or_default();
+            stage.count += 1;
+            stage.total_us = stage.total_us.saturating_add(*duration);
+        }
+    }
+
+    /// 开始一个请求；调用 `RequestSpan::finish` 时更新统计数据
+    /// 没有调用 `finish` 就被释放时按连接失败统计
+    pub fn begin_request(&self, url: &str) -> RequestSpan<'_> {
+        RequestSpan::new(self, url)
+    }
+
+    /// 批量更新统计信息；只获取一次锁
+    /// 每项为 (请求时间, 响应时间, 状态码, 请求结果)，和 `update_stats` 的参数一致
+    pub fn update_stats_batch(&self, items: &[(i64, i64, u16, RequestResult)]) {
+        // 在获取锁之前判断是否采样
+        let sampled: Vec<_> = items.iter().filter(|_| self.should_sample()).collect();
+        if sampled.is_empty() {
+            return;
+        }
+
+        let mut inner = self.lock();
+        for (request_time, response_time, status_code, result) in sampled {
+            inner.update_stats(*request_time, *response_time, *status_code, result);
+        }
+    }
+
+    /// 批量更新统计信息，同时统计每项请求的附加信息；只获取一次锁
+    pub fn update_stats_items(&self, items: &[StatsItem<'_>]) {
+        // 在获取锁之前判断是否采样
+        let sampled: Vec<_> = items.iter().filter(|_| self.should_sample()).collect();
+        if sampled.is_empty() {
+            return;
+        }
+
+        let mut inner = self.lock();
+        for item in sampled {
+            inner.update_stats_with(
+                item.request_time,
+                item.response_time,
+                item.status_code,
+                &item.result,
+                &item.info,
+            );
+        }
+    }
+
+    /// 设置每种错误类型保留最近的错误上下文条数
+    pub fn set_recent_errors_limit(&self, limit: usize) {
+        self.lock().recent_errors_limit = limit;
+    }
+
+    /// 设置分组统计（如代理）的标签数量上限
+    pub fn set_max_labels(&self, max_labels: usize) {
+        self.lock().max_labels = max_labels;
+    }
+
+    /// 添加统计中间件；每次更新统计数据时按添加顺序调用
+    pub fn add_middleware(
+        &self,
+        middleware: impl middleware::StatsMiddleware + Send + Sync + 'static,
+    ) {
+        self.lock().middlewares.push(Box::new(middleware));
+    }
+
+    /// 设置输出的 base 中的请求频率是否使用本周期的每秒请求数（取整数部分）
+    pub fn set_auto_request_frequency(&self, enabled: bool) {
+        self.lock().auto_request_frequency = enabled;
+    }
+
+    /// 设置自定义的请求结果（`RequestResult::Custom`）是否计为错误；默认计为错误
+    pub fn set_custom_results_are_errors(&self, enabled: bool) {
+        self.lock().custom_results_are_errors = enabled;
+    }
+
+    /// 设置比例和平均值保留的小数位数；最多 15 位
+    pub fn set_precision(&self, precision: u8) {
+        self.lock().precision = precision.min(MAX_STATS_PRECISION);
+    }
+
+    /// 设置延迟直方图的桶边界（毫秒）；None 表示不统计
+    /// 边界会排序去重，忽略 NaN 和无穷大；修改边界时清空当前周期的直方图
+    pub fn set_latency_buckets(&self, buckets: Option<Vec<f64>>) {
+        let buckets = buckets.map(|mut buckets| {
+            buckets.retain(|b| b.is_finite());
+            buckets.sort_by(f64::total_cmp);
+            buckets.dedup();
+            buckets
+        });
+
+        let mut inner = self.lock();
+        if inner.latency_buckets != buckets {
+            inner.latency_histogram.clear();
+            inner.latency_buckets = buckets;
+        }
+    }
+
+    /// 将当前统计数据拼装到 `Stats` 结构体中，并清空当前统计数据
+    /// 统计的时候需要传入 hosts 测试信息
+    pub fn to_stats_and_reset<'a>(
+        &self,
+        base: &'a StatsBase,
+
+        // 用于测试 hosts 的延迟
+        host_info: Option<HostPingInfo>,
+    ) -> Stats<'a> {
+        let host_ping = host_info.map(ping_hosts).unwrap_or_default();
+        self.to_stats_and_reset_with_ping(base, host_ping)
+    }
+
+    /// 使用已经测试好的 hosts 延迟拼装 `Stats`，并清空当前统计数据
+    fn to_stats_and_reset_with_ping<'a>(
+        &self,
+        base: &'a StatsBase,
+        host_ping: HashMap<String, HostPingStats>,
+    ) -> Stats<'a> {
+        let mut data = self.lock();
+        let mut d = data.to_stats_and_reset(base);
+        data.reset();
+        // 已经上报的数据不再保留在快照中
+        data.persist_snapshot();
+
+        d.hosts_ping_delay = host_ping;
+        d.cycle_count = self.cycle_count.fetch_add(1, Ordering::Relaxed) + 1;
+
+        d
+    }
+
+    /// 当前周期有请求时才拼装 `Stats` 并清空当前统计数据；没有请求时返回 None，统计周期继续累计
+    pub fn to_stats_and_reset_if_nonempty<'a>(
+        &self,
+        base: &'a StatsBase,
+
+        // 用于测试 hosts 的延迟
+        host_info: Option<HostPingInfo>,
+    ) -> Option<Stats<'a>> {
+        if self.lock().total_requests == 0 {
+            return None;
+        }
+
+        Some(self.to_stats_and_reset(base, host_info))
+    }
+
+    /// 获取当前统计周期的累计值，不清空统计数据
+    pub fn values(&self) -> InnerStatsVal {
+        self.lock().base.clone()
+    }
+
+    /// 获取统计周期中途的调试视图；只短暂持有锁复制数据，不会重置统计周期
+    pub fn debug_snapshot(&self) -> InnerStatsDebug {
+        let inner = self.lock();
+        InnerStatsDebug {
+            total_requests: inner.total_requests,
+            total_latency_us: inner.total_latency,
+            start_time: inner.start_time,
+            elapsed_ms: inner.now_millis() - inner.start_time,
+            http_status_codes: inner.http_status_codes.clone(),
+        }
+    }
+
+    /// 在锁内只读访问当前统计周期的累计值，避免复制；`f` 中不要更新同一个实例
+    pub fn borrow_inner<R>(&self, f: impl FnOnce(&InnerStatsVal) -> R) -> R {
+        f(&self.lock().base)
+    }
+
+    /// 当前的连续失败次数；请求成功时清零
+    pub fn current_consecutive_failures(&self) -> i64 {
+        self.lock().current_consecutive_failures
+    }
+
+    /// 获取当前统计数据，不清空统计数据，也不测试 hosts
+    /// `cycle_count` 为当前正在统计的周期
+    pub fn snapshot<'a>(&self, base: &'a StatsBase) -> Stats<'a> {
+        let mut d = self.lock().to_stats(base);
+        d.cycle_count = self.cycle_count.load(Ordering::Relaxed) + 1;
+        d
+    }
+
+    /// 异步更新统计信息
+    #[cfg(feature = "async-stats")]
+    pub async fn update_stats_async(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult,
+    ) {
+        self.update_stats_with_async(
+            request_time,
+            response_time,
+            status_code,
+            result,
+            &RequestInfo::default(),
+        )
+        .await
+    }
+
+    /// 异步更新统计信息，同时统计请求的附加信息
+    #[cfg(feature = "async-stats")]
+    pub async fn update_stats_with_async(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult,
+        info: &RequestInfo<'_>,
+    ) {
+        if !self.should_sample() {
+            return;
+        }
+
+        let mut inner = self.inner.lock().await;
+        inner.update_stats_with(request_time, response_time, status_code, &result, info);
+    }
+
+    /// 异步版本的 `to_stats_and_reset`；hosts 延迟使用异步连接测试，快照在阻塞线程池中写入
+    #[cfg(feature = "async-stats")]
+    pub async fn to_stats_and_reset_async<'a>(
+        &self,
+        base: &'a StatsBase,
+        host_info: Option<HostPingInfo>,
+    ) -> Stats<'a> {
+        let host_ping = match host_info {
+            Some(info) => ping_hosts_async(info).await,
+            None => HashMap::new(),
+        };
+
+        let mut d = {
+            let mut data = self.inner.lock().await;
+            let d = data.to_stats_and_reset(base);
+            data.reset();
+            d
+        };
+        // 已经上报的数据不再保留在快照中；写文件是阻塞操作，释放锁之后执行
+        let stats = self.clone();
+        if let Err(err) = tokio::task::spawn_blocking(move || stats.persist_snapshot()).await {
+            warn!("写入统计快照失败：{:?}", err);
+        }
+
+        d.hosts_ping_delay = host_ping;
+        d.cycle_count = self.cycle_count.fetch_add(1, Ordering::Relaxed) + 1;
+
+        d
+    }
+}
+
+/// 测试所有 host 的延迟
+fn ping_hosts(info: HostPingInfo) -> HashMap<String, HostPingStats> {
+    let mut host_ping = HashMap::new();
+    for host in info.hosts {
+        let stats = ping_host(
+            &host,
+            info.port,
+            info.timeout,
+            info.samples,
+            info.method,
+            info.protocol,
+            info.precision,
+        );
+        host_ping.insert(host, stats);
+    }
+    host_ping
+}
+
+/// 测试 hosts 延迟；跳过连续失败被剔除的 hosts，并记录本次的测试结果
+async fn ping_hosts_with_eviction(
+    mut info: HostPingInfo,
+    eviction: &Option<Arc<Mutex<HostEviction>>>,
+) -> HashMap<String, HostPingStats> {
+    let Some(eviction) = eviction else {
+        return ping_hosts_async(info).await;
+    };
+
+    let samples = info.samples.clamp(1, MAX_HOST_PING_SAMPLES);
+    info.hosts = eviction.lock().filter(info.hosts, Instant::now());
+    let host_ping = ping_hosts_async(info).await;
+    eviction.lock().record(&host_ping, samples, Instant::now());
+    host_ping
+}
+
+// 连续测试失败的 hosts；失败次数超过阈值后剔除，不再测试，`retry_after` 之后重新测试
+struct HostEviction {
+    // 连续失败次数阈值
+    threshold: u32,
+    // 剔除之后重新测试的间隔；None 表示不再测试
+    retry_after: Option<Duration>,
+    // 每个 host 的连续失败次数
+    failures: HashMap<String, u32>,
+    // 剔除的 hosts 和剔除时间
+    evicted: HashMap<String, Instant>,
+}
+
+impl HostEviction {
+    fn new(threshold: u32, retry_after: Option<Duration>) -> Self {
+        Self {
+            threshold,
+            retry_after,
+            failures: HashMap::new(),
+            evicted: HashMap::new(),
+        }
+    }
+
+    /// 过滤掉剔除的 hosts；到了重新测试时间的 hosts 保留，再次失败时立即剔除
+    fn filter(&mut self, hosts: Vec<String>, now: Instant) -> Vec<String> {
+        let retry_after = self.retry_after;
+        self.evicted.retain(|_, evicted_at| {
+            retry_after.is_none_or(|retry| now.duration_since(*evicted_at) < retry)
+        });
+        hosts
+            .into_iter()
+            .filter(|host| !self.evicted.contains_key(host))
+            .collect()
+    }
+
+    /// 记录测试结果；所有测试都失败时计为一次失败，成功时清零
+    fn record(&mut self, host_ping: &HashMap<String, HostPingStats>, samples: u8, now: Instant) {
+        for (host, stats) in host_ping {
+            if stats.lost < samples {
+                self.failures.remove(host);
+                continue;
+            }
+
+            let failures = self.failures.entry(host.clone()).or_insert(0);
+            *failures += 1;
+            if *failures >= self.threshold {
+                warn!("host {} 连续 {} 次测试失败，暂停测试", host, failures);
+                self.evicted.insert(host.clone(), now);
+            }
+        }
+    }
+}
+
+/// 异步测试所有 host 的延迟；所有 host 并发测试
+async fn ping_hosts_async(info: HostPingInfo) -> HashMap<String, HostPingStats> {
+    let tasks = info.hosts.into_iter().map(|host| async move {
+        let stats = ping_host_async(
+            &host,
+            info.port,
+            info.timeout,
+            info.samples,
+            info.method,
+            info.protocol,
+            info.precision,
+        )
+        .await;
+        (host, stats)
+    });
+    futures_util::future::join_all(tasks)
+        .await
+        .into_iter()
+        .collect()
+}
+
+struct InnerStats {
+    // 对象初始化时间（毫秒级时间戳）
+    pub init_time: i64,
+    // 当前统计周期的开始时间（毫秒级时间戳）
+    pub start_time: i64,
+    // 每种错误类型保留最近的错误上下文条数
+    pub recent_errors_limit: usize,
+    // 分组统计（如代理）的标签数量上限
+    pub max_labels: usize,
+    // 采样率；None 表示不采样，记录所有请求
+    pub sample_rate: Option<f64>,
+    // 比例和平均值保留的小数位数
+    pub precision: u8,
+    // 时钟；None 表示使用系统时间
+    pub clock: Option<Arc<dyn Clock>>,
+    // 统计中间件；每次更新统计数据时按顺序调用
+    pub middlewares: Vec<Box<dyn middleware::StatsMiddleware + Send + Sync>>,
+    // 快照文件路径；设置后统计数据会持久化到该文件
+    pub snapshot_path: Option<PathBuf>,
+    // 当前的连续失败次数；请求成功时清零，不随统计周期重置
+    pub current_consecutive_failures: i64,
+    // 延迟直方图的桶边界（毫秒），从小到大排列；None 表示不统计
+    pub latency_buckets: Option<Vec<f64>>,
+    // 输出的 base 中的请求频率使用本周期的每秒请求数（取整数部分）
+    pub auto_request_frequency: bool,
+    // 自定义的请求结果是否计为错误
+    pub custom_results_are_errors: bool,
+
+    pub base: InnerStatsVal,
+}
+
+impl Deref for InnerStats {
+    type Target = InnerStatsVal;
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for InnerStats {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+// 持久化到磁盘的统计快照
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StatsSnapshot<T> {
+    // 当前统计周期的开始时间（毫秒级时间戳）
+    start_time: i64,
+    stats: T,
+}
+
+/// 读取统计快照
+fn read_snapshot(path: &Path) -> Result<StatsSnapshot<InnerStatsVal>> {
+    let data = std::fs::read(path)?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// 写入统计快照；先写入 `.tmp` 文件再重命名，避免写入中途崩溃导致快照损坏
+fn write_snapshot(path: &Path, snapshot: &StatsSnapshot<&InnerStatsVal>) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    std::fs::write(&tmp_path, serde_json::to_vec(snapshot)?)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+impl InnerStats {
+    /// 创建一个新的统计实例，并记录初始化时间和开始时间
+    fn new() -> Self {
+        Self::with_clock(None)
+    }
+
+    /// 使用指定的时钟创建统计实例；`clock` 为 None 时使用系统时间
+    fn with_clock(clock: Option<Arc<dyn Clock>>) -> Self {
+        let current_time = clock.as_ref().map_or_else(get_now_millis, |clock| clock());
+        Self {
+            init_time: current_time,
+            start_time: current_time,
+            recent_errors_limit: entity::DEFAULT_RECENT_ERRORS_LIMIT,
+            max_labels: 100,
+            sample_rate: None,
+            precision: DEFAULT_STATS_PRECISION,
+            clock,
+            middlewares: Vec::new(),
+            snapshot_path: None,
+            current_consecutive_failures: 0,
+            latency_buckets: None,
+            auto_request_frequency: false,
+            custom_results_are_errors: true,
+            base: Default::default(),
+        }
+    }
+
+    /// 更新统计信息的方法
+    pub fn update_stats(
+        &mut self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: &RequestResult, // 使用枚举表示请求结果
+    ) {
+        self.update_stats_with(
+            request_time,
+            response_time,
+            status_code,
+            result,
+            &RequestInfo::default(),
+        )
+    }
+
+    /// 更新统计信息，同时统计请求的附加信息
+    pub fn update_stats_with(
+        &mut self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: &RequestResult,
+        info: &RequestInfo<'_>,
+    ) {
+        if self.middlewares.is_empty() {
+            return self.update_stats_inner(request_time, response_time, status_code, result, info);
+        }
+
+        // 调用中间件时不能同时借用 self
+        let middlewares = std::mem::take(&mut self.middlewares);
+        let mut result = result.clone();
+        for middleware in &middlewares {
+            middleware.before_update(request_time, response_time, status_code, &result);
+        }
+        for middleware in &middlewares {
+            result = middleware.transform(result);
+        }
+
+        self.update_stats_inner(request_time, response_time, status_code, &result, info);
+
+        for middleware in &middlewares {
+            middleware.after_update(&self.base);
+        }
+        self.middlewares = middlewares;
+    }
+
+    fn update_stats_inner(
+        &mut self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: &RequestResult,
+        info: &RequestInfo<'_>,
+    ) {
+        let RequestInfo {
+            retry_count,
+            request_bytes,
+            response_bytes,
+            proxy_id,
+            dns_duration_us,
+            queue_delay_us,
+            connection_reused,
+        } = *info;
+
+        // 增加总请求数
+        self.total_requests += 1;
+        let is_error = match result {
+            RequestResult::Custom(_) => self.custom_results_are_errors,
+            _ => result.error_kind().is_some(),
+        };
+
+        // 流量统计
+        self.total_request_bytes = self.total_request_bytes.saturating_add(request_bytes);
+        self.total_response_bytes = self.total_response_bytes.saturating_add(response_bytes);
+
+        // 重试统计；和第一次请求的数据分开统计
+        if retry_count > 0 {
+            self.total_retries += retry_count as i64;
+            self.retried_requests += 1;
+            if !is_error {
+                self.retry_success += 1;
+            }
+        }
+
+        // 计算请求延迟；响应时间早于请求时间（如时钟回拨）时记为 0，避免出现负的平均延迟
+        let latency = response_time.saturating_sub(request_time).max(0);
+        self.total_latency = self.total_latency.saturating_add(latency);
+
+        // 延迟直方图；延迟等于边界时计入该边界的桶
+        if let Some(buckets) = &self.latency_buckets {
+            let latency_ms = latency as f64 / 1000.0;
+            let index = buckets.partition_point(|b| *b < latency_ms);
+            let histogram = &mut self.base.latency_histogram;
+            histogram.resize(buckets.len() + 1, 0);
+            histogram[index] += 1;
+        }
+
+        // DNS 解析耗时；和总请求延迟分开统计
+        if let Some(dns_duration_us) = dns_duration_us {
+            let dns_duration_us = i64::try_from(dns_duration_us).unwrap_or(i64::MAX);
+            self.total_dns_latency_us = self.total_dns_latency_us.saturating_add(dns_duration_us);
+            self.dns_resolved_count += 1;
+        }
+
+        // 限速队列等待耗时；请求发出之前的等待，不计入请求延迟
+        if let Some(queue_delay_us) = queue_delay_us {
+            self.total_queue_delay_us = self.total_queue_delay_us.saturating_add(queue_delay_us);
+            self.queued_count += 1;
+        }
+
+        // 连接复用统计
+        if connection_reused {
+            self.connection_reused_count += 1;
+        }
+
+        // 代理统计；超过标签数量上限的代理统一计入 `other`
+        let proxy_id = proxy_id.unwrap_or("direct");
+        let key = if self.proxy_stats.contains_key(proxy_id)
+            || self.proxy_stats.len() < self.max_labels
+        {
+            proxy_id
+        } else {
+            "other"
+        };
+        let proxy = self.base.proxy_stats.entry(key.to_string()).or_default();
+        proxy.requests += 1;
+        proxy.total_latency_us = proxy.total_latency_us.saturating_add(latency);
+        if is_error {
+            proxy.errors += 1;
+        }
+
+        // 更新 HTTP 状态码统计
+        // 很多爬虫都是使用0 代替；这里直接忽略0 的情况
+        if status_code != 0 {
+            *self.http_status_codes.entry(status_code).or_insert(0) += 1;
+        }
+
+        // 采样保留最近的错误上下文
+        if let (Some(kind), Some(ctx)) = (result.error_kind(), result.context()) {
+            if self.recent_errors_limit > 0 {
+                let limit = self.recent_errors_limit;
+                let errors = self.recent_errors.entry(kind).or_default();
+                if errors.len() >= limit {
+                    errors.pop_front();
+                }
+                errors.push_back(ctx.into());
+            }
+        }
+
+        // 根据请求结果更新对应的统计数据
+        match result {
+            RequestResult::Successful => {
+                self.successful_requests += 1;
+            }
+            RequestResult::SuccessfulAndCache => {
+                self.successful_requests += 1;
+                self.cache_hit += 1;
+            }
+
+            RequestResult::ParseError(_) => {
+                self.parse_errors += 1;
+            }
+            RequestResult::TimeoutError(_) => {
+                self.timeout_errors += 1;
+            }
+            RequestResult::ConnectionError(_) => {
+                self.connection_errors += 1;
+            }
+            RequestResult::StatusCodeError(_) => {
+                self.status_code_error += 1;
+            }
+            RequestResult::ProxyError(_) => {
+                self.proxy_errors += 1;
+            }
+            RequestResult::RateLimited(_) => {
+                self.rate_limited += 1;
+            }
+            RequestResult::CaptchaDetected(_) => {
+                self.captcha_detected += 1;
+            }
+            RequestResult::Custom(discriminant) => {
+                *self.custom_results.entry(*discriminant).or_insert(0) += 1;
+            }
+        }
+
+        // 连续失败统计
+        if is_error {
+            self.current_consecutive_failures += 1;
+            self.base.max_consecutive_failures = self
+                .max_consecutive_failures
+                .max(self.current_consecutive_failures);
+        } else {
+            self.current_consecutive_failures = 0;
+        }
+    }
+
+    /// 将当前统计数据拼装到 `Stats` 结构体中，并开始新的统计周期
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_stats_and_reset<'a>(&mut self, base: &'a StatsBase) -> Stats<'a> {
+        let stats = self.to_stats(base);
+        self.start_time = stats.time_period.end;
+        stats
+    }
+
+    /// 将当前统计数据拼装到 `Stats` 结构体中，不修改统计数据
+    pub fn to_stats<'a>(&self, base: &'a StatsBase) -> Stats<'a> {
+        // 获取当前时间作为结束时间
+        let end_time = self.now_millis();
+
+        // 构造时间周期
+        let time_period = TimePeriod {
+            start: self.start_time,
+            end: end_time,
+        };
+
+        // 比例和平均值保留的小数位数
+        let round = |value: f64| round_to(value, self.precision);
+
+        // 构造异常类型统计
+        let exception_types = ExceptionTypes {
+            connection_error: self.connection_errors,
+            timeout_error: self.timeout_errors,
+            parse_error: self.parse_errors,
+            status_code_error: self.status_code_error,
+            proxy_error: self.proxy_errors,
+            rate_limited: self.rate_limited,
+            captcha_detected: self.captcha_detected,
+        };
+        // 自定义的请求结果按配置计为错误
+        let custom_errors = match self.custom_results_are_errors {
+            true => self.custom_results.values().sum(),
+            false => 0,
+        };
+        let failed_requests = exception_types.total() + custom_errors;
+
+        // 计算错误率
+        let error_rate = if self.total_requests > 0 {
+            (self.parse_errors
+                + self.timeout_errors
+                + self.connection_errors
+                + self.status_code_error
+                + self.proxy_errors
+                + self.rate_limited
+                + self.captcha_detected
+                + custom_errors) as f64
+                / self.total_requests as f64
+        } else {
+            0.0
+        };
+
+        // 计算每种错误类型的错误率
+        let error_rate_by_type = [
+            ("connectionError", self.connection_errors),
+            ("timeoutError", self.timeout_errors),
+            ("parseError", self.parse_errors),
+            ("statusCodeError", self.status_code_error),
+            ("proxyError", self.proxy_errors),
+            ("rateLimited", self.rate_limited),
+            ("captchaDetected", self.captcha_detected),
+        ]
+        .into_iter()
+        .map(|(k, v)| {
+            let rate = if self.total_requests > 0 {
+                v as f64 / self.total_requests as f64
+            } else {
+                0.0
+            };
+            (k.to_string(), round(rate))
+        })
+        .collect();
 
         // 计算运行时长（从对象初始化到当前时间）
         let runtime_duration = (end_time - self.init_time) / 1000;
 
-        let cache_hit_rate = if self.successful_requests == 0 {
-            0.0
-        } else {
-            let cache_hit_rate = self.cache_hit as f64 / self.successful_requests as f64;
-            (cache_hit_rate * 1000.0).round() / 1000.0
+        let cache_hit_rate = if self.successful_requests == 0 {
+            0.0
+        } else {
+            round(self.cache_hit as f64 / self.successful_requests as f64)
+        };
+
+        let connection_reuse_rate = if self.total_requests == 0 {
+            0.0
+        } else {
+            round(self.connection_reused_count as f64 / self.total_requests as f64)
+        };
+
+        // ms
+        let average_latency = if self.total_requests == 0 {
+            0.0
+        } else {
+            (self.total_latency as f64 / self.total_requests as f64) / 1000.0
+        };
+
+        // 计算每秒请求数
+        let period_secs = (time_period.end - time_period.start) as f64 / 1000.0;
+        let per_second = |count: i64| {
+            if period_secs > 0.0 {
+                round(count as f64 / period_secs)
+            } else {
+                0.0
+            }
+        };
+
+        // 重试统计
+        let ratio = |count: i64, total: i64| {
+            if total > 0 {
+                round(count as f64 / total as f64)
+            } else {
+                0.0
+            }
+        };
+        let retry_stats = RetryStats {
+            total_retries: self.total_retries,
+            retry_success_rate: ratio(self.retry_success, self.retried_requests),
+            retry_rate: ratio(self.retried_requests, self.total_requests),
+        };
+
+        // 代理统计
+        let proxy_breakdown = self
+            .proxy_stats
+            .iter()
+            .map(|(id, proxy)| {
+                let breakdown = ProxyBreakdown {
+                    requests: proxy.requests,
+                    error_rate: ratio(proxy.errors, proxy.requests),
+                    avg_latency_ms: ratio(proxy.total_latency_us, proxy.requests * 1000),
+                };
+                (id.clone(), breakdown)
+            })
+            .collect();
+
+        // 各阶段的平均耗时
+        let stage_durations = self
+            .stage_durations
+            .iter()
+            .map(|(name, stage)| {
+                let avg = ratio(stage.total_us, stage.count * 1000);
+                (name.clone(), StageDuration { avg })
+            })
+            .collect();
+
+        // HTTP 状态码分组统计
+        let mut http_status_groups = HashMap::new();
+        for (code, count) in &self.http_status_codes {
+            *http_status_groups
+                .entry(status_code_group(*code).to_string())
+                .or_insert(0) += count;
+        }
+
+        // 构造 `Stats` 结构体
+        let stats = Stats {
+            schema_version: STATS_SCHEMA_VERSION,
+            base: Cow::Borrowed(base),
+            time_period,
+            cycle_count: 0,
+            restarted_at: self.init_time,
+            sequence_number: 0,
+            stale: false,
+            error_rate: round(error_rate),
+            error_rate_by_type,
+            exception_types,
+            runtime_duration,
+            total_requests: self.total_requests,
+            successful_requests: self.successful_requests,
+            failed_requests,
+            requests_per_second: per_second(self.total_requests),
+            successful_requests_per_second: per_second(self.successful_requests),
+            cache_hit_rate, // 假设没有缓存相关数据，可以根据需要补充
+            connection_reuse_rate,
+            cache_hit: self.cache_hit, // 假设没有缓存相关数据，可以根据需要补充
+            http_status_codes: self
+                .http_status_codes
+                .iter()
+                .map(|(k, v)| (k.to_string(), *v))
+                .collect(),
+            http_status_groups,
+            latency_histogram: self.latency_histogram_stats(),
+            custom_results: self
+                .custom_results
+                .iter()
+                .map(|(k, v)| (k.to_string(), *v))
+                .collect(),
+            retry_stats,
+            proxy_breakdown,
+            stage_durations,
+            avg_dns_latency_ms: ratio(self.total_dns_latency_us, self.dns_resolved_count * 1000),
+            avg_queue_delay_ms: ratio(
+                self.total_queue_delay_us.min(i64::MAX as u64) as i64,
+                self.queued_count * 1000,
+            ),
+            total_request_bytes: self.total_request_bytes,
+            total_response_bytes: self.total_response_bytes,
+            avg_response_bytes: if self.total_requests > 0 {
+                (self.total_response_bytes as f64 / self.total_requests as f64).round()
+            } else {
+                0.0
+            },
+            average_request_latency: round(average_latency),
+            max_consecutive_failures: self.max_consecutive_failures,
+            hosts_ping_delay: HashMap::new(), // 假设没有主机延迟数据，可以根据需要补充
+            system_resources: get_system_resources(),
+            recent_errors: self
+                .recent_errors
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.iter().map(|e| e.to_string()).collect()))
+                .collect(),
+            diff: None,
+            clean_stats: None,
+        };
+        // 浮点数都应该是有限值；发布版本中非有限值在序列化时按 0.0 输出
+        debug_assert!(
+            stats.non_finite_fields().is_empty(),
+            "统计信息中存在非有限值：{:?}",
+            stats.non_finite_fields()
+        );
+
+        let mut stats = match self.sample_rate {
+            Some(rate) if rate > 0.0 => scale_counts(stats, 1.0 / rate, self.precision),
+            _ => stats,
+        };
+        if self.auto_request_frequency {
+            stats.base.to_mut().request_frequency = stats.requests_per_second as i64;
+        }
+        stats
+    }
+
+    pub fn reset(&mut self) {
+        self.base = Default::default();
+    }
+
+    /// 累计的延迟直方图；没有设置桶边界时为空
+    fn latency_histogram_stats(&self) -> HashMap<String, i64> {
+        let Some(buckets) = &self.latency_buckets else {
+            return HashMap::new();
+        };
+
+        let labels = buckets
+            .iter()
+            .map(|b| b.to_string())
+            .chain(std::iter::once("+Inf".to_string()));
+        let mut total = 0;
+        labels
+            .enumerate()
+            .map(|(i, label)| {
+                total += self.latency_histogram.get(i).copied().unwrap_or(0);
+                (label, total)
+            })
+            .collect()
+    }
+
+    /// 当前时间（毫秒级时间戳）
+    fn now_millis(&self) -> i64 {
+        self.clock
+            .as_ref()
+            .map_or_else(get_now_millis, |clock| clock())
+    }
+
+    /// 写入快照；未开启快照时不做任何操作
+    fn persist_snapshot(&self) {
+        if let Some(path) = &self.snapshot_path {
+            let snapshot = StatsSnapshot {
+                start_time: self.start_time,
+                stats: &self.base,
+            };
+            if let Err(err) = write_snapshot(path, &snapshot) {
+                warn!("写入统计快照 {:?} 失败：{}", path, err);
+            }
+        }
+    }
+}
+
+/// 采样统计时放大计数类的数据；比例和平均值不变
+fn scale_counts(mut stats: Stats<'_>, factor: f64, precision: u8) -> Stats<'_> {
+    let scale = |n: &mut i64| *n = (*n as f64 * factor).round() as i64;
+    let scale_u64 = |n: &mut u64| *n = (*n as f64 * factor).round() as u64;
+    let scale_f64 = |n: &mut f64| *n = round_to(*n * factor, precision);
+
+    scale(&mut stats.total_requests);
+    scale(&mut stats.successful_requests);
+    scale(&mut stats.failed_requests);
+    scale(&mut stats.cache_hit);
+    scale_f64(&mut stats.requests_per_second);
+    scale_f64(&mut stats.successful_requests_per_second);
+
+    let e = &mut stats.exception_types;
+    for n in [
+        &mut e.connection_error,
+        &mut e.timeout_error,
+        &mut e.parse_error,
+        &mut e.status_code_error,
+        &mut e.proxy_error,
+        &mut e.rate_limited,
+        &mut e.captcha_detected,
+    ] {
+        scale(n);
+    }
+
+    stats.http_status_codes.values_mut().for_each(scale);
+    stats.http_status_groups.values_mut().for_each(scale);
+    stats.latency_histogram.values_mut().for_each(scale);
+    stats.custom_results.values_mut().for_each(scale);
+    stats
+        .proxy_breakdown
+        .values_mut()
+        .for_each(|p| scale(&mut p.requests));
+    scale(&mut stats.retry_stats.total_retries);
+    scale_u64(&mut stats.total_request_bytes);
+    scale_u64(&mut stats.total_response_bytes);
+
+    stats
+}
+
+/// 保留 `precision` 位小数
+fn round_to(value: f64, precision: u8) -> f64 {
+    let factor = 10f64.powi(precision as i32);
+    (value * factor).round() / factor
+}
+
+/// HTTP 状态码所属的分组
+fn status_code_group(code: u16) -> &'static str {
+    match code {
+        100..=199 => "1xx",
+        200..=299 => "2xx",
+        300..=399 => "3xx",
+        400..=499 => "4xx",
+        500..=599 => "5xx",
+        _ => "other",
+    }
+}
+
+// 比例和平均值默认保留的小数位数
+const DEFAULT_STATS_PRECISION: u8 = 3;
+
+// 比例和平均值最多保留的小数位数；超过 f64 的有效位数没有意义
+const MAX_STATS_PRECISION: u8 = 15;
+
+/// 汇总多个统计实例的数据，不会清空各实例的统计数据
+/// 统计周期从最早的实例开始计算
+pub fn aggregate<'a>(instances: &[&RequestStats], base: &'a StatsBase) -> Stats<'a> {
+    let mut merged = InnerStats::new();
+
+    for stats in instances {
+        let inner = stats.lock();
+        merged.init_time = merged.init_time.min(inner.init_time);
+        merged.start_time = merged.start_time.min(inner.start_time);
+        merged.recent_errors_limit = merged.recent_errors_limit.max(inner.recent_errors_limit);
+        merged.max_labels = merged.max_labels.max(inner.max_labels);
+        merged.precision = merged.precision.max(inner.precision);
+        if merged.latency_buckets.is_none() {
+            merged.latency_buckets = inner.latency_buckets.clone();
+        }
+        let limit = merged.recent_errors_limit;
+        merged.base.merge(&inner.base, limit);
+    }
+
+    merged.to_stats(base)
+}
+
+/// 获取系统资源数据
+/// 是否包含每个 CPU 核心的使用率由 `RequestStatsConfig.include_per_core_cpu` 决定
+/// `RequestStatsConfig.collect_system_resources` 关闭时直接返回默认值
+pub fn get_system_resources() -> SystemResources {
+    if !COLLECT_SYSTEM_RESOURCES.load(Ordering::Relaxed) {
+        return SystemResources::default();
+    }
+
+    get_system_resources_with(INCLUDE_PER_CORE_CPU.load(Ordering::Relaxed))
+}
+
+/// 获取系统资源数据；`include_per_core_cpu` 为 true 时包含每个 CPU 核心的使用率
+/// 读取 `SYSTEM_POLLER` 最近一次刷新的结果，不会触发刷新
+pub fn get_system_resources_with(include_per_core_cpu: bool) -> SystemResources {
+    SYSTEM_POLLER.current_with(include_per_core_cpu)
+}
+
+/// 获取当前进程的线程数；不支持的平台返回 0
+fn get_thread_count() -> u32 {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| {
+                status
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Threads:"))
+                    .and_then(|v| v.trim().parse().ok())
+            })
+            .unwrap_or(0)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        0
+    }
+}
+
+/// 获取当前进程打开的文件描述符数；不支持的平台返回 0
+fn get_open_fd_count() -> u32 {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_dir("/proc/self/fd")
+            .map(|dir| dir.count() as u32)
+            .unwrap_or(0)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        0
+    }
+}
+
+/// 解析待测试的主机地址
+/// 支持 `1.1.1.1`、`::1`、`1.1.1.1:443`、`[::1]:443` 四种写法；
+/// 地址中自带的端口会被忽略，统一使用 `port` 参数
+fn parse_host_addr(addr: &str, port: u16) -> Result<SocketAddr> {
+    let addr = addr.trim();
+
+    if let Ok(ip) = IpAddr::from_str(addr) {
+        return Ok(SocketAddr::new(ip, port));
+    }
+
+    // [host]:port 写法
+    if let Some(rest) = addr.strip_prefix('[') {
+        let inner = rest
+            .split_once(']')
+            .map(|(inner, _)| inner)
+            .ok_or_else(|| anyhow!("无效的主机地址：{addr}"))?;
+        let ip = IpAddr::from_str(inner)?;
+        return Ok(SocketAddr::new(ip, port));
+    }
+
+    // host:port 写法
+    let sock = addr
+        .parse::<SocketAddr>()
+        .map_err(|err| anyhow!("无效的主机地址：{addr}；{err}"))?;
+    Ok(SocketAddr::new(sock.ip(), port))
+}
+
+/// 单个 host 最多测试的次数
+const MAX_HOST_PING_SAMPLES: u8 = 10;
+
+/// 对 host 进行多次 tcp 测试并汇总延迟（毫秒）
+/// 所有测试的总耗时不会超过 `timeout * samples`
+fn ping_host(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    samples: u8,
+    method: PingMethod,
+    protocol: PingProtocol,
+    precision: u8,
+) -> HostPingStats {
+    let mut sampler = PingSampler::new(timeout, samples, precision);
+    while let Some(ping_timeout) = sampler.next_timeout() {
+        sampler.record(match method {
+            PingMethod::Tcp => run_test_port(host, port, ping_timeout, protocol),
+            PingMethod::Icmp => run_test_icmp(host, ping_timeout),
+        });
+    }
+    sampler.finish()
+}
+
+/// `ping_host` 的异步版本；tcp 测试使用异步连接，icmp 测试在阻塞线程池中执行
+async fn ping_host_async(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+    samples: u8,
+    method: PingMethod,
+    protocol: PingProtocol,
+    precision: u8,
+) -> HostPingStats {
+    let mut sampler = PingSampler::new(timeout, samples, precision);
+    while let Some(ping_timeout) = sampler.next_timeout() {
+        sampler.record(match method {
+            PingMethod::Tcp => run_test_port_async(host, port, ping_timeout, protocol).await,
+            PingMethod::Icmp => {
+                let host = host.to_string();
+                tokio::task::spawn_blocking(move || run_test_icmp(&host, ping_timeout))
+                    .await
+                    .unwrap_or_else(|err| Err(anyhow!("icmp 测试任务异常：{err}")))
+            }
+        });
+    }
+    sampler.finish()
+}
+
+// 对一个 host 多次测试时的采样状态；`ping_host` 和 `ping_host_async` 共用
+struct PingSampler {
+    // 单次测试的超时时间
+    timeout: Duration,
+    // 剩余的测试次数
+    remaining_samples: u8,
+    // 所有测试的总超时时间
+    total_timeout: Duration,
+    start_time: Instant,
+    // 成功的测试延迟（毫秒）
+    delays: Vec<f64>,
+    lost: u8,
+    // 平均延迟保留的小数位数
+    precision: u8,
+}
+
+impl PingSampler {
+    fn new(timeout: Duration, samples: u8, precision: u8) -> Self {
+        let samples = samples.clamp(1, MAX_HOST_PING_SAMPLES);
+        Self {
+            timeout,
+            remaining_samples: samples,
+            // 超时时间很大时乘法会溢出
+            total_timeout: timeout.saturating_mul(samples as u32),
+            start_time: Instant::now(),
+            delays: Vec::with_capacity(samples as usize),
+            lost: 0,
+            precision,
+        }
+    }
+
+    /// 下一次测试的超时时间；测试次数用完时返回 None
+    /// 总超时时间已经用完时，剩余的测试直接记为失败
+    fn next_timeout(&mut self) -> Option<Duration> {
+        while self.remaining_samples > 0 {
+            self.remaining_samples -= 1;
+            let remaining = self.total_timeout.saturating_sub(self.start_time.elapsed());
+            if remaining.is_zero() {
+                self.lost += 1;
+                continue;
+            }
+            return Some(self.timeout.min(remaining));
+        }
+        None
+    }
+
+    /// 记录一次测试结果；成功时为耗时（微秒）
+    fn record(&mut self, res: Result<u64>) {
+        match res {
+            // 微秒转成毫秒
+            Ok(d) => self.delays.push(d as f64 / 1000.0),
+            Err(_) => self.lost += 1,
+        }
+    }
+
+    fn finish(self) -> HostPingStats {
+        summarize_ping_delays(self.delays, self.lost, self.timeout, self.precision)
+    }
+}
+
+/// 汇总延迟样本；全部失败时各项指标都记为超时时间
+/// 平均延迟保留 `precision` 位小数
+fn summarize_ping_delays(
+    mut delays: Vec<f64>,
+    lost: u8,
+    timeout: Duration,
+    precision: u8,
+) -> HostPingStats {
+    if delays.is_empty() {
+        let ms = timeout.as_micros() as f64 / 1000.0;
+        return HostPingStats {
+            min: ms,
+            max: ms,
+            avg: ms,
+            p95: ms,
+            lost,
+        };
+    }
+
+    delays.sort_by(|a, b| a.total_cmp(b));
+
+    let avg = delays.iter().sum::<f64>() / delays.len() as f64;
+    // nearest-rank 算法
+    let p95_idx = ((delays.len() as f64 * 0.95).ceil() as usize).saturating_sub(1);
+
+    HostPingStats {
+        min: delays[0],
+        max: delays[delays.len() - 1],
+        avg: round_to(avg, precision),
+        p95: delays[p95_idx],
+        lost,
+    }
+}
+
+/// 按指定方式测试 host 延迟；返回耗时（微秒）
+pub fn run_test_host(
+    addr: &str,
+    port: u16,
+    ping_timeout: Duration,
+    method: PingMethod,
+) -> Result<u64> {
+    match method {
+        PingMethod::Tcp => run_test_tcp(addr, port, ping_timeout),
+        PingMethod::Icmp => run_test_icmp(addr, ping_timeout),
+    }
+}
+
+/// 按指定协议测试端口延迟；返回耗时（微秒）
+pub fn run_test_port(
+    addr: &str,
+    port: u16,
+    ping_timeout: Duration,
+    protocol: PingProtocol,
+) -> Result<u64> {
+    match protocol {
+        PingProtocol::Tcp => run_test_tcp(addr, port, ping_timeout),
+        PingProtocol::UdpEcho => run_test_udp_echo(addr, port, ping_timeout),
+        PingProtocol::Auto => run_test_tcp(addr, port, ping_timeout)
+            .or_else(|_| run_test_udp_echo(addr, port, ping_timeout)),
+    }
+}
+
+/// `run_test_port` 的异步版本；udp echo 测试在阻塞线程池中执行
+async fn run_test_port_async(
+    addr: &str,
+    port: u16,
+    ping_timeout: Duration,
+    protocol: PingProtocol,
+) -> Result<u64> {
+    let run_udp_echo = || {
+        let addr = addr.to_string();
+        async move {
+            tokio::task::spawn_blocking(move || run_test_udp_echo(&addr, port, ping_timeout))
+                .await
+                .unwrap_or_else(|err| Err(anyhow!("udp echo 测试任务异常：{err}")))
+        }
+    };
+
+    match protocol {
+        PingProtocol::Tcp => run_test_tcp_async(addr, port, ping_timeout).await,
+        PingProtocol::UdpEcho => run_udp_echo().await,
+        PingProtocol::Auto => match run_test_tcp_async(addr, port, ping_timeout).await {
+            Ok(d) => Ok(d),
+            Err(_) => run_udp_echo().await,
+        },
+    }
+}
+
+/// 测试 udp echo 耗时；发送 `PING\n` 并等待任意响应，返回耗时（微秒）
+pub fn run_test_udp_echo(addr: &str, port: u16, timeout: Duration) -> Result<u64> {
+    let sk = parse_host_addr(addr, port)?;
+    let local = match sk {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let socket = UdpSocket::bind(local)?;
+    // 超时时间不能为 0
+    socket.set_read_timeout(Some(timeout.max(Duration::from_millis(1))))?;
+    socket.connect(sk)?;
+
+    let start_time = Instant::now();
+    socket.send(b"PING\n")?;
+    let mut buf = [0u8; 64];
+    socket.recv(&mut buf).map_err(|err| {
+        anyhow!(
+            "当前等待时长：{} ms;错误信息：{err}",
+            start_time.elapsed().as_millis()
+        )
+    })?;
+    Ok(start_time.elapsed().as_micros() as u64)
+}
+
+/// 测试 icmp echo 耗时; 返回耗时（微秒）
+/// 使用 raw socket 时需要较高权限，权限不足会返回错误
+#[cfg(feature = "icmp")]
+pub fn run_test_icmp(addr: &str, ping_timeout: Duration) -> Result<u64> {
+    let ip = parse_host_addr(addr, 0)?.ip();
+    let reply =
+        ping::ping(ip, ping_timeout).map_err(|err| anyhow!("icmp 测试 {addr} 失败：{err}"))?;
+    Ok(reply.rtt.as_micros() as u64)
+}
+
+/// 未开启 icmp feature 时直接返回错误
+#[cfg(not(feature = "icmp"))]
+pub fn run_test_icmp(addr: &str, _ping_timeout: Duration) -> Result<u64> {
+    Err(anyhow!("icmp 测试 {addr} 失败：未开启 icmp feature"))
+}
+
+/// 测试tcp 连接耗时; 返回连接的耗时
+pub fn run_test_tcp(addr: &str, port: u16, ping_timeout: Duration) -> Result<u64> {
+    let sk = parse_host_addr(addr, port)?;
+    let start_time = Instant::now();
+    let _ = TcpStream::connect_timeout(&sk, ping_timeout).map_err(|err| {
+        anyhow!(
+            "当前连接时长：{} ms;错误信息：{err}",
+            start_time.elapsed().as_millis()
+        )
+    })?;
+    let elapsed_time = start_time.elapsed();
+    Ok(elapsed_time.as_micros() as u64)
+}
+
+/// 异步测试tcp 连接耗时; 返回连接的耗时（微秒）
+/// 不会阻塞 tokio 的工作线程，在异步上下文中使用
+pub async fn run_test_tcp_async(addr: &str, port: u16, ping_timeout: Duration) -> Result<u64> {
+    let sk = parse_host_addr(addr, port)?;
+    let start_time = Instant::now();
+    let _ = tokio::time::timeout(ping_timeout, tokio::net::TcpStream::connect(sk))
+        .await
+        .map_err(|_| anyhow!("连接超时：{} ms", ping_timeout.as_millis()))?
+        .map_err(|err| {
+            anyhow!(
+                "当前连接时长：{} ms;错误信息：{err}",
+                start_time.elapsed().as_millis()
+            )
+        })?;
+    let elapsed_time = start_time.elapsed();
+    Ok(elapsed_time.as_micros() as u64)
+}
+
+// 修改全局的推送通道和上报任务的测试串行执行
+#[cfg(test)]
+pub(crate) static GLOBAL_STATE_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_host_addr() {
+        let cases = [
+            ("127.0.0.1", "127.0.0.1:443"),
+            ("2001:db8::1", "[2001:db8::1]:443"),
+            ("127.0.0.1:80", "127.0.0.1:443"),
+            ("[::1]:80", "[::1]:443"),
+        ];
+
+        for (addr, expected) in cases {
+            let sk = parse_host_addr(addr, 443).unwrap();
+            assert_eq!(sk, expected.parse::<SocketAddr>().unwrap(), "{addr}");
+        }
+
+        assert!(parse_host_addr("ssss", 443).is_err());
+        assert!(parse_host_addr("[::1", 443).is_err());
+    }
+
+    #[test]
+    fn test_run_test_tcp_loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        for addr in ["127.0.0.1", "127.0.0.1:1"] {
+            run_test_tcp(addr, port, Duration::from_secs(1)).unwrap();
+        }
+
+        // ipv6 回环地址在部分环境下不可用，只在能绑定的时候测试
+        if let Ok(listener) = TcpListener::bind("[::1]:0") {
+            let port = listener.local_addr().unwrap().port();
+            for addr in ["::1", "[::1]:1"] {
+                run_test_tcp(addr, port, Duration::from_secs(1)).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_test_tcp_async() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        for addr in ["127.0.0.1", "127.0.0.1:1"] {
+            run_test_tcp_async(addr, port, Duration::from_secs(1))
+                .await
+                .unwrap();
+        }
+        assert!(run_test_tcp_async("ssss", port, Duration::from_secs(1))
+            .await
+            .is_err());
+
+        let info = HostPingInfo {
+            hosts: vec!["127.0.0.1".to_string(), "ssss".to_string()],
+            port,
+            timeout: Duration::from_millis(500),
+            samples: 2,
+            method: PingMethod::Tcp,
+            protocol: PingProtocol::Tcp,
+            precision: DEFAULT_STATS_PRECISION,
+        };
+        let host_ping = ping_hosts_async(info).await;
+        assert_eq!(host_ping["127.0.0.1"].lost, 0);
+        assert_eq!(host_ping["ssss"].lost, 2);
+    }
+
+    #[test]
+    fn test_summarize_ping_delays() {
+        let delays = (1..=20).map(|v| v as f64).collect();
+        let stats = summarize_ping_delays(delays, 2, Duration::from_secs(3), 3);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 20.0);
+        assert_eq!(stats.avg, 10.5);
+        assert_eq!(stats.p95, 19.0);
+        assert_eq!(stats.lost, 2);
+
+        let stats = summarize_ping_delays(vec![], 3, Duration::from_millis(1500), 3);
+        assert_eq!(stats.avg, 1500.0);
+        assert_eq!(stats.p95, 1500.0);
+        assert_eq!(stats.lost, 3);
+
+        // 平均延迟按配置的精度保留小数
+        let delays = vec![1.0, 1.0, 1.5];
+        assert_eq!(
+            summarize_ping_delays(delays.clone(), 0, Duration::from_secs(3), 3).avg,
+            1.167
+        );
+        assert_eq!(
+            summarize_ping_delays(delays, 0, Duration::from_secs(3), 1).avg,
+            1.2
+        );
+    }
+
+    #[test]
+    fn test_ping_host_samples() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let stats = ping_host(
+            "127.0.0.1",
+            port,
+            Duration::from_secs(1),
+            3,
+            PingMethod::Tcp,
+            PingProtocol::Tcp,
+            DEFAULT_STATS_PRECISION,
+        );
+        assert_eq!(stats.lost, 0);
+        assert!(stats.min <= stats.avg && stats.avg <= stats.max);
+
+        // 无效地址全部记为失败；次数最大为 10
+        let stats = ping_host(
+            "ssss",
+            port,
+            Duration::from_millis(10),
+            20,
+            PingMethod::Tcp,
+            PingProtocol::Tcp,
+            DEFAULT_STATS_PRECISION,
+        );
+        assert_eq!(stats.lost, 10);
+    }
+
+    #[test]
+    fn test_ping_sampler() {
+        let mut sampler = PingSampler::new(Duration::from_secs(1), 3, 3);
+        assert_eq!(sampler.next_timeout(), Some(Duration::from_secs(1)));
+        sampler.record(Ok(2000));
+        assert!(sampler.next_timeout().is_some());
+        sampler.record(Err(anyhow!("连接失败")));
+        assert!(sampler.next_timeout().is_some());
+        sampler.record(Ok(4000));
+        assert_eq!(sampler.next_timeout(), None);
+        let stats = sampler.finish();
+        assert_eq!((stats.min, stats.max, stats.lost), (2.0, 4.0, 1));
+
+        // 超时时间很大时不会溢出
+        let mut sampler = PingSampler::new(Duration::MAX, 3, 3);
+        assert_eq!(sampler.total_timeout, Duration::MAX);
+        assert!(sampler.next_timeout().is_some());
+
+        // 总超时时间用完后剩余的测试直接记为失败
+        let mut sampler = PingSampler::new(Duration::ZERO, 3, 3);
+        assert_eq!(sampler.next_timeout(), None);
+        assert_eq!(sampler.finish().lost, 3);
+    }
+
+    #[test]
+    fn test_run_test_udp_echo() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            while let Ok((n, peer)) = server.recv_from(&mut buf) {
+                let _ = server.send_to(&buf[..n], peer);
+            }
+        });
+
+        run_test_udp_echo("127.0.0.1", port, Duration::from_secs(1)).unwrap();
+        run_test_port(
+            "127.0.0.1",
+            port,
+            Duration::from_secs(1),
+            PingProtocol::UdpEcho,
+        )
+        .unwrap();
+        // 端口上没有 tcp 服务时使用 udp echo
+        run_test_port(
+            "127.0.0.1",
+            port,
+            Duration::from_secs(1),
+            PingProtocol::Auto,
+        )
+        .unwrap();
+
+        // 没有 echo 服务时超时返回错误
+        let silent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = silent.local_addr().unwrap().port();
+        assert!(run_test_udp_echo("127.0.0.1", port, Duration::from_millis(100)).is_err());
+
+        let config: RequestStatsConfig = serde_json::from_str(
+            r#"{"target":[],"reportingCycle":"10s","hostPingProtocol":"udpEcho"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.host_ping_protocol, PingProtocol::UdpEcho);
+    }
+
+    #[test]
+    fn test_run_test_icmp() {
+        // 权限不足或未开启 feature 时需要返回错误而不是 panic
+        let res = run_test_icmp("127.0.0.1", Duration::from_secs(1));
+        if cfg!(feature = "icmp") {
+            // 有权限时本机的延迟不会超过超时时间；没有权限时返回 icmp 测试失败
+            match res {
+                Ok(d) => assert!(d <= 1_000_000, "{d}"),
+                Err(err) => assert!(err.to_string().contains("icmp 测试 127.0.0.1 失败")),
+            }
+        } else {
+            assert_eq!(
+                res.unwrap_err().to_string(),
+                "icmp 测试 127.0.0.1 失败：未开启 icmp feature"
+            );
+        }
+        assert!(run_test_icmp("ssss", Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn test_host_ping_timeout_config() {
+        let config: RequestStatsConfig =
+            serde_json::from_str(r#"{"target":[],"reportingCycle":"10s"}"#).unwrap();
+        assert_eq!(config.host_ping_timeout, Duration::from_secs(3));
+        assert_eq!(config.host_ping_samples, 1);
+        assert_eq!(config.host_ping_method, PingMethod::Tcp);
+        assert_eq!(config.host_ping_protocol, PingProtocol::Tcp);
+        assert!(config.collect_system_resources);
+
+        let config: RequestStatsConfig = serde_json::from_str(
+            r#"{"target":[],"reportingCycle":"10s","hostPingTimeout":"500ms","hostPingMethod":"icmp"}"#,
+        )
+        .unwrap();
+        assert_eq!(config.host_ping_timeout, Duration::from_millis(500));
+        assert_eq!(config.host_ping_method, PingMethod::Icmp);
+    }
+
+    #[test]
+    fn test_scraper_error_results() {
+        let mut inner = InnerStats::new();
+        inner.update_stats(0, 10, 200, &RequestResult::Successful);
+        inner.update_stats(0, 10, 407, &RequestResult::ProxyError(None));
+        inner.update_stats(0, 10, 429, &RequestResult::RateLimited(None));
+        inner.update_stats(0, 10, 200, &RequestResult::CaptchaDetected(None));
+
+        let base = get_base();
+        let stats = inner.to_stats_and_reset(&base);
+        assert_eq!(stats.exception_types.proxy_error, 1);
+        assert_eq!(stats.exception_types.rate_limited, 1);
+        assert_eq!(stats.exception_types.captcha_detected, 1);
+        assert_eq!(stats.error_rate, 0.75);
+        assert_eq!(stats.error_rate_by_type["proxyError"], 0.25);
+        assert_eq!(stats.error_rate_by_type["rateLimited"], 0.25);
+        assert_eq!(stats.error_rate_by_type["captchaDetected"], 0.25);
+        assert_eq!(stats.error_rate_by_type["timeoutError"], 0.0);
+        assert_eq!(stats.error_rate_by_type.len(), 7);
+
+        // 旧版本的数据没有新增的字段
+        let exception_types: ExceptionTypes = serde_json::from_str(
+            r#"{"connectionError":1,"timeoutError":2,"parseError":3,"statusCodeError":4}"#,
+        )
+        .unwrap();
+        assert_eq!(exception_types.status_code_error, 4);
+        assert_eq!(exception_types.proxy_error, 0);
+        assert_eq!(exception_types.rate_limited, 0);
+        assert_eq!(exception_types.captcha_detected, 0);
+    }
+
+    #[test]
+    fn test_requests_per_second() {
+        let mut inner = InnerStats::new();
+        inner.start_time -= 2000;
+        for _ in 0..3 {
+            inner.update_stats(0, 10, 200, &RequestResult::Successful);
+        }
+        inner.update_stats(0, 10, 0, &RequestResult::TimeoutError(None));
+
+        let base = get_base();
+        let stats = inner.to_stats_and_reset(&base);
+        let period = (stats.time_period.end - stats.time_period.start) as f64 / 1000.0;
+        assert!((stats.requests_per_second - 4.0 / period).abs() < 0.001);
+        assert!((stats.successful_requests_per_second - 3.0 / period).abs() < 0.001);
+
+        // 周期时长为 0
+        inner.start_time = get_now_millis() + 1000;
+        let stats = inner.to_stats_and_reset(&base);
+        assert_eq!(stats.requests_per_second, 0.0);
+        assert_eq!(stats.successful_requests_per_second, 0.0);
+    }
+
+    #[test]
+    fn test_stats_display() {
+        let stats = Stats {
+            time_period: TimePeriod {
+                start: 1705312800000,
+                end: 1705313100000,
+            },
+            total_requests: 1234,
+            error_rate: 0.012,
+            average_request_latency: 45.3,
+            cache_hit_rate: 0.234,
+            ..Default::default()
+        };
+        assert_eq!(
+            stats.to_string(),
+            "[2024-01-15 10:00–10:05] requests: 1,234  errors: 1.2%  avg_latency: 45.3ms  cache: 23.4%"
+        );
+        assert_eq!(stats.to_owned().to_string(), stats.to_string());
+        // 开启 compact_log 时日志使用 Display，默认使用 key=value 格式
+        assert_eq!(log_summary(&stats, true), stats.to_string());
+        assert_eq!(log_summary(&stats, false), stats.to_line_summary());
+
+        // 超出可以格式化的范围时输出毫秒数
+        let stats = Stats {
+            time_period: TimePeriod {
+                start: 253402300800000,
+                end: 253402300800001,
+            },
+            ..Default::default()
+        };
+        assert!(stats
+            .to_string()
+            .starts_with("[253402300800000–253402300800001] requests: 0"));
+        let stats = Stats {
+            time_period: TimePeriod {
+                start: 0,
+                end: i64::MAX,
+            },
+            ..Default::default()
+        };
+        assert!(stats
+            .to_string()
+            .starts_with(&format!("[1970-01-01 00:00–{}]", i64::MAX)));
+    }
+
+    #[test]
+    fn test_push_handle() {
+        let handle = push::load_push_handle(vec![]);
+        assert!(handle.target_urls().is_empty());
+        // 没有推送目标时没有接收方
+        assert!(handle.send(Arc::new(OwnedStats::default())).is_err());
+    }
+
+    #[test]
+    fn test_spider_stats_handle() {
+        let config = RequestStatsConfig {
+            target: vec![],
+            ..Default::default()
+        };
+        let a = SpiderStatsHandle::new(&config);
+        let b = SpiderStatsHandle::new(&config);
+        a.update_stats(0, 10, 200, RequestResult::Successful);
+        a.update_stats(0, 10, 200, RequestResult::Successful);
+        b.update_stats(0, 10, 0, RequestResult::TimeoutError(None));
+
+        // 实例之间互不影响
+        let base = get_base();
+        assert_eq!(a.stats().snapshot(&base).total_requests, 2);
+        assert_eq!(b.stats().snapshot(&base).error_rate, 1.0);
+        assert!(a.push_handle().target_urls().is_empty());
+
+        a.send_stats(&base, None);
+        assert_eq!(a.stats().snapshot(&base).total_requests, 0);
+        assert!(!a.send_stats_if_nonempty(&base, None));
+        assert!(b.clone().send_stats_if_nonempty(&base, None));
+        assert_eq!(b.stats().snapshot(&base).total_requests, 0);
+    }
+
+    #[test]
+    fn test_push_envelope() {
+        let stats = Stats {
+            total_requests: 10,
+            ..Default::default()
+        }
+        .into_owned();
+
+        let value: serde_json::Value =
+            serde_json::from_str(&push::encode(&stats, false, None).unwrap()).unwrap();
+        assert_eq!(value["totalRequests"], 10);
+
+        let value: serde_json::Value =
+            serde_json::from_str(&push::encode(&stats, true, None).unwrap()).unwrap();
+        assert_eq!(value["schemaVersion"], STATS_SCHEMA_VERSION);
+        assert_eq!(value["payload"]["totalRequests"], 10);
+        assert!(value["sentAtMs"].as_i64().unwrap() > 0);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn test_push_gzip() {
+        use base64::Engine;
+        use std::io::Read;
+
+        let stats = Stats::default().into_owned();
+        let pkg = push::encode(&stats, false, Some(PushCompression::Gzip)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&pkg).unwrap();
+        assert_eq!(value["encoding"], "gzip");
+
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(value["data"].as_str().unwrap())
+            .unwrap();
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(&data[..])
+            .read_to_string(&mut json)
+            .unwrap();
+        assert_eq!(json, serde_json::to_string(&stats).unwrap());
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_push_zstd() {
+        use base64::Engine;
+
+        let stats = Stats::default().into_owned();
+        let pkg = push::encode(&stats, false, Some(PushCompression::Zstd)).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&pkg).unwrap();
+        assert_eq!(value["encoding"], "zstd");
+
+        let data = base64::engine::general_purpose::STANDARD
+            .decode(value["data"].as_str().unwrap())
+            .unwrap();
+        let json = zstd::decode_all(&data[..]).unwrap();
+        assert_eq!(json, serde_json::to_vec(&stats).unwrap());
+    }
+
+    #[test]
+    fn test_stats_diff() {
+        let previous = Stats {
+            total_requests: 100,
+            error_rate: 0.01,
+            average_request_latency: 50.0,
+            ..Default::default()
+        };
+        let current = Stats {
+            total_requests: 120,
+            error_rate: 0.03,
+            average_request_latency: 40.0,
+            ..Default::default()
+        };
+
+        let diff = current.diff(&previous);
+        assert_eq!(diff.request_count_delta, 20);
+        assert!(testing::f64_approx_eq(diff.error_rate_delta, 0.02));
+        assert!(testing::f64_approx_eq(diff.latency_delta_ms, -10.0));
+        assert!(diff.errors_increased());
+        assert!(!previous.diff(&current).errors_increased());
+        assert_eq!(
+            diff.to_string(),
+            "errors: +2.0%  latency: -10.0ms  requests: +20  rps: +0.0  cache: +0.0%"
+        );
+
+        // 没有开启时不输出
+        let value = serde_json::to_value(&current).unwrap();
+        assert!(value.get("diff").is_none());
+    }
+
+    #[test]
+    fn test_run_clean_configs() {
+        let dir = std::env::temp_dir().join(format!("stats_run_clean_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.json"), [0u8; 10]).unwrap();
+        std::fs::write(dir.join("b.json"), [0u8; 20]).unwrap();
+
+        let clean_config = clean::CleanConfig {
+            paths: vec![dir.to_str().unwrap().to_string(), "/not/exists".to_string()],
+            max_age: None,
+            max_total_bytes: Some(0),
+            dry_run: false,
+            allowed_extensions: None,
+        };
+        let clean_stats = run_clean_configs(&[clean_config]);
+        assert_eq!(clean_stats.files_deleted, 2);
+        assert_eq!(clean_stats.bytes_freed, 30);
+
+        let stats = Stats {
+            clean_stats: Some(clean_stats),
+            ..Default::default()
+        };
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(value["cleanStats"]["filesDeleted"], 2);
+        assert_eq!(value["cleanStats"]["bytesFreed"], 30);
+
+        // 没有配置清理目录时不输出
+        let value = serde_json::to_value(Stats::default()).unwrap();
+        assert!(value.get("cleanStats").is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_record_history() {
+        let mut history = VecDeque::new();
+        for total_requests in 1..=5 {
+            let stats = Stats {
+                total_requests,
+                ..Default::default()
+            }
+            .into_owned();
+            record_history(&mut history, &stats, 3);
+        }
+        let totals: Vec<_> = history.iter().map(|s| s.total_requests).collect();
+        assert_eq!(totals, [3, 4, 5]);
+
+        // 不保留时清空历史数据
+        record_history(&mut history, &OwnedStats::default(), 0);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_stats_precision() {
+        assert_eq!(round_to(0.123456, 3), 0.123);
+        assert_eq!(round_to(0.123456, 2), 0.12);
+        assert_eq!(round_to(0.123456, 0), 0.0);
+
+        let stats = RequestStats::new();
+        for result in [
+            RequestResult::Successful,
+            RequestResult::Successful,
+            RequestResult::TimeoutError(None),
+        ] {
+            stats.update_stats(0, 1000, 200, result);
+        }
+
+        let base = get_base();
+        assert_eq!(stats.snapshot(&base).error_rate, 0.333);
+        stats.set_precision(6);
+        let d = stats.snapshot(&base);
+        assert_eq!(d.error_rate, 0.333333);
+        assert_eq!(d.error_rate_by_type["timeoutError"], 0.333333);
+        stats.set_precision(1);
+        assert_eq!(stats.snapshot(&base).error_rate, 0.3);
+
+        let config: RequestStatsConfig =
+            serde_json::from_str(r#"{"target":[],"reportingCycle":"10s"}"#).unwrap();
+        assert_eq!(config.stats_precision, 3);
+    }
+
+    #[test]
+    fn test_status_code_group() {
+        assert_eq!(status_code_group(101), "1xx");
+        assert_eq!(status_code_group(204), "2xx");
+        assert_eq!(status_code_group(301), "3xx");
+        assert_eq!(status_code_group(429), "4xx");
+        assert_eq!(status_code_group(503), "5xx");
+        assert_eq!(status_code_group(999), "other");
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let a = RequestStats::new();
+        let b = RequestStats::new();
+        a.update_stats(0, 1000, 200, RequestResult::Successful);
+        a.update_stats(0, 1000, 500, RequestResult::StatusCodeError(None));
+        b.update_stats(0, 4000, 200, RequestResult::SuccessfulAndCache);
+        b.update_stats(0, 2000, 0, RequestResult::TimeoutError(None));
+
+        let base = get_base();
+        let d = aggregate(&[&a, &b], &base);
+        assert_eq!(d.total_requests, 4);
+        assert_eq!(d.cache_hit, 1);
+        assert_eq!(d.cache_hit_rate, 0.5);
+        assert_eq!(d.error_rate, 0.5);
+        assert_eq!(d.exception_types.status_code_error, 1);
+        assert_eq!(d.exception_types.timeout_error, 1);
+        assert_eq!(d.http_status_codes["200"], 2);
+        assert_eq!(d.http_status_codes["500"], 1);
+        assert_eq!(d.http_status_groups["2xx"], 2);
+        assert_eq!(d.http_status_groups["5xx"], 1);
+        assert!(!d.http_status_groups.contains_key("4xx"));
+        assert_eq!(d.average_request_latency, 2.0);
+
+        // 不会清空各实例的数据
+        assert_eq!(a.snapshot(&base).total_requests, 2);
+        assert_eq!(b.snapshot(&base).total_requests, 2);
+    }
+
+    #[test]
+    fn test_request_stats_clone() {
+        let stats = RequestStats::new();
+        let cloned = stats.clone();
+        cloned.update_stats(0, 10, 200, RequestResult::Successful);
+        stats.update_stats(0, 10, 200, RequestResult::Successful);
+
+        let base = get_base();
+        assert_eq!(stats.to_stats_and_reset(&base, None).total_requests, 2);
+        assert_eq!(cloned.snapshot(&base).total_requests, 0);
+    }
+
+    #[test]
+    fn test_sampled_stats() {
+        let base = get_base();
+
+        // 采样率为 1 时记录所有请求
+        let stats = RequestStats::new_sampled(1.0);
+        for _ in 0..10 {
+            stats.update_stats(0, 10, 200, RequestResult::Successful);
+        }
+        assert_eq!(stats.to_stats_and_reset(&base, None).total_requests, 10);
+
+        // 采样率为 0 时不记录
+        let stats = RequestStats::new_sampled(0.0);
+        stats.update_stats(0, 10, 200, RequestResult::Successful);
+        assert_eq!(stats.to_stats_and_reset(&base, None).total_requests, 0);
+
+        let stats = RequestStats::new_sampled(0.5);
+        for i in 0..10000 {
+            let result = match i % 4 {
+                0 => RequestResult::TimeoutError(None),
+                _ => RequestResult::Successful,
+            };
+            stats.update_stats(0, 10, 200, result);
+        }
+        let d = stats.to_stats_and_reset(&base, None);
+        assert!((9000..=11000).contains(&d.total_requests));
+        assert_eq!(d.http_status_codes["200"], d.total_requests);
+        assert!((d.successful_requests + d.failed_requests - d.total_requests).abs() <= 1);
+        // 错误率不放大
+        assert!((d.error_rate - 0.25).abs() < 0.05);
+
+        let v = (0..1000).map(|_| fast_random()).collect::<Vec<_>>();
+        assert!(v.iter().all(|x| (0.0..1.0).contains(x)));
+    }
+
+    #[test]
+    fn test_inner_stats_val_add() {
+        let a = RequestStats::new();
+        let b = RequestStats::new();
+        a.update_stats(0, 1000, 200, RequestResult::Successful);
+        b.update_stats(0, 3000, 200, RequestResult::SuccessfulAndCache);
+        b.update_stats(0, 2000, 500, RequestResult::StatusCodeError(None));
+
+        assert_eq!(b.borrow_inner(|v| v.total_requests), 2);
+
+        let workers = [a.values(), b.values()];
+        let combined = workers
+            .iter()
+            .fold(InnerStatsVal::default(), |acc, s| acc + s.clone());
+        assert_eq!(combined.total_requests, 3);
+        assert_eq!(combined.cache_hit, 1);
+        assert_eq!(combined.http_status_codes[&200], 2);
+        assert_eq!(combined.http_status_codes[&500], 1);
+
+        let mut total = a.values();
+        total += b.values();
+        assert_eq!(total, combined);
+
+        let base = get_base();
+        let d = RequestStats::from(combined).snapshot(&base);
+        assert_eq!(d.total_requests, 3);
+        assert_eq!(d.average_request_latency, 2.0);
+    }
+
+    #[test]
+    fn test_stats_schema_version() {
+        let mut inner = InnerStats::new();
+        inner.update_stats(0, 1000, 200, &RequestResult::Successful);
+
+        let base = get_base();
+        let stats = inner.to_stats_and_reset(&base);
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(value["schemaVersion"], STATS_SCHEMA_VERSION);
+
+        let decoded: Stats = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.schema_version, STATS_SCHEMA_VERSION);
+        assert_eq!(decoded.total_requests, 1);
+        assert_eq!(*decoded.base, base);
+
+        // 缺失的字段使用默认值，未知字段忽略
+        let decoded: Stats = serde_json::from_str(
+            r#"{"serverName":"s","scraperName":"","projectCode":"","scraperType":"","totalRequests":3,"unknown":1}"#,
+        )
+        .unwrap();
+        assert_eq!(decoded.schema_version, 0);
+        assert_eq!(decoded.total_requests, 3);
+        assert_eq!(decoded.base.server_name, "s");
+
+        assert!(Stats::is_compatible(STATS_SCHEMA_VERSION));
+        assert!(Stats::is_compatible(STATS_SCHEMA_VERSION - 1));
+        assert!(!Stats::is_compatible(STATS_SCHEMA_VERSION + 1));
+        assert!(!Stats::is_compatible(u32::MAX));
+    }
+
+    #[test]
+    fn test_owned_stats() {
+        let owned = {
+            let base = get_base();
+            let stats = InnerStats::new().to_stats_and_reset(&base);
+            stats.to_owned()
+        };
+        assert!(matches!(owned.base, Cow::Owned(_)));
+
+        let value = serde_json::to_value(&owned).unwrap();
+        assert_eq!(value["schemaVersion"], STATS_SCHEMA_VERSION);
+
+        let stats: Stats<'static> = owned.into();
+        assert_eq!(stats.total_requests, 0);
+    }
+
+    #[test]
+    fn test_stats_eq() {
+        let base = get_base();
+        let mut inner = InnerStats::new();
+        inner.update_stats(0, 1000, 200, &RequestResult::Successful);
+        inner.update_stats(0, 2000, 0, &RequestResult::TimeoutError(None));
+        let stats = inner.to_stats(&base);
+
+        let value = serde_json::to_value(&stats).unwrap();
+        let back: Stats = serde_json::from_value(value).unwrap();
+        assert_eq!(back, stats);
+        assert_eq!(stats.to_owned(), stats.clone().into_owned());
+
+        let inner_back: InnerStatsVal =
+            serde_json::from_value(serde_json::to_value(&inner.base).unwrap()).unwrap();
+        assert!(inner_back == inner.base);
+
+        assert!(testing::f64_approx_eq(0.1 + 0.2, 0.3));
+        assert!(!testing::f64_approx_eq(stats.error_rate, 0.4));
+    }
+
+    #[test]
+    fn test_stats_to_json_value() {
+        let base = get_base();
+        let mut inner = InnerStats::new();
+        inner.update_stats_with(
+            0,
+            1000,
+            200,
+            &RequestResult::Successful,
+            &RequestInfo {
+                retry_count: 1,
+                request_bytes: 100,
+                response_bytes: 2000,
+                proxy_id: Some("proxy-a"),
+                dns_duration_us: Some(500),
+                ..Default::default()
+            },
+        );
+        inner.update_stats(
+            0,
+            2000,
+            0,
+            &RequestResult::TimeoutError(Some("read timeout".into())),
+        );
+        let mut stats = inner.to_stats(&base);
+        stats.diff = Some(StatsDiff::default());
+        stats.clean_stats = Some(clean::CleanStats::default());
+
+        let value = serde_json::Value::from(&stats);
+        assert_eq!(value, serde_json::to_value(&stats).unwrap());
+        assert_eq!(value["exceptionTypes"]["timeoutError"], 1);
+
+        let stats = Stats::default();
+        assert_eq!(
+            serde_json::Value::from(&stats),
+            serde_json::to_value(&stats).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stats_accessors() {
+        let stats = Stats {
+            error_rate: 0.25,
+            total_requests: 8,
+            average_request_latency: 12.5,
+            cache_hit_rate: 0.5,
+            exception_types: ExceptionTypes {
+                connection_error: 1,
+                timeout_error: 2,
+                rate_limited: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(stats.error_rate(), 0.25);
+        assert_eq!(stats.total_requests(), 8);
+        assert_eq!(stats.average_latency_ms(), 12.5);
+        assert_eq!(stats.cache_hit_rate(), 0.5);
+        assert_eq!(stats.connection_errors(), 1);
+        assert_eq!(stats.timeout_errors(), 2);
+        assert_eq!(stats.rate_limited(), 1);
+        assert_eq!(stats.parse_errors(), 0);
+        assert_eq!(stats.error_count(), 4);
+        assert_eq!(stats.to_owned().timeout_errors(), 2);
+    }
+
+    #[test]
+    fn test_stats_base_builder() {
+        let base = StatsBase::builder()
+            .server_name("server")
+            .scraper_name("scraper".to_string())
+            .project_code("code")
+            .scraper_type("type")
+            .request_frequency(10)
+            .build();
+
+        assert_eq!(
+            base,
+            StatsBase {
+                server_name: "server".to_string(),
+                scraper_name: "scraper".to_string(),
+                project_code: "code".to_string(),
+                scraper_type: "type".to_string(),
+                request_frequency: 10,
+                extra: HashMap::new(),
+            }
+        );
+        assert_eq!(StatsBase::builder().build(), get_base());
+    }
+
+    #[test]
+    fn test_set_base() {
+        // 测试结束后恢复，不影响其他测试使用的全局 base
+        let previous = CURRENT_BASE.lock().take();
+
+        set_base(
+            StatsBase::builder()
+                .scraper_name("spider")
+                .request_frequency(10)
+                .build(),
+        );
+        assert_eq!(current_base().scraper_name, "spider");
+
+        update_base(|base| base.request_frequency = 20);
+        let base = current_base();
+        assert_eq!(base.scraper_name, "spider");
+        assert_eq!(base.request_frequency, 20);
+
+        *CURRENT_BASE.lock() = previous;
+    }
+
+    #[test]
+    fn test_stats_base_extra() {
+        let base = StatsBase::builder()
+            .scraper_name("spider")
+            .extra("dataCenter", "us-east-1")
+            .extra("workers", 8)
+            .build();
+        assert_eq!(base.extra["dataCenter"], "us-east-1");
+
+        // 自定义的元数据展开到统计信息的顶层
+        let stats = RequestStats::new();
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        let stats = stats.snapshot(&base);
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(value["dataCenter"], "us-east-1");
+        assert_eq!(value["workers"], 8);
+        assert_eq!(value["scraperName"], "spider");
+        assert_eq!(serde_json::Value::from(&stats), value);
+
+        let parsed: Stats = serde_json::from_value(value).unwrap();
+        assert_eq!(*parsed.base, base);
+    }
+
+    #[test]
+    fn test_stats_base_eq_ignore_case() {
+        let a = StatsBase::builder()
+            .server_name("server")
+            .scraper_name("MySpider")
+            .project_code("ProjA")
+            .scraper_type("http")
+            .build();
+        let b = StatsBase::builder()
+            .server_name("server")
+            .scraper_name("myspider")
+            .project_code("PROJA")
+            .scraper_type("http")
+            .build();
+        assert_eq!(a, b);
+
+        // 服务器名称和爬虫类型区分大小写
+        let c = StatsBase {
+            server_name: "SERVER".to_string(),
+            ..b.clone()
+        };
+        assert_ne!(a, c);
+        let d = StatsBase {
+            scraper_type: "HTTP".to_string(),
+            ..b
+        };
+        assert_ne!(a, d);
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        let from_vars = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            RequestStatsConfig::from_env_with(|name| vars.get(name).cloned())
+        };
+        assert!(from_vars(&[("STATS_REPORTING_CYCLE", "30s")]).is_err());
+
+        let vars = [
+            ("STATS_TARGET", "ws://127.0.0.1:5003, ws://127.0.0.1:5004"),
+            ("STATS_REPORTING_CYCLE", "30s"),
+        ];
+        let config = from_vars(&vars).unwrap();
+        assert_eq!(
+            config.target,
+            vec!["ws://127.0.0.1:5003", "ws://127.0.0.1:5004"]
+        );
+        assert_eq!(config.reporting_cycle, Duration::from_secs(30));
+        assert_eq!(config.host_test_port, 443);
+        assert_eq!(config.host_ping_timeout, Duration::from_secs(3));
+
+        let config = from_vars(&[
+            vars[0],
+            vars[1],
+            ("STATS_HOST_TEST_PORT", "80"),
+            ("STATS_HOST_PING_TIMEOUT", "500ms"),
+        ])
+        .unwrap();
+        assert_eq!(config.host_test_port, 80);
+        assert_eq!(config.host_ping_timeout, Duration::from_millis(500));
+
+        assert!(from_vars(&[vars[0], vars[1], ("STATS_HOST_TEST_PORT", "abc")]).is_err());
+    }
+
+    #[test]
+    fn test_base_from_env() {
+        let from_vars = |vars: &[(&str, &str)]| {
+            let vars: HashMap<String, String> = vars
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            StatsBase::from_env_with(|name| vars.get(name).cloned())
+        };
+        assert!(from_vars(&[("STATS_PROJECT_CODE", "P001")]).is_err());
+
+        let vars = [
+            ("STATS_PROJECT_CODE", "P001"),
+            ("STATS_SCRAPER_NAME", "amazon"),
+            ("HOSTNAME", "pod-1"),
+        ];
+        let base = from_vars(&vars).unwrap();
+        assert_eq!(base.server_name, "pod-1");
+        assert_eq!(base.scraper_name, "amazon");
+        assert_eq!(base.project_code, "P001");
+        assert_eq!(base.scraper_type, "");
+        assert_eq!(base.request_frequency, 0);
+
+        let base = from_vars(&[
+            vars[0],
+            vars[1],
+            vars[2],
+            ("STATS_SERVER_NAME", "server-1"),
+            ("STATS_SCRAPER_TYPE", "http"),
+            ("STATS_REQUEST_FREQUENCY", "20"),
+        ])
+        .unwrap();
+        assert_eq!(base.server_name, "server-1");
+        assert_eq!(base.scraper_type, "http");
+        assert_eq!(base.request_frequency, 20);
+
+        assert!(from_vars(&[vars[0], vars[1], ("STATS_REQUEST_FREQUENCY", "fast")]).is_err());
+        assert!(from_vars(&[("STATS_PROJECT_CODE", " "), vars[1]]).is_err());
+    }
+
+    #[test]
+    fn test_config_from_toml() {
+        let config = RequestStatsConfig::from_toml(
+            r#"
+            target = ["ws://127.0.0.1:5003"]
+            reporting_cycle = "30s"
+            host_ping_timeout = "500ms"
+            host_ping_method = "icmp"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.target, vec!["ws://127.0.0.1:5003"]);
+        assert_eq!(config.reporting_cycle, Duration::from_secs(30));
+        assert_eq!(config.host_ping_timeout, Duration::from_millis(500));
+        assert_eq!(config.host_ping_method, PingMethod::Icmp);
+        assert_eq!(config.host_test_port, 443);
+        assert_eq!(config.health_max_error_rate, 0.5);
+
+        // 也支持 camelCase
+        let config = RequestStatsConfig::from_toml(
+            "target = []\nreportingCycle = \"1m\"\nhostTestPort = 80",
+        )
+        .unwrap();
+        assert_eq!(config.reporting_cycle, Duration::from_secs(60));
+        assert_eq!(config.host_test_port, 80);
+        assert_eq!(config.snapshot_path, None);
+        assert_eq!(config.snapshot_interval, Duration::from_secs(5));
+
+        let config = RequestStatsConfig::from_toml(
+            "target = []\nreporting_cycle = \"1m\"\nsnapshot_path = \"/tmp/stats.json\"\nsnapshot_interval = \"10s\"",
+        )
+        .unwrap();
+        assert_eq!(config.snapshot_path, Some(PathBuf::from("/tmp/stats.json")));
+        assert_eq!(config.snapshot_interval, Duration::from_secs(10));
+
+        assert!(RequestStatsConfig::from_toml("target = []\nreporting_cycle = \"abc\"").is_err());
+
+        let path = std::env::temp_dir().join(format!("stats_config_{}.toml", std::process::id()));
+        std::fs::write(&path, "target = []\nreporting_cycle = \"30s\"").unwrap();
+        let config = RequestStatsConfig::from_toml_file(&path).unwrap();
+        assert_eq!(config.reporting_cycle, Duration::from_secs(30));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_config_default() {
+        let config = RequestStatsConfig::default();
+        assert_eq!(config.target, vec!["ws://127.0.0.1:5003"]);
+        assert_eq!(config.reporting_cycle, Duration::from_secs(60));
+        assert_eq!(config.host_test_port, 443);
+
+        // 可选项和反序列化的默认值一致
+        let deserialized: RequestStatsConfig =
+            serde_json::from_str(r#"{"target":["ws://127.0.0.1:5003"],"reportingCycle":"60s"}"#)
+                .unwrap();
+        assert_eq!(deserialized, config);
+    }
+
+    #[test]
+    fn test_config_log_level() {
+        let config =
+            RequestStatsConfig::from_toml("target = []\nreporting_cycle = \"5s\"").unwrap();
+        assert_eq!(config.log_level, LogLevel::Info);
+
+        let config = RequestStatsConfig::from_toml(
+            "target = []\nreporting_cycle = \"5s\"\nlog_level = \"debug\"",
+        )
+        .unwrap();
+        assert_eq!(config.log_level, LogLevel::Debug);
+        assert_eq!(LogLevel::from_u8(config.log_level as u8), LogLevel::Debug);
+    }
+
+    #[test]
+    fn test_config_targets() {
+        let config = RequestStatsConfig::from_toml(
+            r#"
+            target = []
+            reporting_cycle = "60s"
+
+            [[targets]]
+            url = "wss://saas.example.com/stats"
+            cycle = "1m"
+            include_system_resources = false
+            include_host_ping = false
+
+            [[targets]]
+            url = "ws://collector:5003"
+            cycle = "10s"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.targets.len(), 2);
+        assert_eq!(config.targets[0].cycle, Duration::from_secs(60));
+        assert!(!config.targets[0].include_system_resources);
+        assert!(!config.targets[0].include_host_ping);
+        assert_eq!(config.targets[1].url, "ws://collector:5003");
+        assert!(config.targets[1].include_system_resources);
+        assert!(config.targets[1].include_host_ping);
+
+        let value = serde_json::to_value(&config).unwrap();
+        assert_eq!(value["targets"][1]["cycle"], "10s");
+    }
+
+    #[test]
+    fn test_config_validate() {
+        let config = RequestStatsConfig {
+            target: vec!["ws://collector:5003".to_string()],
+            reporting_cycle: Duration::from_secs(60),
+            ..Default::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+
+        let config = RequestStatsConfig {
+            target: vec![],
+            reporting_cycle: Duration::ZERO,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err(),
+            vec!["target 不能为空", "reporting_cycle 必须大于 0"]
+        );
+
+        // 只使用独立周期推送的目标；icmp 测试延迟时不使用端口
+        let config = RequestStatsConfig {
+            target: vec!["not a url".to_string()],
+            targets: vec![TargetConfig {
+                url: "ws://collector:5003".to_string(),
+                cycle: Duration::ZERO,
+                include_system_resources: true,
+                include_host_ping: true,
+            }],
+            reporting_cycle: Duration::from_secs(60),
+            host_test_port: 0,
+            host_ping_method: PingMethod::Icmp,
+            ..Default::default()
+        };
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("target 地址 \"not a url\" 格式错误"));
+        assert_eq!(
+            errors[1],
+            "targets 地址 \"ws://collector:5003\" 的 cycle 必须大于 0"
+        );
+
+        // 自定义上报方式不要求推送地址
+        let config = RequestStatsConfig {
+            target: vec![],
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+        assert!(config.validation_errors(false, true).is_empty());
+
+        // tcp 测试延迟时端口必须大于 0；没有需要测试的 hosts 时不检查端口
+        let config = RequestStatsConfig {
+            target: vec!["ws://collector:5003".to_string()],
+            host_test_port: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err(),
+            vec!["host_test_port 必须大于 0"]
+        );
+        assert!(config.validation_errors(true, false).is_empty());
+        assert!(check_config(&config, true, &|| Ok(vec![])).is_ok());
+        assert!(check_config(&config, true, &|| Ok(vec!["a.com".to_string()])).is_err());
+
+        let config = RequestStatsConfig {
+            target: vec!["ws://collector:5003".to_string()],
+            host_ping_samples: 0,
+            stats_precision: 16,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.validate().unwrap_err(),
+            vec![
+                "host_ping_samples 必须在 1 到 10 之间",
+                "stats_precision 不能超过 15"
+            ]
+        );
+        let config = RequestStatsConfig {
+            host_ping_samples: 11,
+            ..config
         };
+        assert_eq!(config.validate().unwrap_err().len(), 2);
+    }
 
-        // ms
-        let average_latency = (self.total_latency as f64 / self.total_requests as f64) / 1000.0;
+    #[test]
+    fn test_config_duration_round_trip() {
+        let config = RequestStatsConfig::from_toml(
+            "target = []\nreporting_cycle = \"5m\"\nreporting_jitter = \"1m 30s\"\nsnapshot_interval = \"10s\"",
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&config).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["reportingCycle"], "5m");
+        assert_eq!(value["hostPingTimeout"], "3s");
+        assert_eq!(value["reportingJitter"], "1m 30s");
+        assert_eq!(value["snapshotInterval"], "10s");
+
+        let back: RequestStatsConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, config);
+    }
+
+    #[test]
+    fn test_global() {
+        static VALUE: Global<String> = Global::new();
+        assert!(VALUE.try_deref().is_none());
+        assert!(!VALUE.is_initialized());
+
+        VALUE.init("value".to_string()).unwrap();
+        assert_eq!(VALUE.try_deref().unwrap(), "value");
+        assert_eq!(VALUE.len(), 5);
+        assert!(VALUE.init("other".to_string()).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "未初始化")]
+    fn test_global_deref_uninit() {
+        static VALUE: Global<String> = Global::new();
+        let _ = VALUE.len();
+    }
+
+    #[test]
+    fn test_per_core_cpu() {
+        let resources = get_system_resources_with(true);
+        assert!(!resources.cpu_cores.is_empty());
+        for (i, core) in resources.cpu_cores.iter().enumerate() {
+            assert_eq!(core.index, i);
+        }
+
+        assert!(resources.process_memory.rss_mb <= resources.process_memory.virtual_mb);
+
+        let resources = get_system_resources_with(false);
+        assert!(resources.cpu_cores.is_empty());
+        let value = serde_json::to_value(&resources).unwrap();
+        assert!(value.get("cpuCores").is_none());
+    }
+
+    #[test]
+    fn test_network_io_delta() {
+        let prev = NetworkIO {
+            bytes_received: 100,
+            bytes_sent: 200,
+            packets_received: 10,
+            packets_sent: 20,
+        };
+        let now = NetworkIO {
+            bytes_received: 150,
+            bytes_sent: 260,
+            packets_received: 15,
+            packets_sent: 19,
+        };
+        assert_eq!(
+            now.delta(&prev),
+            NetworkIO {
+                bytes_received: 50,
+                bytes_sent: 60,
+                packets_received: 5,
+                packets_sent: 0,
+            }
+        );
+        assert_eq!(now.delta(&now), NetworkIO::default());
+    }
+
+    #[test]
+    fn test_disk_io_throughput() {
+        let prev = DiskIOSnapshot {
+            read_bytes: 1000,
+            written_bytes: 2000,
+            at: Instant::now(),
+        };
+        assert_eq!(prev.throughput(&prev), DiskIO::default());
+
+        let now = DiskIOSnapshot {
+            read_bytes: 3000,
+            written_bytes: 1000,
+            at: prev.at + Duration::from_secs(2),
+        };
+        assert_eq!(
+            now.throughput(&prev),
+            DiskIO {
+                read_bytes_per_sec: 1000,
+                write_bytes_per_sec: 0,
+            }
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_thread_and_fd_count() {
+        let handle = thread::spawn(|| thread::sleep(Duration::from_millis(200)));
+        assert!(get_thread_count() >= 2);
+        handle.join().unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(get_open_fd_count() >= 1);
+        drop(listener);
+    }
+
+    #[test]
+    fn test_recent_errors() {
+        let mut inner = InnerStats::new();
+        inner.recent_errors_limit = 2;
+        for i in 0..3 {
+            let ctx = format!("https://example.com/{i}");
+            inner.update_stats(0, 10, 200, &RequestResult::ParseError(Some(ctx.into())));
+        }
+        inner.update_stats(0, 10, 0, &RequestResult::TimeoutError(None));
+
+        let base = get_base();
+        let stats = inner.to_stats_and_reset(&base);
+        assert_eq!(stats.exception_types.parse_error, 3);
+        assert_eq!(stats.exception_types.timeout_error, 1);
+        assert_eq!(
+            stats.recent_errors["parseError"],
+            vec!["https://example.com/1", "https://example.com/2"]
+        );
+        assert!(!stats.recent_errors.contains_key("timeoutError"));
+
+        let value = serde_json::to_value(&stats).unwrap();
+        assert_eq!(
+            value["recentErrors"]["parseError"][1],
+            "https://example.com/2"
+        );
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let stats = RequestStats::new();
+        let base = StatsBase::default();
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        assert!(stats.snapshot(&base).latency_histogram.is_empty());
+
+        stats.set_latency_buckets(Some(vec![10.0, 1.0, 5.0, f64::NAN, 5.0]));
+        // 延迟（微秒）：0.5ms、1ms、3ms、10ms、20ms
+        for latency in [500, 1000, 3000, 10_000, 20_000] {
+            stats.update_stats(0, latency, 200, RequestResult::Successful);
+        }
+
+        let d = stats.snapshot(&base);
+        let expected: HashMap<String, i64> = [("1", 2), ("5", 3), ("10", 4), ("+Inf", 5)]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        assert_eq!(d.latency_histogram, expected);
+        let value = serde_json::to_value(&d).unwrap();
+        assert_eq!(value["latencyHistogram"]["+Inf"], 5);
+        assert_eq!(serde_json::Value::from(&d), value);
+
+        // 合并时按桶相加
+        let merged = aggregate(&[&stats, &stats], &base);
+        assert_eq!(merged.latency_histogram["5"], 6);
+    }
+
+    #[test]
+    fn test_custom_results() {
+        let stats = RequestStats::new();
+        for result in [
+            RequestResult::Successful,
+            RequestResult::Successful,
+            RequestResult::Custom(1),
+            RequestResult::Custom(1),
+            RequestResult::Custom(2),
+        ] {
+            stats.update_stats(0, 1000, 200, result);
+        }
+
+        // 默认计为错误
+        let base = StatsBase::default();
+        let d = stats.snapshot(&base);
+        assert_eq!(d.error_rate, 0.6);
+        assert_eq!(d.failed_requests, 3);
+        assert_eq!(d.max_consecutive_failures, 3);
+        assert_eq!(d.proxy_breakdown["direct"].error_rate, 0.6);
+        let value = serde_json::to_value(&d).unwrap();
+        assert_eq!(value["customResults"]["1"], 2);
+        assert_eq!(value["customResults"]["2"], 1);
+        assert_eq!(serde_json::Value::from(&d), value);
+
+        stats.set_custom_results_are_errors(false);
+        let d = stats.snapshot(&base);
+        assert_eq!(d.error_rate, 0.0);
+        assert_eq!(d.failed_requests, 0);
+        assert_eq!(d.custom_results["1"], 2);
+
+        // 合并时按编号相加
+        let merged = aggregate(&[&stats, &stats], &base);
+        assert_eq!(merged.custom_results["1"], 4);
+
+        register_custom_result(200, "robotsDisallowed");
+        assert_eq!(custom_result_name(200).as_deref(), Some("robotsDisallowed"));
+        assert_eq!(custom_result_discriminant("robotsDisallowed"), Some(200));
+        assert_eq!(custom_result_name(201), None);
+        assert_eq!(custom_result_discriminant("authenticationError"), None);
+    }
+
+    #[test]
+    fn test_auto_request_frequency() {
+        let clock = testing::MockClock::new(1_700_000_000_000);
+        let stats = RequestStats::new_with_clock(clock.clock());
+        let base = StatsBase::builder().request_frequency(5).build();
+        let items: Vec<_> = (0..25)
+            .map(|_| (0, 1000, 200, RequestResult::Successful))
+            .collect();
+
+        // 默认使用 base 中的请求频率
+        stats.update_stats_batch(&items);
+        clock.advance(10_000);
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.requests_per_second, 2.5);
+        assert_eq!(serde_json::to_value(&d).unwrap()["requestFrequency"], 5);
+
+        // 开启后使用本周期的每秒请求数
+        stats.set_auto_request_frequency(true);
+        stats.update_stats_batch(&items);
+        clock.advance(10_000);
+        // 独立周期的推送目标使用的快照同样使用本周期的每秒请求数
+        assert_eq!(stats.snapshot(&base).base.request_frequency, 2);
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(serde_json::to_value(&d).unwrap()["requestFrequency"], 2);
+        assert_eq!(base.request_frequency, 5);
+    }
+
+    #[test]
+    fn test_observed_request_frequency() {
+        // 测试结束后恢复，不影响其他测试使用的全局 base
+        let previous = (
+            AUTO_REQUEST_FREQUENCY.load(Ordering::Relaxed),
+            OBSERVED_REQUEST_FREQUENCY.lock().take(),
+        );
 
-        // 构造 `Stats` 结构体
         let stats = Stats {
-            base,
-            time_period,
-            error_rate: (error_rate * 1000.0).round() / 1000.0,
-            exception_types,
-            runtime_duration,
-            total_requests: self.total_requests,
-            cache_hit_rate,            // 假设没有缓存相关数据，可以根据需要补充
-            cache_hit: self.cache_hit, // 假设没有缓存相关数据，可以根据需要补充
-            http_status_codes: self
-                .http_status_codes
-                .iter()
-                .map(|(k, v)| (k.to_string(), *v))
-                .collect(),
-            average_request_latency: (average_latency * 1000.0).round() / 1000.0,
-            hosts_ping_delay: HashMap::new(), // 假设没有主机延迟数据，可以根据需要补充
-            system_resources: get_system_resources(),
+            requests_per_second: 2.5,
+            ..Default::default()
         };
+        AUTO_REQUEST_FREQUENCY.store(false, Ordering::Relaxed);
+        record_request_frequency(&stats);
+        assert_eq!(*OBSERVED_REQUEST_FREQUENCY.lock(), None);
 
-        stats
+        // 开启后 `current_base` 使用上一个周期的每秒请求数
+        AUTO_REQUEST_FREQUENCY.store(true, Ordering::Relaxed);
+        record_request_frequency(&stats);
+        assert_eq!(*OBSERVED_REQUEST_FREQUENCY.lock(), Some(3));
+        assert_eq!(current_base().request_frequency, 3);
+
+        AUTO_REQUEST_FREQUENCY.store(previous.0, Ordering::Relaxed);
+        *OBSERVED_REQUEST_FREQUENCY.lock() = previous.1;
     }
 
-    pub fn reset(&mut self) {
-        self.base = Default::default();
+    #[test]
+    fn test_debug_snapshot() {
+        let clock = testing::MockClock::new(1_700_000_000_000);
+        let stats = RequestStats::new_with_clock(clock.clock());
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        stats.update_stats(0, 3000, 500, RequestResult::StatusCodeError(None));
+        stats.update_stats(0, 2000, 200, RequestResult::Successful);
+        clock.advance(2500);
+
+        let debug = stats.debug_snapshot();
+        assert_eq!(debug.total_requests, 3);
+        assert_eq!(debug.total_latency_us, 6000);
+        assert_eq!(debug.start_time, 1_700_000_000_000);
+        assert_eq!(debug.elapsed_ms, 2500);
+        assert_eq!(
+            format!("{debug:?}"),
+            "[since 2023-11-14 22:13, 2.5s] requests: 3  avg_latency: 2.0ms  status: 200=2 500=1"
+        );
+
+        // 不会重置统计周期
+        assert_eq!(stats.debug_snapshot(), debug);
     }
-}
 
-/// 获取系统资源数据
-pub fn get_system_resources() -> SystemResources {
-    // 创建一个 System 实例
-    let mut system = System::new_all();
+    #[test]
+    fn test_cycle_count() {
+        let clock = testing::MockClock::new(1_700_000_000_000);
+        let stats = RequestStats::new_with_clock(clock.clock());
+        let base = StatsBase::default();
+        clock.advance(1000);
 
-    // 刷新系统信息
-    system.refresh_all();
+        let snapshot = stats.snapshot(&base);
+        assert_eq!(snapshot.cycle_count, 1);
+        assert_eq!(snapshot.restarted_at, 1_700_000_000_000);
 
-    // 获取 CPU 使用率
-    let cpu_usage = format!("{:.2}%", system.global_cpu_info().cpu_usage());
+        for i in 1..=3 {
+            stats.update_stats(0, 1000, 200, RequestResult::Successful);
+            let d = stats.to_stats_and_reset(&base, None);
+            assert_eq!(d.cycle_count, i);
+            // 重置统计周期不影响初始化时间
+            assert_eq!(d.restarted_at, 1_700_000_000_000);
+        }
 
-    // 获取内存使用情况（单位从 KB 转换为 MB）
-    let total_memory = system.total_memory() / (1024 * 1024); // 总内存（MB）
-    let used_memory = system.used_memory() / (1024 * 1024); // 已使用内存（MB）
+        let value = serde_json::Value::from(&stats.snapshot(&base));
+        assert_eq!(value["cycleCount"], 4);
+        assert_eq!(value["restartedAt"], 1_700_000_000_000i64);
 
-    let memory_usage = Usage {
-        used: used_memory,
-        total: total_memory,
-    };
+        // 新的统计实例重新计数
+        assert_eq!(RequestStats::new().snapshot(&base).cycle_count, 1);
+    }
 
-    // 获取所有磁盘的使用情况（单位从字节转换为 MB）
-    let mut total_disk_space = 0;
-    let mut total_disk_used = 0;
+    #[test]
+    fn test_noop_stats() {
+        let stats = RequestStats::noop();
+        assert!(stats.is_noop());
+        assert!(!RequestStats::new().is_noop());
 
-    for disk in system.disks() {
-        total_disk_space += disk.total_space() / (1024 * 1024); // 累加磁盘总空间（MB）
-        total_disk_used += (disk.total_space() - disk.available_space()) / (1024 * 1024);
-        // 累加磁盘已使用空间（MB）
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        stats.update_stats_batch(&[(0, 1000, 500, RequestResult::TimeoutError(None))]);
+        stats.update_stats_items(&[StatsItem::new(
+            0,
+            1000,
+            500,
+            RequestResult::TimeoutError(None),
+        )]);
+        let base = StatsBase::default();
+        let d = stats.clone().snapshot(&base);
+        assert_eq!(d.total_requests, 0);
+        assert!(d.http_status_codes.is_empty());
     }
 
-    let disk_usage = Usage {
-        used: total_disk_used,
-        total: total_disk_space,
-    };
+    #[test]
+    fn test_successful_and_failed_requests() {
+        let stats = RequestStats::new();
+        for result in [
+            RequestResult::Successful,
+            RequestResult::SuccessfulAndCache,
+            RequestResult::ProxyError(None),
+            RequestResult::CaptchaDetected(None),
+            RequestResult::TimeoutError(None),
+        ] {
+            stats.update_stats(0, 1000, 0, result);
+        }
 
-    // 构造 SystemResources
-    SystemResources {
-        cpu_usage,
-        memory_usage,
-        disk_usage,
+        let base = StatsBase::default();
+        let d = stats.snapshot(&base);
+        assert_eq!(d.successful_requests, 2);
+        assert_eq!(d.failed_requests, 3);
+        let value = serde_json::to_value(&d).unwrap();
+        assert_eq!(value["successfulRequests"], 2);
+        assert_eq!(value["failedRequests"], 3);
     }
-}
 
-/// 测试tcp 连接耗时; 返回连接的耗时
-pub fn run_test_tcp(addr: &str, port: u16, ping_timeout: Duration) -> Result<u64> {
-    let sk = match addr.parse::<SocketAddr>() {
-        Ok(sock) => sock,
-        Err(_) => {
-            let resolve_ip = IpAddr::from_str(addr)?;
+    #[test]
+    fn test_consecutive_failures() {
+        let stats = RequestStats::new();
+        let timeout = || RequestResult::TimeoutError(None);
+        for result in [timeout(), timeout(), RequestResult::Successful, timeout()] {
+            stats.update_stats(0, 1000, 0, result);
+        }
+        assert_eq!(stats.current_consecutive_failures(), 1);
 
-            SocketAddr::new(resolve_ip, port)
+        for _ in 0..3 {
+            stats.update_stats(0, 1000, 0, timeout());
         }
-    };
-    let start_time = Instant::now();
-    let _ = TcpStream::connect_timeout(&sk, ping_timeout).map_err(|err| {
-        anyhow!(
-            "当前连接时长：{} ms;错误信息：{err}",
-            start_time.elapsed().as_millis()
+        let base = StatsBase::default();
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.max_consecutive_failures, 4);
+        assert_eq!(
+            serde_json::to_value(&d).unwrap()["maxConsecutiveFailures"],
+            4
+        );
+
+        // 最大值按周期统计，当前的连续失败次数跨周期保留
+        assert_eq!(stats.current_consecutive_failures(), 4);
+        stats.update_stats(0, 1000, 0, timeout());
+        assert_eq!(stats.snapshot(&base).max_consecutive_failures, 5);
+        stats.update_stats(0, 1000, 200, RequestResult::SuccessfulAndCache);
+        assert_eq!(stats.current_consecutive_failures(), 0);
+
+        // 告警使用周期内的最大值，周期结束时已经恢复也会告警
+        let d = stats.to_stats_and_reset(&base, None).into_owned();
+        let config = RequestStatsConfig {
+            consecutive_failure_alarm: Some(4),
+            ..Default::default()
+        };
+        let (rule, value) = consecutive_failure_alarm(&config, &d).unwrap();
+        assert_eq!(rule.field, alert::AlertField::ConsecutiveFailures);
+        assert_eq!(rule.threshold, 4.0);
+        assert_eq!(value, 5.0);
+        let config = RequestStatsConfig {
+            consecutive_failure_alarm: Some(5),
+            ..Default::default()
+        };
+        assert!(consecutive_failure_alarm(&config, &d).is_none());
+        assert!(consecutive_failure_alarm(&RequestStatsConfig::default(), &d).is_none());
+    }
+
+    #[test]
+    fn test_update_stats_invalid_latency() {
+        let stats = RequestStats::new();
+        // 响应时间早于请求时间
+        stats.update_stats(2000, 1000, 200, RequestResult::Successful);
+        // 延迟溢出
+        stats.update_stats(i64::MIN, i64::MAX, 200, RequestResult::Successful);
+        stats.update_stats(i64::MIN, i64::MAX, 200, RequestResult::Successful);
+
+        let base = StatsBase::default();
+        let d = stats.snapshot(&base);
+        assert_eq!(d.total_requests, 3);
+        assert!(d.average_request_latency.is_finite());
+        assert!(d.average_request_latency >= 0.0);
+
+        // 流量和 DNS 耗时溢出
+        let info = RequestInfo {
+            request_bytes: u64::MAX,
+            response_bytes: u64::MAX,
+            dns_duration_us: Some(u64::MAX),
+            ..Default::default()
+        };
+        stats.update_stats_with(0, 1000, 200, RequestResult::Successful, &info);
+        stats.update_stats_with(0, 1000, 200, RequestResult::Successful, &info);
+        let values = stats.values();
+        assert_eq!(values.total_request_bytes, u64::MAX);
+        assert_eq!(values.total_response_bytes, u64::MAX);
+        assert_eq!(values.total_dns_latency_us, i64::MAX);
+
+        // 合并时同样不溢出
+        let merged = values.clone() + values;
+        assert_eq!(merged.total_request_bytes, u64::MAX);
+        assert_eq!(merged.total_dns_latency_us, i64::MAX);
+        assert_eq!(merged.total_latency, i64::MAX);
+    }
+
+    #[test]
+    fn test_subscribe_stats() {
+        let mut receiver = subscribe_stats();
+        let base = StatsBase::builder()
+            .server_name("test_subscribe_stats")
+            .build();
+        send_stats(&base, None);
+
+        // 其他测试也可能推送统计信息，按 base 找到这次推送的数据
+        let stats = std::iter::from_fn(|| receiver.try_recv().ok())
+            .find(|stats| stats.base.server_name == "test_subscribe_stats");
+        assert!(stats.is_some());
+    }
+
+    #[test]
+    fn test_empty_period_average_latency() {
+        let stats = RequestStats::new();
+        let base = StatsBase::default();
+        let d = stats.snapshot(&base);
+        assert_eq!(d.total_requests, 0);
+        assert_eq!(d.average_request_latency, 0.0);
+        assert_eq!(
+            serde_json::to_value(&d).unwrap()["averageRequestLatency"],
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_queue_delay() {
+        let stats = RequestStats::new();
+        stats.update_stats_with(
+            0,
+            2000,
+            200,
+            RequestResult::Successful,
+            &RequestInfo {
+                queue_delay_us: Some(30_000),
+                ..Default::default()
+            },
+        );
+        stats.update_stats_with(
+            0,
+            4000,
+            200,
+            RequestResult::Successful,
+            &RequestInfo {
+                queue_delay_us: Some(10_000),
+                ..Default::default()
+            },
+        );
+        // 没有经过限速队列的请求不计入平均等待耗时
+        stats.update_stats(0, 3000, 200, RequestResult::Successful);
+
+        let base = get_base();
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.avg_queue_delay_ms, 20.0);
+        // 平均请求延迟只包含网络耗时
+        assert_eq!(d.average_request_latency, 3.0);
+        assert_eq!(serde_json::Value::from(&d)["avgQueueDelayMs"], 20.0);
+
+        // 没有统计时不输出
+        stats.update_stats(0, 3000, 200, RequestResult::Successful);
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.avg_queue_delay_ms, 0.0);
+        assert!(serde_json::Value::from(&d).get("avgQueueDelayMs").is_none());
+        assert!(serde_json::to_value(&d)
+            .unwrap()
+            .get("avgQueueDelayMs")
+            .is_none());
+    }
+
+    #[test]
+    fn test_connection_reuse_rate() {
+        let stats = RequestStats::new();
+        let base = get_base();
+        // 没有传入连接复用信息时为 0
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        assert_eq!(stats.snapshot(&base).connection_reuse_rate, 0.0);
+
+        for _ in 0..3 {
+            stats.update_stats_with(
+                0,
+                1000,
+                200,
+                RequestResult::Successful,
+                &RequestInfo {
+                    connection_reused: true,
+                    ..Default::default()
+                },
+            );
+        }
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.connection_reuse_rate, 0.75);
+        assert_eq!(serde_json::Value::from(&d)["connectionReuseRate"], 0.75);
+
+        // 新的统计周期重新计算
+        assert_eq!(stats.snapshot(&base).connection_reuse_rate, 0.0);
+    }
+
+    #[test]
+    fn test_callback_cached_values() {
+        let cache = Mutex::new(None);
+        let fail = Arc::new(AtomicBool::new(false));
+
+        let flag = fail.clone();
+        let get_base: GetBaseCall = Arc::new(move || {
+            assert!(!flag.load(Ordering::Relaxed), "base 不可用");
+            StatsBase::builder().server_name("server-1").build()
+        });
+        // 没有缓存时使用默认值
+        fail.store(true, Ordering::Relaxed);
+        assert_eq!(
+            base_or_cached(&get_base, &cache),
+            (StatsBase::default(), true)
+        );
+        fail.store(false, Ordering::Relaxed);
+        let (base, stale) = base_or_cached(&get_base, &cache);
+        assert_eq!((base.server_name.as_str(), stale), ("server-1", false));
+        fail.store(true, Ordering::Relaxed);
+        let (base, stale) = base_or_cached(&get_base, &cache);
+        assert_eq!((base.server_name.as_str(), stale), ("server-1", true));
+
+        let cache = Mutex::new(None);
+        let flag = fail.clone();
+        let get_hosts: GetHostsCall = Arc::new(move || match flag.load(Ordering::Relaxed) {
+            true => Err(anyhow!("hosts 服务不可用")),
+            false => Ok(vec!["example.com".to_string()]),
+        });
+        assert_eq!(hosts_or_cached(&get_hosts, &cache), None);
+        fail.store(false, Ordering::Relaxed);
+        assert_eq!(
+            hosts_or_cached(&get_hosts, &cache),
+            Some((vec!["example.com".to_string()], false))
+        );
+        fail.store(true, Ordering::Relaxed);
+        assert_eq!(
+            hosts_or_cached(&get_hosts, &cache),
+            Some((vec!["example.com".to_string()], true))
+        );
+
+        // 使用缓存的值时推送的数据中标记 `stale`
+        let stats = Stats {
+            stale: true,
+            ..Default::default()
+        };
+        assert_eq!(serde_json::to_value(&stats).unwrap()["stale"], true);
+        assert_eq!(serde_json::Value::from(&stats)["stale"], true);
+        assert!(serde_json::to_value(Stats::default())
+            .unwrap()
+            .get("stale")
+            .is_none());
+    }
+
+    #[test]
+    fn test_host_eviction() {
+        let mut eviction = HostEviction::new(2, Some(Duration::from_secs(60)));
+        let hosts = vec!["a.com".to_string(), "b.com".to_string()];
+        let ping = |lost_a: u8| -> HashMap<String, HostPingStats> {
+            [
+                (
+                    "a.com".to_string(),
+                    HostPingStats {
+                        lost: lost_a,
+                        ..Default::default()
+                    },
+                ),
+                ("b.com".to_string(), HostPingStats::default()),
+            ]
+            .into()
+        };
+        let start = Instant::now();
+
+        // 部分测试失败不计入连续失败
+        eviction.record(&ping(1), 3, start);
+        eviction.record(&ping(3), 3, start);
+        assert_eq!(eviction.filter(hosts.clone(), start), hosts);
+        eviction.record(&ping(3), 3, start);
+        assert_eq!(eviction.filter(hosts.clone(), start), ["b.com"]);
+
+        // 到了重新测试时间后重新测试，再次失败时立即剔除
+        let retry = start + Duration::from_secs(60);
+        assert_eq!(eviction.filter(hosts.clone(), retry), hosts);
+        eviction.record(&ping(3), 3, retry);
+        assert_eq!(eviction.filter(hosts.clone(), retry), ["b.com"]);
+
+        // 测试成功后清零
+        let retry = retry + Duration::from_secs(60);
+        assert_eq!(eviction.filter(hosts.clone(), retry), hosts);
+        eviction.record(&ping(0), 3, retry);
+        eviction.record(&ping(3), 3, retry);
+        assert_eq!(eviction.filter(hosts.clone(), retry), hosts);
+
+        // 不设置重新测试时间时一直剔除
+        let mut eviction = HostEviction::new(1, None);
+        eviction.record(&ping(3), 3, start);
+        let later = start + Duration::from_secs(3600);
+        assert_eq!(eviction.filter(hosts.clone(), later), ["b.com"]);
+
+        let config = RequestStatsConfig::from_toml(
+            "target = []\nreporting_cycle = \"60s\"\nevict_failing_hosts_after = 5\nevict_retry_after = \"10m\"",
         )
-    })?;
-    let elapsed_time = start_time.elapsed();
-    Ok(elapsed_time.as_micros() as u64)
-}
+        .unwrap();
+        assert_eq!(config.evict_failing_hosts_after, Some(5));
+        assert_eq!(config.evict_retry_after, Some(Duration::from_secs(600)));
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::{
-        get_system_resources, init_spider_vars, send_stats, RequestStatsConfig, StatsBase, GET_BASE,
-    };
-    use anyhow::Result;
-    use std::thread;
-    use std::time::Duration;
+    #[test]
+    fn test_update_stats_batch() {
+        let stats = RequestStats::new();
+        stats.update_stats_batch(&[
+            (0, 10, 200, RequestResult::Successful),
+            (0, 20, 500, RequestResult::StatusCodeError(None)),
+        ]);
+        let base = get_base();
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.total_requests, 2);
+        assert_eq!(d.exception_types.status_code_error, 1);
+        assert_eq!(d.http_status_codes["200"], 1);
+
+        stats.update_stats_items(&[
+            StatsItem::new(0, 10, 200, RequestResult::Successful).with_info(RequestInfo {
+                request_bytes: 100,
+                response_bytes: 2000,
+                proxy_id: Some("proxy-a"),
+                dns_duration_us: Some(2000),
+                ..Default::default()
+            }),
+            StatsItem::new(0, 20, 200, RequestResult::SuccessfulAndCache).with_info(RequestInfo {
+                retry_count: 1,
+                request_bytes: 100,
+                response_bytes: 1000,
+                proxy_id: Some("proxy-a"),
+                ..Default::default()
+            }),
+            StatsItem::new(0, 30, 500, RequestResult::StatusCodeError(None)).with_info(
+                RequestInfo {
+                    retry_count: 2,
+                    request_bytes: 100,
+                    proxy_id: Some("proxy-b"),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        stats.update_stats(0, 40, 200, RequestResult::Successful);
+
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.total_requests, 4);
+        assert_eq!(d.cache_hit, 1);
+        assert_eq!(d.exception_types.status_code_error, 1);
+        assert_eq!(d.http_status_codes["200"], 3);
+        assert_eq!(d.http_status_codes["500"], 1);
+        assert_eq!(d.retry_stats.total_retries, 3);
+        assert_eq!(d.retry_stats.retry_success_rate, 0.5);
+        assert_eq!(d.retry_stats.retry_rate, 0.5);
+        assert_eq!(d.total_request_bytes, 300);
+        assert_eq!(d.total_response_bytes, 3000);
+        assert_eq!(d.avg_response_bytes, 750.0);
+        assert_eq!(d.proxy_breakdown["proxy-a"].requests, 2);
+        assert_eq!(d.proxy_breakdown["proxy-a"].avg_latency_ms, 0.015);
+        assert_eq!(d.proxy_breakdown["proxy-b"].error_rate, 1.0);
+        assert_eq!(d.proxy_breakdown["direct"].requests, 1);
+        assert_eq!(d.avg_dns_latency_ms, 2.0);
+
+        // 没有使用流量统计时不输出
+        stats.update_stats(0, 40, 200, RequestResult::Successful);
+        let value = serde_json::to_value(stats.to_stats_and_reset(&base, None)).unwrap();
+        assert!(value.get("totalRequestBytes").is_none());
+        assert!(value.get("avgResponseBytes").is_none());
+    }
+
+    #[test]
+    fn test_proxy_max_labels() {
+        let stats = RequestStats::new();
+        stats.set_max_labels(1);
+        stats.update_stats_items(&[
+            StatsItem::new(0, 10, 200, RequestResult::Successful).with_info(RequestInfo {
+                proxy_id: Some("proxy-a"),
+                ..Default::default()
+            }),
+            StatsItem::new(0, 10, 200, RequestResult::Successful).with_info(RequestInfo {
+                proxy_id: Some("proxy-b"),
+                ..Default::default()
+            }),
+            StatsItem::new(0, 10, 200, RequestResult::Successful).with_info(RequestInfo {
+                proxy_id: Some("proxy-a"),
+                ..Default::default()
+            }),
+        ]);
+
+        let base = get_base();
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.proxy_breakdown.len(), 2);
+        assert_eq!(d.proxy_breakdown["proxy-a"].requests, 2);
+        assert_eq!(d.proxy_breakdown["other"].requests, 1);
+    }
+
+    #[cfg(feature = "async-stats")]
+    #[tokio::test]
+    async fn test_update_stats_async() {
+        let stats = RequestStats::new();
+        stats
+            .update_stats_async(0, 10, 200, RequestResult::Successful)
+            .await;
+        // 异步上下文中调用同步方法不能 panic
+        stats.update_stats(0, 10, 200, RequestResult::TimeoutError(None));
+
+        let base = get_base();
+        let d = stats.to_stats_and_reset_async(&base, None).await;
+        assert_eq!(d.total_requests, 2);
+        assert_eq!(d.exception_types.timeout_error, 1);
+    }
+
+    #[cfg(feature = "async-stats")]
+    #[test]
+    fn test_sync_lock_contended() {
+        // 其他线程持有锁时，同步方法等待锁释放而不是 panic
+        let update_while_locked = |stats: RequestStats| {
+            let guard = stats.inner.clone().try_lock_owned().unwrap();
+            let holder = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                drop(guard);
+            });
+            stats.update_stats(0, 10, 200, RequestResult::Successful);
+            holder.join().unwrap();
+        };
+
+        let stats = RequestStats::new();
+        update_while_locked(stats.clone());
+        tokio::runtime::Builder::new_multi_thread()
+            .build()
+            .unwrap()
+            .block_on(async { update_while_locked(stats.clone()) });
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async { update_while_locked(stats.clone()) });
+        assert_eq!(stats.snapshot(&get_base()).total_requests, 3);
+    }
+
+    #[test]
+    fn test_skip_empty_cycles() {
+        let stats = RequestStats::new();
+        let base = get_base();
+        assert!(stats.to_stats_and_reset_if_nonempty(&base, None).is_none());
+
+        stats.update_stats(0, 10, 200, RequestResult::Successful);
+        let d = stats.to_stats_and_reset_if_nonempty(&base, None).unwrap();
+        assert_eq!(d.total_requests, 1);
+        assert!(stats.to_stats_and_reset_if_nonempty(&base, None).is_none());
+    }
+
+    #[test]
+    fn test_runtime_threads() {
+        assert_eq!(runtime_threads(None), 3);
+        assert_eq!(runtime_threads(Some("8")), 8);
+        assert_eq!(runtime_threads(Some("0")), 3);
+        assert_eq!(runtime_threads(Some("abc")), 3);
+    }
+
+    #[test]
+    fn test_tz_offset_hours() {
+        assert_eq!(tz_offset_hours(None), 0);
+        assert_eq!(tz_offset_hours(Some("8")), 8);
+        assert_eq!(tz_offset_hours(Some(" -5 ")), -5);
+        assert_eq!(tz_offset_hours(Some("24")), 0);
+        assert_eq!(tz_offset_hours(Some("+08:00")), 0);
+    }
+
+    #[test]
+    fn test_random_jitter() {
+        assert_eq!(random_jitter(Duration::ZERO), Duration::ZERO);
+
+        let jitter = Duration::from_secs(5);
+        for _ in 0..100 {
+            assert!(random_jitter(jitter) < jitter);
+        }
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let path = std::env::temp_dir().join(format!("stats_snapshot_{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let stats = RequestStats::with_snapshot_path(&path);
+        stats.update_stats(0, 100, 200, RequestResult::Successful);
+        stats.update_stats(
+            0,
+            300,
+            0,
+            RequestResult::TimeoutError(Some("read timeout".into())),
+        );
+        // 更新统计数据时不写入快照
+        assert!(!path.exists());
+        stats.persist_snapshot();
+        assert!(path.exists());
+
+        // 模拟进程崩溃后重新创建实例
+        let restored = RequestStats::with_snapshot_path(&path);
+        let base = get_base();
+        let d = restored.snapshot(&base);
+        assert_eq!(d.total_requests, 2);
+        assert_eq!(d.exception_types.timeout_error, 1);
+        assert_eq!(d.http_status_codes.get("200"), Some(&1));
+        assert_eq!(
+            d.recent_errors["timeoutError"],
+            vec!["read timeout".to_string()]
+        );
+
+        // 上报之后快照中不再保留已上报的数据
+        restored.to_stats_and_reset(&base, None);
+        let d = RequestStats::with_snapshot_path(&path).snapshot(&base);
+        assert_eq!(d.total_requests, 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
 
     #[test]
     fn it_works() {
+        let _guard = GLOBAL_STATE_TEST_LOCK.lock();
         // 1000XXXUSDT，10000XXXUSDT，1000000XXXUSDT 1MXXXUSDT
 
         // 获取系统资源数据
@@ -484,7 +4853,7 @@ mod tests {
         println!("系统资源数据: {:?}", system_resources);
 
         // 打印详细信息
-        println!("CPU 使用率: {}", system_resources.cpu_usage);
+        println!("CPU 使用率: {:.2}%", system_resources.cpu_usage);
         println!(
             "内存使用: 已使用 {} MB / 总计 {} MB",
             system_resources.memory_usage.used, system_resources.memory_usage.total
@@ -494,34 +4863,103 @@ mod tests {
             system_resources.disk_usage.used, system_resources.disk_usage.total
         );
 
+        // 空实现之后初始化时恢复统计
+        init_noop_stats();
+        assert!(SPIDER_STATS.is_noop());
         init_spider_vars(
             RequestStatsConfig {
                 target: vec!["ws://35.79.121.103:5003".to_string()],
                 reporting_cycle: Duration::from_secs(10000),
                 host_test_port: 0,
+                ..Default::default()
             },
             Box::new(get_base),
-            // Box::new(|| Ok(vec!["ssss".to_string()])),
-            Box::new(get_hosts),
-            None,
+            // 不测试 hosts 延迟时端口可以为 0
+            Box::new(|| Ok(vec![])),
+            vec![],
         )
         .unwrap();
+        assert!(!SPIDER_STATS.is_noop());
+        // 全局统计会被其他测试并发重置，使用本地实例检查清除标记之后恢复统计
+        let stats = RequestStats::noop();
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        assert_eq!(stats.snapshot(&get_base()).total_requests, 0);
+        stats.noop.store(false, Ordering::Relaxed);
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        assert_eq!(stats.snapshot(&get_base()).total_requests, 1);
 
         thread::sleep(Duration::from_secs(5));
-        let base = GET_BASE();
+        let base = current_base();
 
         send_stats(&base, None);
+
+        // 重复初始化返回错误，需要使用 reinit_spider_vars
+        assert!(init_spider_vars(
+            RequestStatsConfig::default(),
+            Box::new(get_base),
+            Box::new(get_hosts),
+            vec![]
+        )
+        .is_err());
+
+        // 配置不合法时不替换当前的推送目标
+        let Err(err) = reinit_spider_vars(
+            RequestStatsConfig {
+                target: vec![],
+                reporting_cycle: Duration::ZERO,
+                ..Default::default()
+            },
+            Box::new(get_base),
+            Box::new(get_hosts),
+            vec![],
+            None,
+        ) else {
+            panic!("配置不合法时应该返回错误");
+        };
+        assert!(err.to_string().contains("target 不能为空"));
+        assert!(err.to_string().contains("reporting_cycle 必须大于 0"));
+        let push = SPIDER_STATS_PUSH.lock().clone().unwrap();
+        assert_eq!(push.target_urls(), ["ws://35.79.121.103:5003"]);
+
+        let handle = reinit_spider_vars(
+            RequestStatsConfig {
+                target: vec!["ws://127.0.0.1:5003".to_string()],
+                reporting_cycle: Duration::from_secs(10000),
+                ..Default::default()
+            },
+            Box::new(get_base),
+            Box::new(get_hosts),
+            vec![],
+            None,
+        )
+        .unwrap();
+        let push = SPIDER_STATS_PUSH.lock().clone().unwrap();
+        assert_eq!(push.target_urls(), ["ws://127.0.0.1:5003"]);
+        // 原来的上报任务退出不影响新的上报任务的状态
+        let reporting_task_ok = || {
+            health::health_check()
+                .checks
+                .iter()
+                .any(|check| check.name == "reporting_task" && check.ok)
+        };
+        assert!(reporting_task_ok());
+        handle.shutdown().unwrap();
+        assert!(!reporting_task_ok());
+
+        shutdown().unwrap();
+        // 重复调用不会报错
+        shutdown().unwrap();
     }
 
     fn get_base() -> StatsBase {
-        let base = StatsBase {
+        StatsBase {
             server_name: "".to_string(),
             scraper_name: "".to_string(),
             project_code: "".to_string(),
             scraper_type: "".to_string(),
             request_frequency: 0,
-        };
-        base
+            extra: HashMap::new(),
+        }
     }
     fn get_hosts() -> Result<Vec<String>> {
         Ok(vec!["ssss".to_string()])
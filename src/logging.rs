@@ -0,0 +1,106 @@
+// 请求日志
+// 根据 `OutRespInfo` 输出请求和响应信息
+use crate::OutRespInfo;
+use std::collections::HashMap;
+use tracing::info;
+
+// 日志中 body 的最大字节数
+const MAX_BODY_BYTES: usize = 1024;
+
+/// 根据 `mode` 输出请求日志
+pub fn log_request(
+    url: &str,
+    status_code: u16,
+    headers: Option<&HashMap<String, String>>,
+    body: Option<&str>,
+    mode: &OutRespInfo,
+) {
+    if let Some(msg) = format_request(url, status_code, headers, body, mode) {
+        info!("{}", msg);
+    }
+}
+
+/// 拼装请求日志；`OutRespInfo::None` 时返回 None
+fn format_request(
+    url: &str,
+    status_code: u16,
+    headers: Option<&HashMap<String, String>>,
+    body: Option<&str>,
+    mode: &OutRespInfo,
+) -> Option<String> {
+    if *mode == OutRespInfo::None {
+        return None;
+    }
+
+    let mut msg = format!("{url} => {status_code}");
+
+    if matches!(mode, OutRespInfo::Head | OutRespInfo::Body) {
+        if let Some(headers) = headers {
+            // 按名称排序，保证输出顺序稳定
+            let mut headers: Vec<_> = headers.iter().collect();
+            headers.sort();
+            for (key, value) in headers {
+                msg.push_str(&format!("\n{key}: {value}"));
+            }
+        }
+    }
+
+    if *mode == OutRespInfo::Body {
+        if let Some(body) = body {
+            msg.push_str("\n\n");
+            msg.push_str(truncate(body, MAX_BODY_BYTES));
+            if body.len() > MAX_BODY_BYTES {
+                msg.push_str("...");
+            }
+        }
+    }
+
+    Some(msg)
+}
+
+/// 截断到不超过 `max_bytes` 字节，不会截断多字节字符
+fn truncate(s: &str, max_bytes: usize) -> &str {
+    if s.len() <= max_bytes {
+        return s;
+    }
+
+    let mut end = max_bytes;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_request() {
+        let headers = HashMap::from([
+            ("server".to_string(), "nginx".to_string()),
+            ("content-type".to_string(), "text/html".to_string()),
+        ]);
+        let format = |mode| format_request("https://a.com", 200, Some(&headers), Some("ok"), &mode);
+
+        assert_eq!(format(OutRespInfo::None), None);
+        assert_eq!(format(OutRespInfo::Url).unwrap(), "https://a.com => 200");
+        assert_eq!(
+            format(OutRespInfo::Head).unwrap(),
+            "https://a.com => 200\ncontent-type: text/html\nserver: nginx"
+        );
+        assert_eq!(
+            format(OutRespInfo::Body).unwrap(),
+            "https://a.com => 200\ncontent-type: text/html\nserver: nginx\n\nok"
+        );
+    }
+
+    #[test]
+    fn test_truncate_body() {
+        let body = "中".repeat(500);
+        let msg = format_request("u", 200, None, Some(&body), &OutRespInfo::Body).unwrap();
+        let logged = msg.strip_prefix("u => 200\n\n").unwrap();
+        assert!(logged.ends_with("..."));
+        assert_eq!(logged.len(), 1023 + 3);
+    }
+}
@@ -0,0 +1,115 @@
+// 统计中间件
+// 每次更新统计数据时按添加顺序调用，用于转发统计数据、修改请求结果等
+use crate::{InnerStatsVal, RequestResult};
+use tracing::debug;
+
+/// 统计中间件；所有方法都有默认实现，按需实现
+/// 在统计数据的锁内调用，实现中不要执行耗时操作，也不要再更新同一个 `RequestStats`
+pub trait StatsMiddleware {
+    /// 更新统计数据之前调用；`result` 为 `transform` 之前的请求结果
+    fn before_update(
+        &self,
+        _request_time: i64,
+        _response_time: i64,
+        _status_code: u16,
+        _result: &RequestResult,
+    ) {
+    }
+
+    /// 修改请求结果；多个中间件依次修改
+    fn transform(&self, result: RequestResult) -> RequestResult {
+        result
+    }
+
+    /// 更新统计数据之后调用；参数为当前周期的累计值
+    fn after_update(&self, _snapshot: &InnerStatsVal) {}
+}
+
+/// 输出每次更新的 debug 日志
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingMiddleware;
+
+impl StatsMiddleware for LoggingMiddleware {
+    fn before_update(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: &RequestResult,
+    ) {
+        debug!(
+            "更新统计数据：状态码 {}，耗时 {} us，结果 {:?}",
+            status_code,
+            response_time - request_time,
+            result
+        );
+    }
+
+    fn after_update(&self, snapshot: &InnerStatsVal) {
+        debug!(
+            "当前周期请求数 {}，成功请求数 {}",
+            snapshot.total_requests, snapshot.successful_requests
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RequestStats, StatsBase, StatsItem};
+    use std::sync::atomic::{AtomicI64, Ordering};
+    use std::sync::Arc;
+
+    // 验证码不计入错误，并记录最后一次更新后的请求数
+    struct IgnoreCaptcha {
+        before: Arc<AtomicI64>,
+        total: Arc<AtomicI64>,
+    }
+
+    impl StatsMiddleware for IgnoreCaptcha {
+        fn before_update(&self, _: i64, _: i64, _: u16, _: &RequestResult) {
+            self.before.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn transform(&self, result: RequestResult) -> RequestResult {
+            match result {
+                RequestResult::CaptchaDetected(_) => RequestResult::Successful,
+                result => result,
+            }
+        }
+
+        fn after_update(&self, snapshot: &InnerStatsVal) {
+            self.total.store(snapshot.total_requests, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_middleware() {
+        let before = Arc::new(AtomicI64::new(0));
+        let total = Arc::new(AtomicI64::new(0));
+
+        let stats = RequestStats::new();
+        stats.add_middleware(LoggingMiddleware);
+        stats.add_middleware(IgnoreCaptcha {
+            before: before.clone(),
+            total: total.clone(),
+        });
+
+        stats.update_stats(0, 10, 200, RequestResult::Successful);
+        stats.update_stats_items(&[StatsItem::new(
+            0,
+            10,
+            200,
+            RequestResult::CaptchaDetected(None),
+        )]);
+
+        assert_eq!(before.load(Ordering::Relaxed), 2);
+        assert_eq!(total.load(Ordering::Relaxed), 2);
+
+        let base = StatsBase::default();
+        let d = stats.snapshot(&base);
+        assert_eq!(d.total_requests, 2);
+        assert_eq!(d.exception_types.captcha_detected, 0);
+        assert_eq!(d.error_rate, 0.0);
+    }
+}
@@ -0,0 +1,8 @@
+//! 常用的类型和函数；使用 `use stats::prelude::*` 一次导入
+//!
+//! 只包含初始化、更新和推送统计数据需要的接口，不包含内部使用的类型
+pub use crate::{
+    get_system_resources, init_spider_vars, send_stats, subscribe_stats, update_stats,
+    update_stats_with, Global, OutRespInfo, RequestInfo, RequestResult, RequestStats,
+    RequestStatsConfig, Stats, StatsBase,
+};
@@ -1,10 +1,19 @@
 // 推送爬虫统计信息
 // 使用广播的版本；
 use super::websocket::connect_to_ws_with_timeout;
-use crate::GLOBAL_RUNTIME;
+use crate::{
+    OwnedStats, PushCompression, PushEnvelope, GLOBAL_RUNTIME, PUSH_COMPRESSION, USE_ENVELOPE,
+};
 use anyhow::Result;
 use futures_util::{SinkExt, StreamExt, TryFutureExt};
-use tokio::sync::broadcast::{channel, Receiver, Sender};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast::{
+    channel,
+    error::{RecvError, TryRecvError},
+    Receiver, Sender,
+};
 use tokio::{
     net::TcpStream,
     time::{Duration, Instant},
@@ -16,28 +25,97 @@ use tracing::{error, info};
 const PING_KEEP_ALIVE: u64 = 30_000;
 // 重连 间隔 ms
 const RECONNECTION_DELAY: u64 = 3_000;
+// 推送通道的容量；重连期间最多保留同样多的数据
+const PUSH_CHANNEL_CAPACITY: usize = 10;
+
+/// 统计信息的推送方式
+pub trait PushHandle {
+    /// 推送统计信息
+    fn send(&self, stats: Arc<OwnedStats>) -> Result<()>;
+
+    /// 推送目标地址
+    fn target_urls(&self) -> &[String];
+
+    /// 推送通道是否已经关闭；关闭后推送的数据不会再发送到任何目标
+    fn is_closed(&self) -> bool {
+        false
+    }
+}
+
+// 丢弃所有统计信息；用于 `init_noop_stats`
+pub struct NoopPushHandle;
+
+impl PushHandle for NoopPushHandle {
+    fn send(&self, _stats: Arc<OwnedStats>) -> Result<()> {
+        Ok(())
+    }
+
+    fn target_urls(&self) -> &[String] {
+        &[]
+    }
+}
+
+// 使用广播通道推送到所有 websocket 目标
+// 接收端处理太慢时会丢失数据，推送时写入递增的序号，接收端可以发现丢失的周期
+pub struct BroadcastPushHandle {
+    sender: Sender<Arc<OwnedStats>>,
+    targets: Vec<String>,
+    // 上一次推送的序号
+    push_sequence: AtomicU64,
+}
+
+impl PushHandle for BroadcastPushHandle {
+    fn send(&self, stats: Arc<OwnedStats>) -> Result<()> {
+        let sequence = self.push_sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut stats = Arc::unwrap_or_clone(stats);
+        stats.sequence_number = sequence;
+        self.sender.send(Arc::new(stats))?;
+        Ok(())
+    }
+
+    fn target_urls(&self) -> &[String] {
+        &self.targets
+    }
 
-pub fn load_broadcast_chan(push_target: Vec<String>) -> Sender<String> {
+    fn is_closed(&self) -> bool {
+        // 每个推送目标持有一个接收端，全部退出时通道关闭；没有推送目标时只输出日志
+        !self.targets.is_empty() && self.sender.receiver_count() == 0
+    }
+}
+
+pub fn load_push_handle(push_target: Vec<String>) -> Box<dyn PushHandle + Send + Sync> {
     println!("初始化消息转推");
-    let (create_order_sender, _) = channel(10);
+    let (create_order_sender, _) = channel(PUSH_CHANNEL_CAPACITY);
 
     if !push_target.is_empty() {
-        let s = create_order_sender.clone();
-        GLOBAL_RUNTIME.spawn(init_websocket(push_target, s));
+        // 在这里订阅，保证返回之后推送通道就有接收端
+        let receivers = push_target
+            .iter()
+            .map(|url| (url.clone(), create_order_sender.subscribe()))
+            .collect();
+        GLOBAL_RUNTIME.spawn(init_websocket(receivers));
     }
 
-    create_order_sender
+    Box::new(BroadcastPushHandle {
+        sender: create_order_sender,
+        targets: push_target,
+        push_sequence: AtomicU64::new(0),
+    })
 }
 
-async fn init_websocket(push_targets: Vec<String>, msg_chan: Sender<String>) {
-    for push_url in push_targets {
-        let s = msg_chan.subscribe();
+async fn init_websocket(receivers: Vec<(String, Receiver<Arc<OwnedStats>>)>) {
+    for (push_url, s) in receivers {
         tokio::spawn(push_loop(push_url, s));
     }
 }
 
-async fn push_loop(push_url: String, mut event_receiver: Receiver<String>) {
+async fn push_loop(push_url: String, mut event_receiver: Receiver<Arc<OwnedStats>>) {
+    // 重连期间从推送通道取出的数据；连接成功后先发送
+    let mut pending = VecDeque::new();
     loop {
+        // 推送通道已经释放（如重新初始化、独立周期的推送任务退出）时只再尝试连接一次，发送剩余的数据
+        let closed = drain_pending(&mut event_receiver, &mut pending);
+
         info!(url = &push_url, "准备连接到推送服务r");
 
         let socket = match connect_to_ws_with_timeout(&push_url, Duration::from_secs(2)).await {
@@ -45,6 +123,14 @@ async fn push_loop(push_url: String, mut event_receiver: Receiver<String>) {
                 info!(url = &push_url, "连接推送服务成功");
                 socket
             }
+            Err(err) if closed => {
+                error!(
+                    url = &push_url,
+                    error = %err,
+                    "推送通道已关闭且无法连接，丢弃 {} 条未发送的数据", pending.len()
+                );
+                return;
+            }
             Err(err) => {
                 error!(
                     url = &push_url,
@@ -58,8 +144,13 @@ async fn push_loop(push_url: String, mut event_receiver: Receiver<String>) {
         };
 
         // 开始处理事件
-        match process_events(socket, &mut event_receiver).await {
-            Ok(()) => {}
+        match process_events(socket, &mut pending, &mut event_receiver).await {
+            Ok(true) => {
+                // 推送通道已经释放（如重新初始化），剩余的数据已经发送完
+                info!(url = &push_url, "推送通道已关闭，停止推送");
+                return;
+            }
+            Ok(false) => {}
             Err(err) => {
                 error!(
                     url = &push_url,
@@ -73,10 +164,38 @@ async fn push_loop(push_url: String, mut event_receiver: Receiver<String>) {
     }
 }
 
+/// 取出推送通道中已有的数据，超过通道容量时丢弃最旧的数据；返回推送通道是否已经关闭
+fn drain_pending(
+    event_receiver: &mut Receiver<Arc<OwnedStats>>,
+    pending: &mut VecDeque<Arc<OwnedStats>>,
+) -> bool {
+    loop {
+        match event_receiver.try_recv() {
+            Ok(stats) => {
+                if pending.len() >= PUSH_CHANNEL_CAPACITY {
+                    pending.pop_front();
+                }
+                pending.push_back(stats);
+            }
+            Err(TryRecvError::Lagged(_)) => {}
+            Err(TryRecvError::Empty) => return false,
+            Err(TryRecvError::Closed) => return true,
+        }
+    }
+}
+
+/// 先发送重连期间保留的数据，再转发推送通道中的数据；客户端断开连接时返回 false，推送通道关闭时返回 true
 async fn process_events(
     mut socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
-    event_receiver: &mut Receiver<String>,
-) -> Result<()> {
+    pending: &mut VecDeque<Arc<OwnedStats>>,
+    event_receiver: &mut Receiver<Arc<OwnedStats>>,
+) -> Result<bool> {
+    // 发送失败时保留在队列中，重新连接后再发送
+    while let Some(stats) = pending.front() {
+        send_stats(&mut socket, stats).await?;
+        pending.pop_front();
+    }
+
     let now = Instant::now();
     let mut last_heartbeat = now;
     let mut heartbeat_interval =
@@ -93,7 +212,7 @@ async fn process_events(
                     }
                     None => {
                         // 客户端主动断开连接
-                        return Ok(());
+                        return Ok(false);
                     }
                 }
             }
@@ -112,15 +231,164 @@ async fn process_events(
                 anyhow::ensure!(Instant::now() - last_heartbeat < heartbear_timeout, "heartbeat timeout");
             }
             res = event_receiver.recv() => {
-                let pkg = res?;
-                tokio::time::timeout(
-                    Duration::from_secs(2),
-                    socket.send(Message::Text(pkg)).map_err(anyhow::Error::from),
-                )
-                .map_err(|_| anyhow::anyhow!("timeout"))
-                .await
-                .and_then(std::convert::identity)?;
+                let stats = match res {
+                    Ok(stats) => stats,
+                    Err(RecvError::Closed) => {
+                        let _ = socket.close(None).await;
+                        return Ok(true);
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                send_stats(&mut socket, &stats).await?;
             }
         }
     }
 }
+
+/// 编码并发送一条统计信息
+async fn send_stats(
+    socket: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+    stats: &OwnedStats,
+) -> Result<()> {
+    let compression = *PUSH_COMPRESSION.lock();
+    let pkg = encode(stats, USE_ENVELOPE.load(Ordering::Relaxed), compression)?;
+    tokio::time::timeout(
+        Duration::from_secs(2),
+        socket.send(Message::Text(pkg)).map_err(anyhow::Error::from),
+    )
+    .map_err(|_| anyhow::anyhow!("timeout"))
+    .await
+    .and_then(std::convert::identity)
+}
+
+/// 序列化推送数据；`use_envelope` 时使用 `PushEnvelope` 包装，推送时间为当前时间
+/// 设置了压缩方式时推送 `{"encoding": "gzip", "data": "<base64>"}`
+pub(crate) fn encode(
+    stats: &OwnedStats,
+    use_envelope: bool,
+    compression: Option<PushCompression>,
+) -> Result<String> {
+    let pkg = match use_envelope {
+        true => serde_json::to_string(&PushEnvelope {
+            sequence_number: stats.sequence_number,
+            ..PushEnvelope::new(stats)
+        })?,
+        false => serde_json::to_string(stats)?,
+    };
+
+    match compression {
+        Some(compression) => {
+            let data = compress(pkg.as_bytes(), compression)?;
+            Ok(serde_json::json!({ "encoding": compression.name(), "data": data }).to_string())
+        }
+        None => Ok(pkg),
+    }
+}
+
+/// 压缩数据并使用 base64 编码
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn compress(data: &[u8], compression: PushCompression) -> Result<String> {
+    use base64::Engine;
+
+    let compressed = match compression {
+        #[cfg(feature = "gzip")]
+        PushCompression::Gzip => {
+            use std::io::Write;
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()?
+        }
+        #[cfg(feature = "zstd")]
+        PushCompression::Zstd => zstd::encode_all(data, 0)?,
+        #[allow(unreachable_patterns)]
+        _ => anyhow::bail!(
+            "推送数据使用 {} 压缩需要开启对应的 feature",
+            compression.name()
+        ),
+    };
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(compressed))
+}
+
+/// 未开启压缩相关的 feature 时直接返回错误
+#[cfg(not(any(feature = "gzip", feature = "zstd")))]
+fn compress(_data: &[u8], compression: PushCompression) -> Result<String> {
+    anyhow::bail!(
+        "推送数据使用 {} 压缩需要开启对应的 feature",
+        compression.name()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Stats;
+
+    #[test]
+    fn test_push_sequence_number() {
+        let (sender, mut receiver) = channel(10);
+        let handle = BroadcastPushHandle {
+            sender,
+            targets: vec![],
+            push_sequence: AtomicU64::new(0),
+        };
+        for _ in 0..3 {
+            handle
+                .send(Arc::new(Stats::default().into_owned()))
+                .unwrap();
+        }
+
+        let sequences: Vec<u64> = (0..3)
+            .map(|_| receiver.try_recv().unwrap().sequence_number)
+            .collect();
+        assert_eq!(sequences, [1, 2, 3]);
+
+        // 推送数据和外层结构中都有序号
+        let stats = Stats {
+            sequence_number: 4,
+            ..Default::default()
+        }
+        .into_owned();
+        let value: serde_json::Value =
+            serde_json::from_str(&encode(&stats, true, None).unwrap()).unwrap();
+        assert_eq!(value["sequenceNumber"], 4);
+        assert_eq!(value["payload"]["sequenceNumber"], 4);
+
+        // 没有经过推送通道时不输出
+        let value = serde_json::to_value(Stats::default()).unwrap();
+        assert!(value.get("sequenceNumber").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_push_loop_exits_when_closed() {
+        // 推送通道已经释放且无法连接时不再重连
+        let (sender, receiver) = channel(PUSH_CHANNEL_CAPACITY);
+        sender
+            .send(Arc::new(Stats::default().into_owned()))
+            .unwrap();
+        drop(sender);
+        tokio::time::timeout(
+            Duration::from_secs(10),
+            push_loop("ws://127.0.0.1:1".to_string(), receiver),
+        )
+        .await
+        .expect("推送通道关闭后 push_loop 没有退出");
+
+        // 重连期间取出的数据超过容量时丢弃最旧的数据
+        let (sender, mut receiver) = channel(PUSH_CHANNEL_CAPACITY * 2);
+        for sequence in 1..=15 {
+            let stats = Stats {
+                sequence_number: sequence,
+                ..Default::default()
+            };
+            sender.send(Arc::new(stats.into_owned())).unwrap();
+        }
+        let mut pending = VecDeque::new();
+        assert!(!drain_pending(&mut receiver, &mut pending));
+        assert_eq!(pending.len(), PUSH_CHANNEL_CAPACITY);
+        assert_eq!(pending.front().unwrap().sequence_number, 6);
+        drop(sender);
+        assert!(drain_pending(&mut receiver, &mut pending));
+    }
+}
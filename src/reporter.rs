@@ -0,0 +1,426 @@
+// 统计信息上报
+// 替代固定的 websocket 广播，可以组合多种上报方式；通过 `init_spider_vars_with_reporter` 使用
+use crate::push::{self, PushHandle};
+use crate::{OwnedStats, Stats, GLOBAL_RUNTIME};
+use anyhow::{anyhow, Result};
+use futures_util::future::{join_all, BoxFuture};
+use parking_lot::Mutex;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{error, info, warn};
+
+/// 统计信息的上报方式
+/// 统计信息使用 `Arc` 共享，`StatsReporterChain` 同时上报到多个目标时不需要复制
+pub trait StatsReporter {
+    /// 上报一个周期的统计信息
+    fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>>;
+
+    /// 上报目标地址
+    fn target_urls(&self) -> &[String] {
+        &[]
+    }
+}
+
+// 同时上报到多个目标；任意一个失败时返回错误，不影响其他目标
+#[derive(Default)]
+pub struct StatsReporterChain {
+    reporters: Vec<Box<dyn StatsReporter + Send + Sync>>,
+    targets: Vec<String>,
+}
+
+impl StatsReporterChain {
+    pub fn new(reporters: Vec<Box<dyn StatsReporter + Send + Sync>>) -> Self {
+        let targets = reporters
+            .iter()
+            .flat_map(|r| r.target_urls().iter().cloned())
+            .collect();
+        Self { reporters, targets }
+    }
+
+    /// 增加一个上报目标
+    pub fn with(mut self, reporter: impl StatsReporter + Send + Sync + 'static) -> Self {
+        self.targets.extend(reporter.target_urls().iter().cloned());
+        self.reporters.push(Box::new(reporter));
+        self
+    }
+}
+
+impl StatsReporter for StatsReporterChain {
+    fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let results = join_all(self.reporters.iter().map(|r| r.report(stats.clone()))).await;
+            let errors: Vec<_> = results
+                .into_iter()
+                .filter_map(|res| res.err().map(|err| err.to_string()))
+                .collect();
+            match errors.is_empty() {
+                true => Ok(()),
+                false => Err(anyhow!(
+                    "{} 个目标上报失败：{}",
+                    errors.len(),
+                    errors.join("; ")
+                )),
+            }
+        })
+    }
+
+    fn target_urls(&self) -> &[String] {
+        &self.targets
+    }
+}
+
+// 推送到 websocket 目标；和 `init_spider_vars` 的推送方式一致
+pub struct WebSocketReporter {
+    push: Box<dyn PushHandle + Send + Sync>,
+}
+
+impl WebSocketReporter {
+    pub fn new(targets: Vec<String>) -> Self {
+        Self {
+            push: push::load_push_handle(targets),
+        }
+    }
+}
+
+impl StatsReporter for WebSocketReporter {
+    fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move { self.push.send(stats) })
+    }
+
+    fn target_urls(&self) -> &[String] {
+        self.push.target_urls()
+    }
+}
+
+// 使用 tracing 输出单行摘要
+pub struct LogReporter;
+
+impl StatsReporter for LogReporter {
+    fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let stats: &Stats = &stats;
+            info!("统计信息：{}", stats);
+            Ok(())
+        })
+    }
+}
+
+// 每个周期追加一行 JSON 到文件
+// 文件超过大小上限时轮转：当前文件重命名为 `.1`，原来的 `.1` 重命名为 `.2`，以此类推，超过轮转数量的文件删除
+pub struct FileReporter {
+    inner: Arc<Mutex<FileReporterInner>>,
+}
+
+struct FileReporterInner {
+    path: PathBuf,
+    // 文件大小上限（字节）；0 表示不轮转
+    max_bytes: u64,
+    // 保留的轮转文件数量
+    max_rotations: u8,
+}
+
+impl FileReporter {
+    /// 创建文件上报；`max_file_size_mb` 为 0 时不轮转，父目录不存在时自动创建
+    pub fn new(path: &Path, max_file_size_mb: u64, max_rotations: u8) -> Result<FileReporter> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Ok(FileReporter {
+            inner: Arc::new(Mutex::new(FileReporterInner {
+                path: path.to_path_buf(),
+                max_bytes: max_file_size_mb.saturating_mul(1024 * 1024),
+                max_rotations,
+            })),
+        })
+    }
+}
+
+impl FileReporterInner {
+    /// 追加一行；写入后超过大小上限时先轮转，新的一行写入新文件
+    fn append(&self, line: &str) -> Result<()> {
+        let size = std::fs::metadata(&self.path).map_or(0, |m| m.len());
+        if self.max_bytes > 0 && size > 0 && size + line.len() as u64 > self.max_bytes {
+            return self.rotate(line);
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// 轮转文件；新文件先写入 `.tmp` 再重命名，任何时候当前文件都是完整的
+    fn rotate(&self, line: &str) -> Result<()> {
+        let tmp = self.rotated_path("tmp");
+        std::fs::write(&tmp, line)?;
+
+        if self.max_rotations > 0 {
+            let _ = std::fs::remove_file(self.rotated_path(&self.max_rotations.to_string()));
+            for i in (1..self.max_rotations).rev() {
+                let from = self.rotated_path(&i.to_string());
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated_path(&(i + 1).to_string()))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path("1"))?;
+        }
+
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+
+    /// 在文件名后追加后缀，如 `stats.jsonl.1`
+    fn rotated_path(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(".");
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+}
+
+impl StatsReporter for FileReporter {
+    fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            // 写文件是阻塞操作，在阻塞线程池中执行
+            tokio::task::spawn_blocking(move || {
+                let mut line = serde_json::to_string(&*stats)?;
+                line.push('\n');
+                inner.lock().append(&line)
+            })
+            .await?
+        })
+    }
+}
+
+// 丢弃所有统计信息；用于测试
+pub struct NullReporter;
+
+impl StatsReporter for NullReporter {
+    fn report(&self, _stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+// 上报队列的容量；上报太慢时丢弃新的统计信息，避免队列无限增长
+const REPORTER_QUEUE_CAPACITY: usize = 16;
+
+// 把 `StatsReporter` 适配为推送通道；在 GLOBAL_RUNTIME 中按顺序上报
+pub(crate) struct ReporterPushHandle {
+    sender: mpsc::Sender<Arc<OwnedStats>>,
+    targets: Vec<String>,
+}
+
+impl ReporterPushHandle {
+    pub(crate) fn new(reporter: Box<dyn StatsReporter + Send + Sync>) -> Self {
+        Self::with_capacity(reporter, REPORTER_QUEUE_CAPACITY)
+    }
+
+    /// 指定上报队列的容量
+    fn with_capacity(reporter: Box<dyn StatsReporter + Send + Sync>, capacity: usize) -> Self {
+        let targets = reporter.target_urls().to_vec();
+        let (sender, mut receiver) = mpsc::channel::<Arc<OwnedStats>>(capacity);
+
+        // 推送通道释放后，上报完剩余的数据后退出
+        GLOBAL_RUNTIME.spawn(async move {
+            while let Some(stats) = receiver.recv().await {
+                if let Err(err) = reporter.report(stats).await {
+                    error!("上报统计信息失败：{}", err);
+                }
+            }
+        });
+
+        Self { sender, targets }
+    }
+}
+
+impl PushHandle for ReporterPushHandle {
+    fn send(&self, stats: Arc<OwnedStats>) -> Result<()> {
+        match self.sender.try_send(stats) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(stats)) => {
+                warn!(
+                    "上报队列已满，丢弃第 {} 个统计周期的数据",
+                    stats.cycle_count
+                );
+                Ok(())
+            }
+            Err(TrySendError::Closed(_)) => Err(anyhow!("上报任务已经退出")),
+        }
+    }
+
+    fn target_urls(&self) -> &[String] {
+        &self.targets
+    }
+
+    fn is_closed(&self) -> bool {
+        self.sender.is_closed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // 记录上报的请求数
+    #[derive(Clone, Default)]
+    struct RecordReporter(Arc<Mutex<Vec<i64>>>);
+
+    impl StatsReporter for RecordReporter {
+        fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async move {
+                self.0.lock().push(stats.total_requests);
+                Ok(())
+            })
+        }
+    }
+
+    // 总是上报失败
+    struct FailReporter;
+
+    impl StatsReporter for FailReporter {
+        fn report(&self, _stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async { Err(anyhow!("connection refused")) })
+        }
+    }
+
+    fn stats(total_requests: i64) -> Arc<OwnedStats> {
+        Arc::new(
+            Stats {
+                total_requests,
+                ..Default::default()
+            }
+            .into_owned(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_reporter_chain() {
+        let path =
+            std::env::temp_dir().join(format!("stats_reporter_{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let record = RecordReporter::default();
+        let chain = StatsReporterChain::new(vec![Box::new(record.clone()), Box::new(NullReporter)])
+            .with(LogReporter)
+            .with(FileReporter::new(&path, 10, 3).unwrap());
+        chain.report(stats(1)).await.unwrap();
+        chain.report(stats(2)).await.unwrap();
+        assert_eq!(*record.0.lock(), vec![1, 2]);
+
+        let lines: Vec<OwnedStats> = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].total_requests, 2);
+        std::fs::remove_file(&path).unwrap();
+
+        // 失败的目标不影响其他目标
+        let chain = chain.with(FailReporter);
+        let err = chain.report(stats(3)).await.unwrap_err();
+        assert!(err.to_string().contains("connection refused"));
+        assert_eq!(*record.0.lock(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_file_reporter_rotate() {
+        let dir = std::env::temp_dir().join(format!("stats_file_reporter_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("logs").join("stats.jsonl");
+        let reporter = FileReporter::new(&path, 1, 2).unwrap();
+        assert!(path.parent().unwrap().is_dir());
+
+        // 每个文件最多放两行
+        reporter.inner.lock().max_bytes = 14;
+        let inner = reporter.inner.lock();
+        for i in 0..7 {
+            inner.append(&format!("line {i}\n")).unwrap();
+        }
+
+        let read = |suffix: &str| std::fs::read_to_string(inner.rotated_path(suffix)).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "line 6\n");
+        assert_eq!(read("1"), "line 4\nline 5\n");
+        assert_eq!(read("2"), "line 2\nline 3\n");
+        assert!(!inner.rotated_path("3").exists());
+        assert!(!inner.rotated_path("tmp").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reporter_push_handle() {
+        let record = RecordReporter::default();
+        let handle = ReporterPushHandle::new(Box::new(record.clone()));
+        assert!(handle.target_urls().is_empty());
+        for i in 0..3 {
+            handle.send(stats(i)).unwrap();
+        }
+
+        // 在 GLOBAL_RUNTIME 中按顺序上报
+        for _ in 0..100 {
+            if record.0.lock().len() == 3 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*record.0.lock(), vec![0, 1, 2]);
+        assert!(!handle.is_closed());
+    }
+
+    // 打开之前阻塞上报
+    struct GateReporter {
+        gate: Arc<tokio::sync::Semaphore>,
+        record: RecordReporter,
+    }
+
+    impl StatsReporter for GateReporter {
+        fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+            Box::pin(async move {
+                self.gate.acquire().await?.forget();
+                self.record.report(stats).await
+            })
+        }
+    }
+
+    #[test]
+    fn test_reporter_push_handle_full() {
+        let gate = Arc::new(tokio::sync::Semaphore::new(0));
+        let record = RecordReporter::default();
+        let reporter = GateReporter {
+            gate: gate.clone(),
+            record: record.clone(),
+        };
+        let handle = ReporterPushHandle::with_capacity(Box::new(reporter), 2);
+
+        // 上报阻塞时队列满了之后丢弃新的数据，不返回错误
+        for i in 0..10 {
+            handle.send(stats(i)).unwrap();
+        }
+        gate.add_permits(11);
+
+        // 至少上报队列中的两个之后队列还有空位，再发送一个标记；标记上报之后之前的数据都已经上报
+        let wait_reported = |done: &dyn Fn(&[i64]) -> bool| {
+            let deadline = std::time::Instant::now() + Duration::from_secs(5);
+            while !done(&record.0.lock()) && std::time::Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+        wait_reported(&|reported| reported.len() >= 2);
+        handle.send(stats(100)).unwrap();
+        wait_reported(&|reported| reported.last() == Some(&100));
+
+        // 最多上报队列中的数据和正在上报的一个
+        let mut reported = record.0.lock().clone();
+        assert_eq!(reported.pop(), Some(100), "{reported:?}");
+        assert!(reported.len() >= 2 && reported.len() <= 3, "{reported:?}");
+        assert_eq!(reported[0], 0);
+    }
+}
@@ -0,0 +1,137 @@
+// 单次请求的统计
+// 创建时记录请求时间，结束（或被释放）时自动更新统计数据
+use crate::{custom_result_name, get_now_micros, RequestResult, RequestStats};
+use std::collections::HashMap;
+use tracing::Span;
+
+pub struct RequestSpan<'a> {
+    // 结束之后为 None，避免 Drop 时重复统计
+    stats: Option<&'a RequestStats>,
+    // 请求时间（微秒级时间戳）；和 `update_stats` 中延迟的单位一致
+    request_time: i64,
+    // 阶段名称和阶段结束的时间（微秒级时间戳）
+    checkpoints: Vec<(String, i64)>,
+    span: Span,
+}
+
+impl<'a> RequestSpan<'a> {
+    pub(crate) fn new(stats: &'a RequestStats, url: &str) -> Self {
+        let span = tracing::info_span!(
+            "request",
+            url = %url,
+            status_code = tracing::field::Empty,
+            result = tracing::field::Empty,
+        );
+
+        Self {
+            stats: Some(stats),
+            request_time: get_now_micros(),
+            checkpoints: Vec::new(),
+            span,
+        }
+    }
+
+    /// 当前请求的 tracing span
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// 记录一个阶段结束；阶段耗时从上一个阶段结束（或请求开始）开始计算
+    pub fn checkpoint(&mut self, name: &str) {
+        self.checkpoints.push((name.to_string(), get_now_micros()));
+    }
+
+    /// 请求结束，按耗时更新统计数据；返回每个阶段的耗时（毫秒）
+    pub fn finish(mut self, result: RequestResult, status_code: u16) -> HashMap<String, u64> {
+        self.record(result, status_code)
+            .into_iter()
+            .map(|(name, us)| (name, (us / 1000) as u64))
+            .collect()
+    }
+
+    /// 每个阶段的耗时（微秒）；同名的阶段耗时累加
+    fn stage_durations(&self) -> HashMap<String, i64> {
+        let mut stages = HashMap::new();
+        let mut prev = self.request_time;
+        for (name, time) in &self.checkpoints {
+            *stages.entry(name.clone()).or_insert(0) += time - prev;
+            prev = *time;
+        }
+        stages
+    }
+
+    fn record(&mut self, result: RequestResult, status_code: u16) -> HashMap<String, i64> {
+        let Some(stats) = self.stats.take() else {
+            return HashMap::new();
+        };
+
+        self.span.record("status_code", status_code);
+        match &result {
+            RequestResult::Custom(discriminant) => {
+                let name = custom_result_name(*discriminant)
+                    .unwrap_or_else(|| format!("custom{discriminant}"));
+                self.span.record("result", name.as_str())
+            }
+            _ => self
+                .span
+                .record("result", result.error_kind().unwrap_or("successful")),
+        };
+
+        let stages = self.stage_durations();
+        stats.update_stats_with_stages(
+            self.request_time,
+            get_now_micros(),
+            status_code,
+            result,
+            &stages,
+        );
+        stages
+    }
+}
+
+impl Drop for RequestSpan<'_> {
+    // 没有调用 `finish`（如提前返回）时按连接失败统计
+    fn drop(&mut self) {
+        let _ = self.record(RequestResult::ConnectionError(Some("请求未完成".into())), 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{RequestResult, RequestStats, StatsBase};
+
+    #[test]
+    fn test_request_span() {
+        let stats = RequestStats::new();
+        stats
+            .begin_request("https://a.com")
+            .finish(RequestResult::Successful, 200);
+        {
+            let _span = stats.begin_request("https://b.com");
+        }
+
+        let base = StatsBase::default();
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.total_requests, 2);
+        assert_eq!(d.http_status_codes["200"], 1);
+        assert_eq!(d.exception_types.connection_error, 1);
+        assert_eq!(d.recent_errors["connectionError"], vec!["请求未完成"]);
+    }
+
+    #[test]
+    fn test_request_span_checkpoint() {
+        let stats = RequestStats::new();
+        let mut span = stats.begin_request("https://a.com");
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        span.checkpoint("fetch");
+        span.checkpoint("parse");
+        let stages = span.finish(RequestResult::Successful, 200);
+        assert!(stages["fetch"] >= 20);
+        assert!(stages["parse"] < 20);
+
+        let base = StatsBase::default();
+        let d = stats.to_stats_and_reset(&base, None);
+        assert!(d.stage_durations["fetch"].avg >= 20.0);
+        assert!(d.stage_durations.contains_key("parse"));
+    }
+}
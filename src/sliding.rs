@@ -0,0 +1,135 @@
+// 滑动窗口统计
+// 只保留最近一段时间内的请求记录，用于近实时的监控；和定时推送并清空的统计互不影响
+use crate::{get_now_millis, InnerStats, RequestResult, Stats, StatsBase};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// 单次请求记录
+struct WindowEvent {
+    // 记录时间（毫秒级时间戳）
+    timestamp: i64,
+    status_code: u16,
+    result: RequestResult,
+    // 请求延迟；和 `update_stats` 中 response_time - request_time 的单位一致
+    latency: i64,
+}
+
+pub struct SlidingWindowStats {
+    // 对象初始化时间（毫秒级时间戳）
+    init_time: i64,
+    // 窗口时长
+    window: Duration,
+    // 最多保留的请求记录数
+    max_events: usize,
+    events: Mutex<VecDeque<WindowEvent>>,
+}
+
+impl SlidingWindowStats {
+    /// 创建滑动窗口统计；`max_events` 限制内存占用，超过时丢弃最旧的记录
+    pub fn new(window: Duration, max_events: usize) -> Self {
+        Self {
+            init_time: get_now_millis(),
+            window,
+            max_events,
+            events: Mutex::new(VecDeque::with_capacity(max_events)),
+        }
+    }
+
+    /// 记录一次请求
+    pub fn update_stats(
+        &self,
+        request_time: i64,
+        response_time: i64,
+        status_code: u16,
+        result: RequestResult,
+    ) {
+        if self.max_events == 0 {
+            return;
+        }
+
+        let now = get_now_millis();
+        let mut events = self.events.lock();
+
+        // 清理窗口外的记录
+        let window_start = now - self.window.as_millis() as i64;
+        while events.front().is_some_and(|e| e.timestamp < window_start) {
+            events.pop_front();
+        }
+
+        if events.len() >= self.max_events {
+            events.pop_front();
+        }
+
+        events.push_back(WindowEvent {
+            timestamp: now,
+            status_code,
+            result,
+            latency: response_time - request_time,
+        });
+    }
+
+    /// 计算窗口内的统计数据；不会修改已有记录
+    pub fn current_stats<'a>(&self, base: &'a StatsBase) -> Stats<'a> {
+        let now = get_now_millis();
+        let window_start = now - self.window.as_millis() as i64;
+
+        let mut inner = InnerStats::new();
+        inner.init_time = self.init_time;
+        inner.start_time = window_start.max(self.init_time);
+
+        for e in self.events.lock().iter() {
+            if e.timestamp >= window_start {
+                inner.update_stats(0, e.latency, e.status_code, &e.result);
+            }
+        }
+
+        inner.to_stats_and_reset(base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_base() -> StatsBase {
+        StatsBase {
+            server_name: "".to_string(),
+            scraper_name: "".to_string(),
+            project_code: "".to_string(),
+            scraper_type: "".to_string(),
+            request_frequency: 0,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_sliding_window_stats() {
+        let stats = SlidingWindowStats::new(Duration::from_secs(60), 3);
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        stats.update_stats(0, 2000, 200, RequestResult::SuccessfulAndCache);
+        stats.update_stats(0, 3000, 500, RequestResult::StatusCodeError(None));
+        stats.update_stats(0, 4000, 200, RequestResult::Successful);
+
+        let base = get_base();
+        let d = stats.current_stats(&base);
+        // 只保留最近 3 条
+        assert_eq!(d.total_requests, 3);
+        assert_eq!(d.cache_hit, 1);
+        assert_eq!(d.exception_types.status_code_error, 1);
+        assert_eq!(d.average_request_latency, 3.0);
+
+        // 计算统计数据不会清空记录
+        assert_eq!(stats.current_stats(&base).total_requests, 3);
+    }
+
+    #[test]
+    fn test_sliding_window_expired() {
+        let stats = SlidingWindowStats::new(Duration::ZERO, 10);
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        std::thread::sleep(Duration::from_millis(5));
+
+        let base = get_base();
+        assert_eq!(stats.current_stats(&base).total_requests, 0);
+    }
+}
@@ -0,0 +1,432 @@
+// 统计信息写入本地 SQLite 数据库；需要开启 sqlite feature
+// 标量字段各占一列，方便直接用 SQL 查询；其他字段（状态码、hosts 延迟、系统资源等）以 JSON 保存在 `json_extra` 列
+use crate::reporter::StatsReporter;
+use crate::OwnedStats;
+use anyhow::{anyhow, Result};
+use futures_util::future::BoxFuture;
+use parking_lot::Mutex;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OptionalExtension};
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::sync::Arc;
+
+// 列的类型；布尔值保存为 0 / 1
+#[derive(Clone, Copy)]
+enum ColumnType {
+    Integer,
+    Real,
+    Text,
+    Bool,
+}
+
+// `stats` 表的标量列：列名、序列化后的字段名（嵌套字段用 `.` 分隔）和类型
+const COLUMNS: &[(&str, &str, ColumnType)] = &[
+    ("schema_version", "schemaVersion", ColumnType::Integer),
+    ("server_name", "serverName", ColumnType::Text),
+    ("scraper_name", "scraperName", ColumnType::Text),
+    ("project_code", "projectCode", ColumnType::Text),
+    ("scraper_type", "scraperType", ColumnType::Text),
+    ("request_frequency", "requestFrequency", ColumnType::Integer),
+    ("period_start", "timePeriod.start", ColumnType::Integer),
+    ("period_end", "timePeriod.end", ColumnType::Integer),
+    ("cycle_count", "cycleCount", ColumnType::Integer),
+    ("restarted_at", "restartedAt", ColumnType::Integer),
+    ("sequence_number", "sequenceNumber", ColumnType::Integer),
+    ("stale", "stale", ColumnType::Bool),
+    ("error_rate", "errorRate", ColumnType::Real),
+    ("runtime_duration", "runtimeDuration", ColumnType::Integer),
+    ("total_requests", "totalRequests", ColumnType::Integer),
+    (
+        "successful_requests",
+        "successfulRequests",
+        ColumnType::Integer,
+    ),
+    ("failed_requests", "failedRequests", ColumnType::Integer),
+    ("requests_per_second", "requestsPerSecond", ColumnType::Real),
+    (
+        "successful_requests_per_second",
+        "successfulRequestsPerSecond",
+        ColumnType::Real,
+    ),
+    ("cache_hit_rate", "cacheHitRate", ColumnType::Real),
+    ("cache_hit", "cacheHit", ColumnType::Integer),
+    (
+        "connection_reuse_rate",
+        "connectionReuseRate",
+        ColumnType::Real,
+    ),
+    ("avg_dns_latency_ms", "avgDnsLatencyMs", ColumnType::Real),
+    ("avg_queue_delay_ms", "avgQueueDelayMs", ColumnType::Real),
+    (
+        "total_request_bytes",
+        "totalRequestBytes",
+        ColumnType::Integer,
+    ),
+    (
+        "total_response_bytes",
+        "totalResponseBytes",
+        ColumnType::Integer,
+    ),
+    ("avg_response_bytes", "avgResponseBytes", ColumnType::Real),
+    (
+        "average_request_latency",
+        "averageRequestLatency",
+        ColumnType::Real,
+    ),
+    (
+        "max_consecutive_failures",
+        "maxConsecutiveFailures",
+        ColumnType::Integer,
+    ),
+];
+
+// 数据库结构的迁移语句；第 n 条把数据库从版本 n 升级到版本 n + 1
+// 修改表结构时在末尾追加新的语句，不要修改已有的语句
+const MIGRATIONS: &[&str] = &[
+    // 版本 1：创建 stats 表
+    "CREATE TABLE IF NOT EXISTS stats (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        schema_version INTEGER,
+        server_name TEXT,
+        scraper_name TEXT,
+        project_code TEXT,
+        scraper_type TEXT,
+        request_frequency INTEGER,
+        period_start INTEGER,
+        period_end INTEGER,
+        cycle_count INTEGER,
+        restarted_at INTEGER,
+        sequence_number INTEGER,
+        stale INTEGER,
+        error_rate REAL,
+        runtime_duration INTEGER,
+        total_requests INTEGER,
+        successful_requests INTEGER,
+        failed_requests INTEGER,
+        requests_per_second REAL,
+        successful_requests_per_second REAL,
+        cache_hit_rate REAL,
+        cache_hit INTEGER,
+        connection_reuse_rate REAL,
+        avg_dns_latency_ms REAL,
+        avg_queue_delay_ms REAL,
+        total_request_bytes INTEGER,
+        total_response_bytes INTEGER,
+        avg_response_bytes REAL,
+        average_request_latency REAL,
+        max_consecutive_failures INTEGER,
+        json_extra TEXT
+    );
+    CREATE INDEX IF NOT EXISTS stats_period_end ON stats (period_end);",
+];
+
+/// 当前的数据库结构版本
+pub const SQLITE_SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+// 每个周期插入一行到 SQLite 数据库
+// 打开数据库时自动创建表，并按 `schema_version` 表中记录的版本执行迁移
+pub struct SqliteReporter {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl SqliteReporter {
+    /// 打开（不存在时创建）数据库文件；父目录不存在时自动创建
+    pub fn new(path: &Path) -> Result<SqliteReporter> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// 使用已经打开的连接，如 `Connection::open_in_memory()`
+    pub fn with_connection(mut conn: Connection) -> Result<SqliteReporter> {
+        migrate(&mut conn)?;
+        Ok(SqliteReporter {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// 读取最近 `n` 个周期的统计信息，按时间顺序；会阻塞当前线程
+    pub fn query_last_n(&self, n: usize) -> Result<Vec<OwnedStats>> {
+        let columns = COLUMNS
+            .iter()
+            .map(|(name, _, _)| *name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let sql = format!("SELECT {columns}, json_extra FROM stats ORDER BY id DESC LIMIT ?1");
+
+        let conn = self.conn.lock();
+        let mut statement = conn.prepare(&sql)?;
+        let rows = statement
+            .query_map([n.min(i64::MAX as usize) as i64], |row| {
+                let values = (0..COLUMNS.len())
+                    .map(|i| row.get::<_, SqlValue>(i))
+                    .collect::<rusqlite::Result<Vec<_>>>()?;
+                let extra: Option<String> = row.get(COLUMNS.len())?;
+                Ok((values, extra))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter()
+            .rev()
+            .map(|(values, extra)| row_to_stats(values, extra.as_deref()))
+            .collect()
+    }
+}
+
+impl StatsReporter for SqliteReporter {
+    fn report(&self, stats: Arc<OwnedStats>) -> BoxFuture<'_, Result<()>> {
+        let conn = self.conn.clone();
+        Box::pin(async move {
+            // 写数据库是阻塞操作，在阻塞线程池中执行
+            tokio::task::spawn_blocking(move || insert(&conn.lock(), &stats)).await?
+        })
+    }
+}
+
+/// 按 `schema_version` 表中记录的版本执行剩余的迁移语句
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute_batch("CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)")?;
+    let version: i64 = tx
+        .query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+            row.get::<_, Option<i64>>(0)
+        })
+        .optional()?
+        .flatten()
+        .unwrap_or(0);
+    anyhow::ensure!(
+        (0..=SQLITE_SCHEMA_VERSION).contains(&version),
+        "数据库结构版本 {} 不支持，当前版本为 {}",
+        version,
+        SQLITE_SCHEMA_VERSION
+    );
+    if version == SQLITE_SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    for sql in &MIGRATIONS[version as usize..] {
+        tx.execute_batch(sql)?;
+    }
+    tx.execute("DELETE FROM schema_version", [])?;
+    tx.execute(
+        "INSERT INTO schema_version (version) VALUES (?1)",
+        [SQLITE_SCHEMA_VERSION],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// 插入一行；标量字段从序列化结果中取出，剩下的字段保存到 `json_extra`
+fn insert(conn: &Connection, stats: &OwnedStats) -> Result<()> {
+    let Value::Object(mut extra) = serde_json::to_value(stats)? else {
+        return Err(anyhow!("统计信息序列化的结果不是对象"));
+    };
+
+    let mut values: Vec<SqlValue> = COLUMNS
+        .iter()
+        .map(|(_, key, column_type)| {
+            take(&mut extra, key).map_or(SqlValue::Null, |v| to_sql(&v, *column_type))
+        })
+        .collect();
+    values.push(SqlValue::Text(serde_json::to_string(&extra)?));
+
+    let columns = COLUMNS
+        .iter()
+        .map(|(name, _, _)| *name)
+        .chain(std::iter::once("json_extra"))
+        .collect::<Vec<_>>();
+    let placeholders = (1..=columns.len())
+        .map(|i| format!("?{i}"))
+        .collect::<Vec<_>>();
+    let sql = format!(
+        "INSERT INTO stats ({}) VALUES ({})",
+        columns.join(", "),
+        placeholders.join(", ")
+    );
+    conn.execute(&sql, rusqlite::params_from_iter(values))?;
+    Ok(())
+}
+
+/// 把一行数据还原为统计信息；NULL 的列使用默认值
+fn row_to_stats(values: Vec<SqlValue>, extra: Option<&str>) -> Result<OwnedStats> {
+    let mut map: Map<String, Value> = match extra {
+        Some(extra) => serde_json::from_str(extra)?,
+        None => Map::new(),
+    };
+
+    for ((_, key, column_type), value) in COLUMNS.iter().zip(values) {
+        let value = match (value, column_type) {
+            (SqlValue::Integer(v), ColumnType::Bool) => Value::Bool(v != 0),
+            (SqlValue::Integer(v), _) => v.into(),
+            (SqlValue::Real(v), _) => v.into(),
+            (SqlValue::Text(v), _) => v.into(),
+            (SqlValue::Null | SqlValue::Blob(_), _) => continue,
+        };
+        put(&mut map, key, value);
+    }
+
+    Ok(serde_json::from_value(Value::Object(map))?)
+}
+
+/// 转换为列的类型；类型不匹配时为 NULL
+fn to_sql(value: &Value, column_type: ColumnType) -> SqlValue {
+    let value = match column_type {
+        ColumnType::Integer => value
+            .as_i64()
+            .map(SqlValue::Integer)
+            .or_else(|| value.as_f64().map(SqlValue::Real)),
+        ColumnType::Real => value.as_f64().map(SqlValue::Real),
+        ColumnType::Text => value.as_str().map(|v| SqlValue::Text(v.to_string())),
+        ColumnType::Bool => value.as_bool().map(|v| SqlValue::Integer(v as i64)),
+    };
+    value.unwrap_or(SqlValue::Null)
+}
+
+/// 取出字段；嵌套字段取出后父对象为空时一起删除
+fn take(map: &mut Map<String, Value>, key: &str) -> Option<Value> {
+    match key.split_once('.') {
+        None => map.remove(key),
+        Some((parent, child)) => {
+            let object = map.get_mut(parent)?.as_object_mut()?;
+            let value = take(object, child);
+            if object.is_empty() {
+                map.remove(parent);
+            }
+            value
+        }
+    }
+}
+
+/// 写入字段；嵌套字段的父对象不存在时创建
+fn put(map: &mut Map<String, Value>, key: &str, value: Value) {
+    match key.split_once('.') {
+        None => {
+            map.insert(key.to_string(), value);
+        }
+        Some((parent, child)) => {
+            let parent = map
+                .entry(parent)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(object) = parent {
+                put(object, child, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HostPingStats, Stats, StatsBase, TimePeriod};
+    use std::collections::HashMap;
+
+    fn stats(total_requests: i64) -> Arc<OwnedStats> {
+        let base = StatsBase::builder()
+            .server_name("server-1")
+            .extra("dataCenter", "us-east-1")
+            .build();
+        Arc::new(
+            Stats {
+                base: std::borrow::Cow::Owned(base),
+                time_period: TimePeriod {
+                    start: 1_700_000_000_000,
+                    end: 1_700_000_060_000 + total_requests,
+                },
+                total_requests,
+                stale: true,
+                error_rate: 0.25,
+                avg_queue_delay_ms: 1.5,
+                http_status_codes: HashMap::from([("200".to_string(), total_requests)]),
+                hosts_ping_delay: HashMap::from([(
+                    "example.com".to_string(),
+                    HostPingStats {
+                        avg: 12.0,
+                        ..Default::default()
+                    },
+                )]),
+                ..Default::default()
+            }
+            .into_owned(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_reporter() {
+        let reporter =
+            SqliteReporter::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+        for i in 1..=3 {
+            reporter.report(stats(i)).await.unwrap();
+        }
+
+        // 按时间顺序返回最近的几行，所有字段都可以还原
+        let rows = reporter.query_last_n(2).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0], *stats(2));
+        assert_eq!(rows[1], *stats(3));
+        assert_eq!(reporter.query_last_n(10).unwrap().len(), 3);
+        assert!(reporter.query_last_n(0).unwrap().is_empty());
+
+        // 标量字段可以直接查询，其他字段保存在 json_extra
+        let conn = reporter.conn.lock();
+        let (total, stale, extra): (i64, bool, String) = conn
+            .query_row(
+                "SELECT SUM(total_requests), MAX(stale), json_extra FROM stats",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(total, 6);
+        assert!(stale);
+        let extra: Value = serde_json::from_str(&extra).unwrap();
+        assert!(extra.get("httpStatusCodes").is_some());
+        assert!(extra.get("hostsPingDelay").is_some());
+        assert!(extra.get("totalRequests").is_none());
+        assert!(extra.get("timePeriod").is_none());
+    }
+
+    #[test]
+    fn test_sqlite_migrate() {
+        let path = std::env::temp_dir().join(format!("stats_sqlite_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let file = path.join("stats.db");
+        let reporter = SqliteReporter::new(&file).unwrap();
+
+        // 所有的列都在迁移之后的表中
+        {
+            let conn = reporter.conn.lock();
+            let mut statement = conn
+                .prepare("SELECT name FROM pragma_table_info('stats')")
+                .unwrap();
+            let names = statement
+                .query_map([], |row| row.get::<_, String>(0))
+                .unwrap()
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .unwrap();
+            for (name, _, _) in COLUMNS {
+                assert!(names.iter().any(|n| n == name), "{name}");
+            }
+        }
+        drop(reporter);
+
+        // 重新打开时不会重复迁移
+        let reporter = SqliteReporter::new(&file).unwrap();
+        let version: i64 = reporter
+            .conn
+            .lock()
+            .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, SQLITE_SCHEMA_VERSION);
+        drop(reporter);
+
+        // 比当前版本高的数据库不能打开
+        let conn = Connection::open(&file).unwrap();
+        conn.execute("UPDATE schema_version SET version = 100", [])
+            .unwrap();
+        assert!(SqliteReporter::with_connection(conn).is_err());
+
+        std::fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+// 系统资源轮询；后台任务定时刷新 `System`，读取时直接使用最近一次刷新的结果
+use crate::{
+    get_open_fd_count, get_thread_count, CpuCoreUsage, DiskIOSnapshot, NetworkIO, ProcessMemory,
+    SystemResources, Usage, GLOBAL_RUNTIME, LAST_DISK_IO, LAST_NETWORK_IO,
+};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use sysinfo::{CpuExt, DiskExt, NetworkExt, NetworksExt, ProcessExt, System, SystemExt};
+use tracing::warn;
+
+// 默认的刷新间隔
+pub const DEFAULT_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 全局的系统资源轮询器；`get_system_resources` 从这里读取数据
+/// 第一次使用时刷新一次，之后按 `RequestStatsConfig.system_refresh_interval` 定时刷新
+pub static SYSTEM_POLLER: Lazy<SystemPoller> =
+    Lazy::new(|| SystemPoller::new(DEFAULT_REFRESH_INTERVAL));
+
+/// 系统资源轮询器
+/// 创建时刷新一次系统信息，并在 `GLOBAL_RUNTIME` 中启动后台任务定时刷新；轮询器销毁后任务退出
+pub struct SystemPoller {
+    inner: Arc<PollerInner>,
+}
+
+struct PollerInner {
+    // 复用的 System 实例；CPU 使用率需要和上一次刷新比较，每次新建时不准确
+    system: Mutex<System>,
+    // 最近一次刷新的结果
+    sample: Mutex<SystemSample>,
+    // 刷新间隔（毫秒）
+    interval_ms: AtomicU64,
+}
+
+// 一次刷新得到的数据；网络和磁盘 IO 是累计值，读取时再计算和上一次读取的差值
+#[derive(Clone)]
+struct SystemSample {
+    resources: SystemResources,
+    network_total: NetworkIO,
+    disk_total: DiskIOSnapshot,
+}
+
+impl SystemPoller {
+    /// 创建轮询器；`interval` 为 0 时使用默认的刷新间隔
+    pub fn new(interval: Duration) -> Self {
+        let mut system = System::new_all();
+        system.refresh_all();
+        let sample = sample_system(&system);
+
+        let inner = Arc::new(PollerInner {
+            system: Mutex::new(system),
+            sample: Mutex::new(sample),
+            interval_ms: AtomicU64::new(interval_millis(interval)),
+        });
+        GLOBAL_RUNTIME.spawn(refresh_loop(Arc::downgrade(&inner)));
+
+        Self { inner }
+    }
+
+    /// 设置刷新间隔；下一次刷新之后生效。`interval` 为 0 时使用默认的刷新间隔
+    pub fn set_interval(&self, interval: Duration) {
+        self.inner
+            .interval_ms
+            .store(interval_millis(interval), Ordering::Relaxed);
+    }
+
+    /// 当前的刷新间隔
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.inner.interval_ms.load(Ordering::Relaxed))
+    }
+
+    /// 最近一次刷新的时间
+    pub fn refreshed_at(&self) -> Instant {
+        self.inner.sample.lock().disk_total.at
+    }
+
+    /// 立即刷新一次系统信息；会阻塞当前线程
+    pub fn refresh(&self) {
+        self.inner.refresh();
+    }
+
+    /// 最近一次刷新的系统资源数据；不会触发刷新
+    pub fn current(&self) -> SystemResources {
+        self.current_with(true)
+    }
+
+    /// 最近一次刷新的系统资源数据；`include_per_core_cpu` 为 false 时不包含每个 CPU 核心的使用率
+    /// 网络 IO 和磁盘 IO 是和上一次读取之间的差值；第一次读取时记为 0
+    pub fn current_with(&self, include_per_core_cpu: bool) -> SystemResources {
+        let SystemSample {
+            mut resources,
+            network_total,
+            disk_total,
+        } = self.inner.sample.lock().clone();
+
+        if !include_per_core_cpu {
+            resources.cpu_cores.clear();
+        }
+
+        resources.network_io = LAST_NETWORK_IO
+            .lock()
+            .replace(network_total.clone())
+            .map(|prev| network_total.delta(&prev))
+            .unwrap_or_default();
+        resources.disk_io = LAST_DISK_IO
+            .lock()
+            .replace(disk_total.clone())
+            .map(|prev| disk_total.throughput(&prev))
+            .unwrap_or_default();
+
+        resources
+    }
+}
+
+impl PollerInner {
+    fn refresh(&self) {
+        let sample = {
+            let mut system = self.system.lock();
+            system.refresh_all();
+            sample_system(&system)
+        };
+        *self.sample.lock() = sample;
+    }
+}
+
+/// 转换为毫秒；0 表示使用默认的刷新间隔
+fn interval_millis(interval: Duration) -> u64 {
+    let interval = if interval.is_zero() {
+        DEFAULT_REFRESH_INTERVAL
+    } else {
+        interval
+    };
+    (interval.as_millis() as u64).max(1)
+}
+
+/// 定时刷新系统信息；轮询器销毁后退出。刷新是阻塞操作，在阻塞线程池中执行
+async fn refresh_loop(inner: Weak<PollerInner>) {
+    loop {
+        let interval = match inner.upgrade() {
+            Some(inner) => Duration::from_millis(inner.interval_ms.load(Ordering::Relaxed)),
+            None => return,
+        };
+        tokio::time::sleep(interval).await;
+
+        let Some(inner) = inner.upgrade() else {
+            return;
+        };
+        if let Err(err) = tokio::task::spawn_blocking(move || inner.refresh()).await {
+            warn!("刷新系统资源失败: {:?}", err);
+        }
+    }
+}
+
+/// 从已经刷新的 System 中读取系统资源数据
+fn sample_system(system: &System) -> SystemSample {
+    // 获取 CPU 使用率
+    let cpu_usage = (system.global_cpu_info().cpu_usage() * 100.0).round() / 100.0;
+
+    let cpu_cores = system
+        .cpus()
+        .iter()
+        .enumerate()
+        .map(|(index, cpu)| CpuCoreUsage {
+            index,
+            usage: (cpu.cpu_usage() * 100.0).round() / 100.0,
+        })
+        .collect();
+
+    // 获取内存使用情况（单位从 KB 转换为 MB）
+    let total_memory = system.total_memory() / (1024 * 1024); // 总内存（MB）
+    let used_memory = system.used_memory() / (1024 * 1024); // 已使用内存（MB）
+
+    let memory_usage = Usage {
+        used: used_memory,
+        total: total_memory,
+    };
+
+    // 获取所有磁盘的使用情况（单位从字节转换为 MB）
+    let mut total_disk_space = 0;
+    let mut total_disk_used = 0;
+
+    for disk in system.disks() {
+        total_disk_space += disk.total_space() / (1024 * 1024); // 累加磁盘总空间（MB）
+        total_disk_used += (disk.total_space() - disk.available_space()) / (1024 * 1024);
+        // 累加磁盘已使用空间（MB）
+    }
+
+    let disk_usage = Usage {
+        used: total_disk_used,
+        total: total_disk_space,
+    };
+
+    // 网络 IO 的累计值
+    let network_total = system
+        .networks()
+        .iter()
+        .fold(NetworkIO::default(), |acc, (_, data)| NetworkIO {
+            bytes_received: acc.bytes_received + data.total_received(),
+            bytes_sent: acc.bytes_sent + data.total_transmitted(),
+            packets_received: acc.packets_received + data.total_packets_received(),
+            packets_sent: acc.packets_sent + data.total_packets_transmitted(),
+        });
+
+    // 磁盘 IO 使用所有进程的累计读写字节数
+    let disk_total = system.processes().values().fold(
+        DiskIOSnapshot {
+            read_bytes: 0,
+            written_bytes: 0,
+            at: Instant::now(),
+        },
+        |mut acc, process| {
+            let usage = process.disk_usage();
+            acc.read_bytes += usage.total_read_bytes;
+            acc.written_bytes += usage.total_written_bytes;
+            acc
+        },
+    );
+
+    // 获取当前进程的内存使用情况（单位从字节转换为 MB）
+    let process_memory = match sysinfo::get_current_pid()
+        .ok()
+        .and_then(|pid| system.process(pid))
+    {
+        Some(process) => ProcessMemory {
+            rss_mb: process.memory() / (1024 * 1024),
+            virtual_mb: process.virtual_memory() / (1024 * 1024),
+        },
+        None => {
+            warn!("获取当前进程信息失败");
+            ProcessMemory::default()
+        }
+    };
+
+    SystemSample {
+        resources: SystemResources {
+            cpu_usage,
+            memory_usage,
+            disk_usage,
+            cpu_cores,
+            network_io: NetworkIO::default(),
+            disk_io: Default::default(),
+            process_memory,
+            thread_count: get_thread_count(),
+            open_fd_count: get_open_fd_count(),
+        },
+        network_total,
+        disk_total,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_poller() {
+        let poller = SystemPoller::new(Duration::from_millis(100));
+        assert_eq!(poller.interval(), Duration::from_millis(100));
+
+        // 读取不会触发刷新
+        let refreshed_at = poller.refreshed_at();
+        let resources = poller.current_with(true);
+        assert!(!resources.cpu_cores.is_empty());
+        assert!(resources.memory_usage.total > 0);
+        assert!(poller.current_with(false).cpu_cores.is_empty());
+        assert_eq!(poller.refreshed_at(), refreshed_at);
+
+        // 后台任务按间隔刷新
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while poller.refreshed_at() == refreshed_at {
+            assert!(Instant::now() < deadline, "后台任务没有刷新");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        poller.set_interval(Duration::ZERO);
+        assert_eq!(poller.interval(), DEFAULT_REFRESH_INTERVAL);
+    }
+}
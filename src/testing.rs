@@ -0,0 +1,189 @@
+// 测试辅助工具
+// 需要开启 testing feature；提供可以手动调整的时钟和 `Stats` 的逐字段比较
+use crate::{Clock, Stats};
+use serde_json::Value;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+/// 浮点数近似相等（误差 1e-6）；统计数据中的比例和平均值经过计算，测试中应使用近似比较
+pub fn f64_approx_eq(a: f64, b: f64) -> bool {
+    (a - b).abs() < 1e-6
+}
+
+/// 手动调整的时钟；clone 之后共享同一个时间
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    // 当前时间（毫秒级时间戳）
+    pub current_ms: Arc<AtomicI64>,
+}
+
+impl MockClock {
+    /// 创建时钟，当前时间为 `current_ms`
+    pub fn new(current_ms: i64) -> Self {
+        Self {
+            current_ms: Arc::new(AtomicI64::new(current_ms)),
+        }
+    }
+
+    /// 当前时间（毫秒级时间戳）
+    pub fn now(&self) -> i64 {
+        self.current_ms.load(Ordering::SeqCst)
+    }
+
+    /// 设置当前时间
+    pub fn set(&self, current_ms: i64) {
+        self.current_ms.store(current_ms, Ordering::SeqCst);
+    }
+
+    /// 时间前进 `ms` 毫秒
+    pub fn advance(&self, ms: i64) {
+        self.current_ms.fetch_add(ms, Ordering::SeqCst);
+    }
+
+    /// 转换成 `RequestStats::new_with_clock` 使用的时钟
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        let current_ms = self.current_ms.clone();
+        Arc::new(move || current_ms.load(Ordering::SeqCst))
+    }
+}
+
+/// 逐字段比较两个 `Stats`；不相等时 panic 并列出所有不同的字段
+#[track_caller]
+pub fn assert_stats_eq(actual: &Stats, expected: &Stats) {
+    let mut diffs = Vec::new();
+    diff_values("", &Value::from(actual), &Value::from(expected), &mut diffs);
+    if !diffs.is_empty() {
+        panic!("Stats 不相等：\n{}", diffs.join("\n"));
+    }
+}
+
+/// 检查统计数据始终有效：所有数值字段都是有限值（不是 NaN 或无穷大），
+/// 请求总数和平均延迟不为负，错误率在 0 到 1 之间；不满足时 panic 并列出所有无效的字段
+#[track_caller]
+pub fn assert_stats_valid(stats: &Stats) {
+    let mut errors: Vec<String> = stats
+        .non_finite_fields()
+        .into_iter()
+        .map(|(name, value)| format!("  {name} 不是有限值：{value}"))
+        .collect();
+    if stats.total_requests < 0 {
+        errors.push(format!("  totalRequests 为负：{}", stats.total_requests));
+    }
+    if stats.average_request_latency < 0.0 {
+        errors.push(format!(
+            "  averageRequestLatency 为负：{}",
+            stats.average_request_latency
+        ));
+    }
+    if !(0.0..=1.0).contains(&stats.error_rate) {
+        errors.push(format!("  errorRate 超出范围：{}", stats.error_rate));
+    }
+    if !errors.is_empty() {
+        panic!("Stats 无效：\n{}", errors.join("\n"));
+    }
+}
+
+/// 递归比较 JSON，记录不同字段的路径和值
+fn diff_values(path: &str, actual: &Value, expected: &Value, diffs: &mut Vec<String>) {
+    match (actual, expected) {
+        (Value::Object(a), Value::Object(e)) => {
+            let mut keys: Vec<_> = a.keys().chain(e.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = match path.is_empty() {
+                    true => key.clone(),
+                    false => format!("{path}.{key}"),
+                };
+                let a = a.get(key).unwrap_or(&Value::Null);
+                let e = e.get(key).unwrap_or(&Value::Null);
+                diff_values(&path, a, e, diffs);
+            }
+        }
+        (a, e) if a != e => diffs.push(format!("  {path}: 实际 {a}，期望 {e}")),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RequestInfo, RequestResult, RequestStats, StatsBase};
+
+    #[test]
+    fn test_mock_clock() {
+        let clock = MockClock::new(1_000_000);
+        let stats = RequestStats::new_with_clock(clock.clock());
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+        stats.update_stats(0, 1000, 200, RequestResult::Successful);
+
+        clock.advance(2000);
+        let base = StatsBase::default();
+        let d = stats.to_stats_and_reset(&base, None);
+        assert_eq!(d.time_period.start, 1_000_000);
+        assert_eq!(d.time_period.end, 1_002_000);
+        assert_eq!(d.requests_per_second, 1.0);
+        assert_eq!(d.runtime_duration, 2);
+
+        // 下一个周期从上一个周期结束时开始
+        clock.set(1_005_000);
+        let d = stats.snapshot(&base);
+        assert_eq!(d.time_period.start, 1_002_000);
+        assert_eq!(d.time_period.end, 1_005_000);
+    }
+
+    #[test]
+    fn test_assert_stats_eq() {
+        let stats = Stats {
+            total_requests: 10,
+            ..Default::default()
+        };
+        assert_stats_eq(&stats, &stats.clone());
+
+        let other = Stats {
+            total_requests: 11,
+            error_rate: 0.5,
+            ..Default::default()
+        };
+        let err = std::panic::catch_unwind(|| assert_stats_eq(&stats, &other)).unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("totalRequests: 实际 10，期望 11"), "{msg}");
+        assert!(msg.contains("errorRate: 实际 0.0，期望 0.5"), "{msg}");
+        assert!(!msg.contains("cacheHit"), "{msg}");
+    }
+
+    #[test]
+    fn test_assert_stats_valid() {
+        // 极端输入下统计数据仍然有效；和模糊测试检查的内容一致
+        let stats = RequestStats::new();
+        let extremes = [i64::MIN, -1, 0, 1, i64::MAX];
+        let info = RequestInfo {
+            retry_count: u8::MAX,
+            request_bytes: u64::MAX,
+            response_bytes: u64::MAX,
+            proxy_id: Some(""),
+            dns_duration_us: Some(u64::MAX),
+            queue_delay_us: Some(u64::MAX),
+            connection_reused: true,
+        };
+        for request_time in extremes {
+            for response_time in extremes {
+                let result = RequestResult::TimeoutError(None);
+                stats.update_stats_with(request_time, response_time, 0, result, &info);
+                let result = RequestResult::Successful;
+                stats.update_stats(request_time, response_time, u16::MAX, result);
+            }
+        }
+        assert_stats_valid(&stats.snapshot(&StatsBase::default()));
+
+        let invalid = Stats {
+            average_request_latency: f64::NAN,
+            error_rate: 2.0,
+            ..Default::default()
+        };
+        let err = std::panic::catch_unwind(|| assert_stats_valid(&invalid)).unwrap_err();
+        let msg = err.downcast_ref::<String>().unwrap();
+        assert!(msg.contains("averageRequestLatency 不是有限值"), "{msg}");
+        assert!(msg.contains("errorRate 超出范围"), "{msg}");
+    }
+}
@@ -0,0 +1,236 @@
+// tracing 集成
+// span 结束时读取 `stats.*` 字段自动更新统计数据，不需要手动调用 `update_stats`
+//
+// 支持的字段：
+// - `stats.result`：请求结果，如 `success`、`cache`、`timeoutError`（和 `RequestResult::error_kind` 一致），或者通过 `register_custom_result` 注册的名称
+// - `stats.status_code`：HTTP 状态码；没有 `stats.result` 时 2xx 记为成功，其他记为状态码异常
+// - `stats.error`：错误上下文
+// - `stats.retry_count`、`stats.request_bytes`、`stats.response_bytes`、`stats.proxy_id`
+use crate::{
+    custom_result_discriminant, get_now_micros, RequestInfo, RequestResult, RequestStats,
+    SPIDER_STATS,
+};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// span 结束时自动更新统计数据的 tracing layer；可以和其他 layer 一起使用
+pub struct StatsTracingLayer {
+    stats: RequestStats,
+}
+
+impl Default for StatsTracingLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatsTracingLayer {
+    /// 更新全局的爬虫统计
+    pub fn new() -> Self {
+        Self::with_stats(SPIDER_STATS.clone())
+    }
+
+    /// 更新指定的统计实例
+    pub fn with_stats(stats: RequestStats) -> Self {
+        Self { stats }
+    }
+}
+
+// span 中记录的统计字段
+#[derive(Default)]
+struct StatsFields {
+    // span 创建时间（微秒级时间戳）
+    request_time: i64,
+    result: Option<String>,
+    status_code: Option<u16>,
+    error: Option<String>,
+    retry_count: u8,
+    request_bytes: u64,
+    response_bytes: u64,
+    proxy_id: Option<String>,
+    // 是否有 `stats.*` 字段
+    has_stats: bool,
+}
+
+impl StatsFields {
+    /// 转换成请求结果；没有 `stats.result` 和 `stats.status_code` 时返回 None
+    fn request_result(&self) -> Option<RequestResult> {
+        let ctx = self.error.as_deref().map(Box::from);
+        match self.result.as_deref() {
+            Some(result) => parse_result(result, ctx),
+            None => match self.status_code? {
+                200..=299 => Some(RequestResult::Successful),
+                _ => Some(RequestResult::StatusCodeError(ctx)),
+            },
+        }
+    }
+}
+
+/// 解析 `stats.result`；不是内置结果时按 `register_custom_result` 注册的名称查找，未知的结果返回 None
+fn parse_result(result: &str, ctx: Option<Box<str>>) -> Option<RequestResult> {
+    let result = match result {
+        "success" | "successful" => RequestResult::Successful,
+        "cache" | "successfulAndCache" => RequestResult::SuccessfulAndCache,
+        "parseError" => RequestResult::ParseError(ctx),
+        "timeoutError" => RequestResult::TimeoutError(ctx),
+        "connectionError" => RequestResult::ConnectionError(ctx),
+        "statusCodeError" => RequestResult::StatusCodeError(ctx),
+        "proxyError" => RequestResult::ProxyError(ctx),
+        "rateLimited" => RequestResult::RateLimited(ctx),
+        "captchaDetected" => RequestResult::CaptchaDetected(ctx),
+        _ => RequestResult::Custom(custom_result_discriminant(result)?),
+    };
+    Some(result)
+}
+
+impl Visit for StatsFields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        let Some(name) = field.name().strip_prefix("stats.") else {
+            return;
+        };
+        self.has_stats = true;
+        match name {
+            "status_code" => self.status_code = u16::try_from(value).ok(),
+            "retry_count" => self.retry_count = value.min(u8::MAX as u64) as u8,
+            "request_bytes" => self.request_bytes = value,
+            "response_bytes" => self.response_bytes = value,
+            _ => {}
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record_u64(field, value.max(0) as u64);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        let Some(name) = field.name().strip_prefix("stats.") else {
+            return;
+        };
+        self.has_stats = true;
+        match name {
+            "result" => self.result = Some(value.to_string()),
+            "error" => self.error = Some(value.to_string()),
+            "proxy_id" => self.proxy_id = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        // `%value` 形式记录的字段
+        self.record_str(field, &format!("{value:?}"));
+    }
+}
+
+impl<S> Layer<S> for StatsTracingLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = StatsFields {
+            request_time: get_now_micros(),
+            ..Default::default()
+        };
+        attrs.record(&mut fields);
+
+        // 声明了 `stats.*` 字段（包括 `Empty`）的 span 才需要统计
+        let declared = attrs
+            .metadata()
+            .fields()
+            .iter()
+            .any(|field| field.name().starts_with("stats."));
+        if !declared {
+            return;
+        }
+
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(fields);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(fields) = span.extensions_mut().get_mut::<StatsFields>() {
+                values.record(fields);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(fields) = span.extensions_mut().remove::<StatsFields>() else {
+            return;
+        };
+        if !fields.has_stats {
+            return;
+        }
+        let Some(result) = fields.request_result() else {
+            return;
+        };
+
+        self.stats.update_stats_with(
+            fields.request_time,
+            get_now_micros(),
+            fields.status_code.unwrap_or(0),
+            result,
+            &RequestInfo {
+                retry_count: fields.retry_count,
+                request_bytes: fields.request_bytes,
+                response_bytes: fields.response_bytes,
+                proxy_id: fields.proxy_id.as_deref(),
+                ..Default::default()
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StatsBase;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_stats_tracing_layer() {
+        let stats = RequestStats::new();
+        let subscriber =
+            tracing_subscriber::registry().with(StatsTracingLayer::with_stats(stats.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("request", stats.result = "success", stats.status_code = 200)
+                .in_scope(|| {});
+
+            // 结束之前记录结果
+            let span = tracing::info_span!(
+                "request",
+                stats.result = tracing::field::Empty,
+                stats.status_code = tracing::field::Empty,
+                stats.error = tracing::field::Empty,
+            );
+            span.record("stats.status_code", 504);
+            span.record("stats.result", "timeoutError");
+            span.record("stats.error", "read timeout");
+            drop(span);
+
+            // 只有状态码
+            tracing::info_span!("request", stats.status_code = 500).in_scope(|| {});
+
+            // 没有统计字段的 span 不统计
+            tracing::info_span!("other", status_code = 200).in_scope(|| {});
+        });
+
+        let base = StatsBase::default();
+        let d = stats.snapshot(&base);
+        assert_eq!(d.total_requests, 3);
+        assert_eq!(d.exception_types.timeout_error, 1);
+        assert_eq!(d.exception_types.status_code_error, 1);
+        assert_eq!(d.http_status_codes["200"], 1);
+        assert_eq!(d.http_status_codes["504"], 1);
+        assert_eq!(d.recent_errors["timeoutError"], vec!["read timeout"]);
+    }
+}
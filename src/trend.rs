@@ -0,0 +1,145 @@
+// 趋势分析
+// 使用内存中保留的历史统计信息计算关键指标的移动平均，判断指标的变化趋势
+use crate::{get_stats_history, Stats};
+use serde::{Deserialize, Serialize};
+
+// 变化幅度小于该值（百分比）时认为指标稳定
+const STABLE_THRESHOLD_PCT: f64 = 5.0;
+
+// 可以分析趋势的统计字段
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum TrendMetric {
+    // 错误率
+    ErrorRate,
+    // 平均请求延迟（毫秒）
+    AverageLatency,
+    // 请求总次数
+    TotalRequests,
+    // 缓存命中率
+    CacheHitRate,
+}
+
+impl TrendMetric {
+    /// 获取字段的当前值
+    pub fn value(&self, stats: &Stats) -> f64 {
+        match self {
+            TrendMetric::ErrorRate => stats.error_rate(),
+            TrendMetric::AverageLatency => stats.average_latency_ms(),
+            TrendMetric::TotalRequests => stats.total_requests() as f64,
+            TrendMetric::CacheHitRate => stats.cache_hit_rate(),
+        }
+    }
+
+    /// 值越大越好时返回 true；错误率和延迟下降才是改善
+    pub fn higher_is_better(&self) -> bool {
+        match self {
+            TrendMetric::ErrorRate | TrendMetric::AverageLatency => false,
+            TrendMetric::TotalRequests | TrendMetric::CacheHitRate => true,
+        }
+    }
+}
+
+// 指标的变化趋势
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Trend {
+    // 改善
+    Improving,
+    // 稳定；变化幅度小于 5%
+    Stable,
+    // 恶化
+    Degrading,
+}
+
+// 趋势分析结果
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TrendResult {
+    // 最近一个周期的值
+    pub current: f64,
+    // 之前 `window` 个周期的平均值
+    pub moving_avg: f64,
+    // 当前值相对平均值的变化（百分比）
+    pub delta_pct: f64,
+    pub trend: Trend,
+}
+
+/// 使用内存中保留的历史统计信息计算趋势；需要设置 `RequestStatsConfig.history_size`
+/// 最近一个周期和之前最多 `window` 个周期的平均值比较；历史数据少于两个周期时返回 None
+pub fn compute_trend(metric: TrendMetric, window: usize) -> Option<TrendResult> {
+    let history = get_stats_history();
+    let values: Vec<f64> = history.iter().map(|stats| metric.value(stats)).collect();
+    compute_trend_from(&values, metric, window)
+}
+
+/// 按时间顺序的指标值计算趋势
+fn compute_trend_from(values: &[f64], metric: TrendMetric, window: usize) -> Option<TrendResult> {
+    let (current, previous) = values.split_last()?;
+    let previous = &previous[previous.len().saturating_sub(window)..];
+    if previous.is_empty() {
+        return None;
+    }
+
+    let moving_avg = previous.iter().sum::<f64>() / previous.len() as f64;
+    let delta = current - moving_avg;
+    // 平均值为 0 时无法计算比例，有变化时记为 ±100%
+    let delta_pct = if moving_avg != 0.0 {
+        delta / moving_avg.abs() * 100.0
+    } else if delta != 0.0 {
+        100.0_f64.copysign(delta)
+    } else {
+        0.0
+    };
+
+    let trend = if delta_pct.abs() < STABLE_THRESHOLD_PCT {
+        Trend::Stable
+    } else if (delta > 0.0) == metric.higher_is_better() {
+        Trend::Improving
+    } else {
+        Trend::Degrading
+    };
+
+    Some(TrendResult {
+        current: *current,
+        moving_avg,
+        delta_pct,
+        trend,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_trend() {
+        // 历史数据不足
+        assert!(compute_trend_from(&[], TrendMetric::ErrorRate, 3).is_none());
+        assert!(compute_trend_from(&[0.1], TrendMetric::ErrorRate, 3).is_none());
+        assert!(compute_trend_from(&[0.1, 0.2], TrendMetric::ErrorRate, 0).is_none());
+
+        // 只使用最近 2 个周期计算平均值
+        let result = compute_trend_from(&[1.0, 0.2, 0.2, 0.1], TrendMetric::ErrorRate, 2).unwrap();
+        assert!((result.moving_avg - 0.2).abs() < 1e-9);
+        assert!((result.delta_pct + 50.0).abs() < 1e-9);
+        assert_eq!(result.current, 0.1);
+        assert_eq!(result.trend, Trend::Improving);
+
+        let result = compute_trend_from(&[0.1, 0.2], TrendMetric::ErrorRate, 5).unwrap();
+        assert_eq!(result.trend, Trend::Degrading);
+
+        let result = compute_trend_from(&[100.0, 103.0], TrendMetric::TotalRequests, 5).unwrap();
+        assert_eq!(result.trend, Trend::Stable);
+
+        let result = compute_trend_from(&[0.5, 0.8], TrendMetric::CacheHitRate, 5).unwrap();
+        assert_eq!(result.trend, Trend::Improving);
+
+        // 平均值为 0
+        let result = compute_trend_from(&[0.0, 0.0, 0.1], TrendMetric::ErrorRate, 5).unwrap();
+        assert_eq!(result.delta_pct, 100.0);
+        assert_eq!(result.trend, Trend::Degrading);
+        let result = compute_trend_from(&[0.0, 0.0], TrendMetric::ErrorRate, 5).unwrap();
+        assert_eq!(result.trend, Trend::Stable);
+    }
+}
@@ -58,3 +58,86 @@ pub async fn connect_to_ws_with_timeout(
         .and_then(std::convert::identity)?;
     Ok(socket)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        push, send_stats, update_stats, OwnedStats, RequestResult, StatsBase, SPIDER_STATS_PUSH,
+        STATS_SCHEMA_VERSION,
+    };
+    use futures_util::StreamExt;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio::sync::oneshot;
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[tokio::test]
+    async fn test_connect_to_ws_timeout() {
+        // 没有监听的端口
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let url = format!("ws://{addr}");
+        assert!(connect_to_ws_with_timeout(&url, Duration::from_secs(2))
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_push_stats_to_ws() {
+        // 使用全局的推送通道，和其他修改全局状态的测试串行执行
+        let _guard = crate::GLOBAL_STATE_TEST_LOCK.lock();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+
+            // 服务端收到本测试推送的统计信息后通过 oneshot 返回；其他测试同时推送的数据忽略
+            let (tx, rx) = oneshot::channel();
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                while let Some(Ok(msg)) = socket.next().await {
+                    let Message::Text(text) = msg else {
+                        continue;
+                    };
+                    let Ok(received) = serde_json::from_str::<OwnedStats>(&text) else {
+                        continue;
+                    };
+                    if received.base.scraper_name == "ws-push-test" {
+                        let _ = tx.send(received);
+                        break;
+                    }
+                }
+            });
+
+            let handle: Arc<dyn push::PushHandle + Send + Sync> =
+                Arc::from(push::load_push_handle(vec![format!("ws://{addr}")]));
+            assert!(!handle.is_closed());
+            assert!(!push::load_push_handle(vec![]).is_closed());
+            let previous = SPIDER_STATS_PUSH.lock().replace(handle);
+
+            // 推送连接是异步建立的，连接之前发送的消息会丢失，这里重复发送直到服务端收到
+            let sender = tokio::spawn(async move {
+                let base = StatsBase::builder().scraper_name("ws-push-test").build();
+                loop {
+                    update_stats(0, 10, 200, RequestResult::Successful);
+                    send_stats(&base, None);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            });
+
+            let received = tokio::time::timeout(Duration::from_secs(10), rx)
+                .await
+                .unwrap()
+                .unwrap();
+            sender.abort();
+            *SPIDER_STATS_PUSH.lock() = previous;
+
+            assert_eq!(received.schema_version, STATS_SCHEMA_VERSION);
+            assert_eq!(received.base.scraper_name, "ws-push-test");
+        });
+    }
+}